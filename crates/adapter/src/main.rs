@@ -11,6 +11,7 @@ use testing_language_server::spec::AdapterCommands;
 use testing_language_server::spec::DetectWorkspaceArgs;
 use testing_language_server::spec::DiscoverArgs;
 use testing_language_server::spec::RunFileTestArgs;
+use testing_language_server::spec::RunTestArgs;
 pub mod log;
 pub mod model;
 pub mod runner;
@@ -41,6 +42,11 @@ fn handle(commands: AdapterCommands) -> Result<(), LSError> {
             test_kind.run_file_test(RunFileTestArgs { extra, ..commands })?;
             Ok(())
         }
+        AdapterCommands::RunTest(mut commands) => {
+            let (extra, test_kind) = pick_test_from_extra(&mut commands.extra)?;
+            test_kind.run_test(RunTestArgs { extra, ..commands })?;
+            Ok(())
+        }
         AdapterCommands::DetectWorkspace(mut commands) => {
             let (extra, test_kind) = pick_test_from_extra(&mut commands.extra)?;
             test_kind.detect_workspaces(DetectWorkspaceArgs { extra, ..commands })?;
@@ -57,6 +63,8 @@ fn main() {
         io::stderr()
             .write_all(format!("{:#?}", error).as_bytes())
             .unwrap();
+        // The server offers the log of a run which failed
+        std::process::exit(1);
     }
 }
 
@@ -10,6 +10,7 @@ use testing_language_server::error::LSError;
 use testing_language_server::spec::DetectWorkspaceArgs;
 use testing_language_server::spec::DiscoverArgs;
 use testing_language_server::spec::RunFileTestArgs;
+use testing_language_server::spec::RunTestArgs;
 
 use crate::runner::jest::JestRunner;
 
@@ -51,6 +52,19 @@ impl Runner for AvailableTestKind {
         }
     }
 
+    fn run_test(&self, args: RunTestArgs) -> Result<(), LSError> {
+        match self {
+            AvailableTestKind::CargoTest(runner) => runner.run_test(args),
+            AvailableTestKind::CargoNextest(runner) => runner.run_test(args),
+            AvailableTestKind::Jest(runner) => runner.run_test(args),
+            AvailableTestKind::Deno(runner) => runner.run_test(args),
+            AvailableTestKind::GoTest(runner) => runner.run_test(args),
+            AvailableTestKind::Vitest(runner) => runner.run_test(args),
+            AvailableTestKind::Phpunit(runner) => runner.run_test(args),
+            AvailableTestKind::NodeTest(runner) => runner.run_test(args),
+        }
+    }
+
     fn detect_workspaces(&self, args: DetectWorkspaceArgs) -> Result<(), LSError> {
         match self {
             AvailableTestKind::CargoTest(runner) => runner.detect_workspaces(args),
@@ -86,5 +100,6 @@ impl FromStr for AvailableTestKind {
 pub trait Runner {
     fn discover(&self, args: DiscoverArgs) -> Result<(), LSError>;
     fn run_file_test(&self, args: RunFileTestArgs) -> Result<(), LSError>;
+    fn run_test(&self, args: RunTestArgs) -> Result<(), LSError>;
     fn detect_workspaces(&self, args: DetectWorkspaceArgs) -> Result<(), LSError>;
 }
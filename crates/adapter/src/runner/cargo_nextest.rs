@@ -1,26 +1,128 @@
 use crate::runner::util::send_stdout;
+use regex::Regex;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process::Output;
 use std::str::FromStr;
+use std::sync::LazyLock;
 use testing_language_server::error::LSError;
+use testing_language_server::spec::AdapterEvent;
 use testing_language_server::spec::DetectWorkspaceResult;
 use testing_language_server::spec::RunFileTestResult;
 
 use testing_language_server::spec::DiscoverResult;
-use testing_language_server::spec::FoundFileTests;
 use testing_language_server::spec::TestItem;
+use testing_language_server::spec::TestResult;
+use testing_language_server::util::OutputStream;
 
 use crate::model::Runner;
 
 use super::util::detect_workspaces_from_file_list;
+use super::util::discover_rust_file;
 use super::util::discover_rust_tests;
+use super::util::output_with_lines;
 use super::util::parse_cargo_diagnostics;
+use super::util::parse_cargo_test_line;
+use super::util::rust_exact_test_filters;
+use super::util::rust_test_full_id;
+use super::util::send_event;
 use super::util::write_result_log;
 
 fn detect_workspaces(file_paths: &[String]) -> DetectWorkspaceResult {
     detect_workspaces_from_file_list(file_paths, &["Cargo.toml".to_string()])
 }
 
+/// Turns the status lines nextest writes while running into adapter events
+struct NextestEvents<'a> {
+    /// Discovered tests by their full libtest name
+    full_ids: HashMap<String, &'a TestItem>,
+    finished: HashSet<String>,
+}
+
+impl<'a> NextestEvents<'a> {
+    fn new(workspace_root: &str, test_items: &'a [TestItem]) -> Self {
+        let workspace_root = Path::new(workspace_root);
+        Self {
+            full_ids: test_items
+                .iter()
+                .map(|item| (rust_test_full_id(workspace_root, item), item))
+                .collect(),
+            finished: HashSet::new(),
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<AdapterEvent> {
+        // Example:
+        //     Starting 3 tests across 2 binaries
+        static STARTING_RE: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"^\s*Starting (\d+) tests?").unwrap());
+        if let Some(m) = STARTING_RE.captures(line) {
+            return Some(AdapterEvent::Plan {
+                total: m[1].parse().ok()?,
+            });
+        }
+        let (name, status, duration) = parse_cargo_test_line(line)?;
+        let test_item = self.full_ids.get(name)?;
+        // The summary repeats the failed tests
+        if !self.finished.insert(name.to_string()) {
+            return None;
+        }
+        Some(AdapterEvent::TestFinished(TestResult {
+            id: test_item.id.clone(),
+            path: test_item.path.clone(),
+            status,
+            duration,
+            output: None,
+        }))
+    }
+}
+
+fn run_cargo_nextest(
+    workspace_root: &str,
+    file_paths: &[String],
+    discovered_tests: &[TestItem],
+    extra: &[String],
+    test_args: &[String],
+) -> Result<RunFileTestResult, LSError> {
+    let mut events = NextestEvents::new(workspace_root, discovered_tests);
+    let output = output_with_lines(
+        std::process::Command::new("cargo")
+            .current_dir(workspace_root)
+            .arg("nextest")
+            .arg("run")
+            .arg("--workspace")
+            .arg("--no-fail-fast")
+            .args(extra)
+            .arg("--")
+            .args(test_args),
+        |stream, line| {
+            if stream == OutputStream::Stderr {
+                events.parse_line(line).iter().for_each(send_event);
+            }
+        },
+    )
+    .unwrap();
+    write_result_log("cargo_nextest.log", &output)?;
+    let Output {
+        stdout,
+        stderr,
+        status,
+    } = output;
+    let unexpected_status_code = status.code().map(|code| code != 100);
+    if stdout.is_empty() && !stderr.is_empty() && unexpected_status_code.unwrap_or(false) {
+        return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
+    }
+    let test_result = String::from_utf8(stderr)?;
+    Ok(parse_cargo_diagnostics(
+        &test_result,
+        PathBuf::from_str(workspace_root).unwrap(),
+        file_paths,
+        discovered_tests,
+    ))
+}
+
 #[derive(Eq, PartialEq, Hash, Debug)]
 pub struct CargoNextestRunner;
 
@@ -31,11 +133,7 @@ impl Runner for CargoNextestRunner {
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
 
         for file_path in file_paths {
-            let tests = discover_rust_tests(&file_path)?;
-            discover_results.data.push(FoundFileTests {
-                tests,
-                path: file_path,
-            });
+            discover_results.data.push(discover_rust_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -57,36 +155,35 @@ impl Runner for CargoNextestRunner {
             .iter()
             .map(|item| item.id.clone())
             .collect::<Vec<String>>();
-        let workspace_root = args.workspace;
-        let test_result = std::process::Command::new("cargo")
-            .current_dir(&workspace_root)
-            .arg("nextest")
-            .arg("run")
-            .arg("--workspace")
-            .arg("--no-fail-fast")
-            .args(args.extra)
-            .arg("--")
-            .args(&test_ids)
-            .output()
-            .unwrap();
-        let output = test_result;
-        write_result_log("cargo_nextest.log", &output)?;
-        let Output {
-            stdout,
-            stderr,
-            status,
-        } = output;
-        let unexpected_status_code = status.code().map(|code| code != 100);
-        if stdout.is_empty() && !stderr.is_empty() && unexpected_status_code.unwrap_or(false) {
-            return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
-        }
-        let test_result = String::from_utf8(stderr)?;
-        let diagnostics: RunFileTestResult = parse_cargo_diagnostics(
-            &test_result,
-            PathBuf::from_str(&workspace_root).unwrap(),
+        let diagnostics = run_cargo_nextest(
+            &args.workspace,
             &file_paths,
             &discovered_tests,
-        );
+            &args.extra,
+            &test_ids,
+        )?;
+        send_stdout(&diagnostics)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        let file_paths = args.file_paths;
+        let discovered_tests: Vec<TestItem> = file_paths
+            .iter()
+            .map(|path| discover_rust_tests(path))
+            .filter_map(Result::ok)
+            .flatten()
+            .collect::<Vec<_>>();
+        let workspace_root = PathBuf::from_str(&args.workspace).unwrap();
+        let test_args = rust_exact_test_filters(&workspace_root, &discovered_tests, &args.test_ids);
+        let diagnostics = run_cargo_nextest(
+            &args.workspace,
+            &file_paths,
+            &discovered_tests,
+            &args.extra,
+            &test_args,
+        )?;
         send_stdout(&diagnostics)?;
         Ok(())
     }
@@ -106,7 +203,7 @@ impl Runner for CargoNextestRunner {
 #[cfg(test)]
 mod tests {
     use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
-    use testing_language_server::spec::{FileDiagnostics, TestItem};
+    use testing_language_server::spec::{FileDiagnostics, TestItem, TestStatus};
 
     use crate::runner::util::MAX_CHAR_LENGTH;
 
@@ -191,11 +288,99 @@ note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
                         ..Diagnostic::default()
                     }]
                 }],
-                messages: vec!()
+                messages: vec!(),
+                tests: vec![],
             }
         )
     }
 
+    #[test]
+    fn parse_nextest_results_with_durations() {
+        let fixture = r#"
+    Starting 3 tests across 1 binary (1 test skipped)
+        PASS [   0.012s] rocks-lib tests::success
+        FAIL [   1.500s] rocks-lib tests::fail
+     TIMEOUT [  60.001s] rocks-lib tests::slow
+------------
+     Summary [  61.513s] 3 tests run: 1 passed, 1 failed, 1 timed out, 1 skipped
+        FAIL [   1.500s] rocks-lib tests::fail
+"#;
+        let path = "/home/example/projects/rocks-lib/src/lib.rs".to_string();
+        let test_item = |id: &str| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: path.clone(),
+            start_position: Range::default(),
+            end_position: Range::default(),
+        };
+        let result = parse_cargo_diagnostics(
+            fixture,
+            PathBuf::from_str("/home/example/projects/rocks-lib").unwrap(),
+            std::slice::from_ref(&path),
+            &[
+                test_item("tests::success"),
+                test_item("tests::fail"),
+                test_item("tests::slow"),
+            ],
+        );
+        let summary = result
+            .tests
+            .iter()
+            .map(|test| (test.id.as_str(), test.status, test.duration))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("tests::success", TestStatus::Passed, Some(12)),
+                ("tests::fail", TestStatus::Failed, Some(1500)),
+                ("tests::slow", TestStatus::Errored, Some(60001)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_nextest_events() {
+        let path = "/home/example/projects/rocks-lib/src/lib.rs".to_string();
+        let test_items = ["tests::success", "tests::fail"].map(|id| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: path.clone(),
+            start_position: Range::default(),
+            end_position: Range::default(),
+        });
+        let mut events = NextestEvents::new("/home/example/projects/rocks-lib", &test_items);
+        let streamed = [
+            "    Starting 2 tests across 1 binary",
+            "        PASS [   0.012s] rocks-lib tests::success",
+            "        FAIL [   1.500s] rocks-lib tests::fail",
+            "     Summary [   1.513s] 2 tests run: 1 passed, 1 failed",
+            "        FAIL [   1.500s] rocks-lib tests::fail",
+        ]
+        .into_iter()
+        .filter_map(|line| events.parse_line(line))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            streamed,
+            vec![
+                AdapterEvent::Plan { total: 2 },
+                AdapterEvent::TestFinished(TestResult {
+                    id: "tests::success".to_string(),
+                    path: path.clone(),
+                    status: TestStatus::Passed,
+                    duration: Some(12),
+                    output: None,
+                }),
+                AdapterEvent::TestFinished(TestResult {
+                    id: "tests::fail".to_string(),
+                    path: path.clone(),
+                    status: TestStatus::Failed,
+                    duration: Some(1500),
+                    output: None,
+                }),
+            ]
+        );
+    }
+
     #[test]
     fn test_discover() {
         let file_path = "../../demo/rust/src/lib.rs";
@@ -7,20 +7,53 @@ use testing_language_server::spec::DetectWorkspaceResult;
 use testing_language_server::spec::RunFileTestResult;
 
 use testing_language_server::spec::DiscoverResult;
-use testing_language_server::spec::FoundFileTests;
 use testing_language_server::spec::TestItem;
 
 use crate::model::Runner;
 
 use super::util::detect_workspaces_from_file_list;
+use super::util::discover_rust_file;
 use super::util::discover_rust_tests;
 use super::util::parse_cargo_diagnostics;
+use super::util::rust_exact_test_filters;
 use super::util::write_result_log;
 
 fn detect_workspaces(file_paths: &[String]) -> DetectWorkspaceResult {
     detect_workspaces_from_file_list(file_paths, &["Cargo.toml".to_string()])
 }
 
+fn run_cargo_test(
+    workspace_root: &str,
+    file_paths: &[String],
+    discovered_tests: &[TestItem],
+    extra: &[String],
+    test_args: &[String],
+) -> Result<RunFileTestResult, LSError> {
+    let output = std::process::Command::new("cargo")
+        .current_dir(workspace_root)
+        .arg("test")
+        .args(extra)
+        .arg("--")
+        .args(test_args)
+        .output()
+        .unwrap();
+    write_result_log("cargo_test.log", &output)?;
+    let Output { stdout, stderr, .. } = output;
+    if stdout.is_empty() {
+        return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
+    }
+    // When `--nocapture` option is set, stderr has some important information
+    // to parse test result
+    let test_result = String::from_utf8(stderr)? + &String::from_utf8(stdout)?;
+
+    Ok(parse_cargo_diagnostics(
+        &test_result,
+        PathBuf::from_str(workspace_root).unwrap(),
+        file_paths,
+        discovered_tests,
+    ))
+}
+
 #[derive(Eq, PartialEq, Hash, Debug)]
 pub struct CargoTestRunner;
 
@@ -31,11 +64,7 @@ impl Runner for CargoTestRunner {
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
 
         for file_path in file_paths {
-            let tests = discover_rust_tests(&file_path)?;
-            discover_results.data.push(FoundFileTests {
-                tests,
-                path: file_path,
-            });
+            discover_results.data.push(discover_rust_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -57,31 +86,35 @@ impl Runner for CargoTestRunner {
             .iter()
             .map(|item| item.id.clone())
             .collect::<Vec<String>>();
-        let workspace_root = args.workspace;
-        let test_result = std::process::Command::new("cargo")
-            .current_dir(&workspace_root)
-            .arg("test")
-            .args(args.extra)
-            .arg("--")
-            .args(&test_ids)
-            .output()
-            .unwrap();
-        let output = test_result;
-        write_result_log("cargo_test.log", &output)?;
-        let Output { stdout, stderr, .. } = output;
-        if stdout.is_empty() {
-            return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
-        }
-        // When `--nocapture` option is set, stderr has some important information
-        // to parse test result
-        let test_result = String::from_utf8(stderr)? + &String::from_utf8(stdout)?;
+        let diagnostics = run_cargo_test(
+            &args.workspace,
+            &file_paths,
+            &discovered_tests,
+            &args.extra,
+            &test_ids,
+        )?;
+        send_stdout(&diagnostics)?;
+        Ok(())
+    }
 
-        let diagnostics: RunFileTestResult = parse_cargo_diagnostics(
-            &test_result,
-            PathBuf::from_str(&workspace_root).unwrap(),
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        let file_paths = args.file_paths;
+        let discovered_tests: Vec<TestItem> = file_paths
+            .iter()
+            .map(|path| discover_rust_tests(path))
+            .filter_map(Result::ok)
+            .flatten()
+            .collect::<Vec<_>>();
+        let workspace_root = PathBuf::from_str(&args.workspace).unwrap();
+        let test_args = rust_exact_test_filters(&workspace_root, &discovered_tests, &args.test_ids);
+        let diagnostics = run_cargo_test(
+            &args.workspace,
             &file_paths,
             &discovered_tests,
-        );
+            &args.extra,
+            &test_args,
+        )?;
         send_stdout(&diagnostics)?;
         Ok(())
     }
@@ -102,7 +135,10 @@ impl Runner for CargoTestRunner {
 mod tests {
     use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
     use testing_language_server::spec::FileDiagnostics;
+    use testing_language_server::spec::TestResult;
+    use testing_language_server::spec::TestStatus;
 
+    use crate::runner::util::rust_test_full_id;
     use crate::runner::util::MAX_CHAR_LENGTH;
 
     use super::*;
@@ -186,17 +222,147 @@ note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
                         ..Diagnostic::default()
                     }]
                 }],
-                messages: vec![]
+                messages: vec![],
+                tests: vec![],
             }
         )
     }
 
+    #[test]
+    fn parse_passed_failed_and_ignored_tests() {
+        let fixture = r#"
+running 3 tests
+test tests::ignored ... ignored
+test tests::success ... ok
+test tests::fail ... FAILED
+
+failures:
+
+---- tests::fail stdout ----
+thread 'tests::fail' panicked at src/lib.rs:22:9:
+assertion failed: false
+
+failures:
+    tests::fail
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.00s
+"#;
+        let path = "/home/example/projects/rocks-lib/src/lib.rs".to_string();
+        let test_item = |id: &str| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: path.clone(),
+            start_position: Range::default(),
+            end_position: Range::default(),
+        };
+        let test_items = vec![
+            test_item("tests::success"),
+            test_item("tests::fail"),
+            test_item("tests::ignored"),
+        ];
+        let result = parse_cargo_diagnostics(
+            fixture,
+            PathBuf::from_str("/home/example/projects/rocks-lib").unwrap(),
+            std::slice::from_ref(&path),
+            &test_items,
+        );
+        let test_result = |id: &str, status: TestStatus, output: Option<&str>| TestResult {
+            id: id.to_string(),
+            path: path.clone(),
+            status,
+            duration: None,
+            output: output.map(String::from),
+        };
+        assert_eq!(
+            result.tests,
+            vec![
+                test_result("tests::ignored", TestStatus::Skipped, None),
+                test_result("tests::success", TestStatus::Passed, None),
+                test_result(
+                    "tests::fail",
+                    TestStatus::Failed,
+                    Some("assertion failed: false\n")
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn test_discover() {
         let file_path = "../../demo/rust/src/lib.rs";
         discover_rust_tests(file_path).unwrap();
     }
 
+    #[test]
+    fn discover_namespaces() {
+        let found = discover_rust_file("../../demo/rust/src/lib.rs").unwrap();
+        let namespaces = found
+            .namespaces
+            .iter()
+            .map(|namespace| {
+                (
+                    namespace.id.as_str(),
+                    namespace.name.as_str(),
+                    namespace.start_position.start.line,
+                    namespace.end_position.end.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            namespaces,
+            vec![
+                ("tests", "tests", 5, 64),
+                ("tests::nested_namespace", "nested_namespace", 28, 54),
+                (
+                    "tests::nested_namespace::nested_nested_namespace",
+                    "nested_nested_namespace",
+                    41,
+                    53
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rust_test_full_id() {
+        let workspace_root = PathBuf::from("/home/example/projects/rocks-lib");
+        let test_item = |path: &str| TestItem {
+            id: "tests::parse_dependency".to_string(),
+            name: "tests::parse_dependency".to_string(),
+            path: path.to_string(),
+            start_position: Range::default(),
+            end_position: Range::default(),
+        };
+        assert_eq!(
+            rust_test_full_id(
+                &workspace_root,
+                &test_item("/home/example/projects/rocks-lib/src/rocks/dependency.rs")
+            ),
+            "rocks::dependency::tests::parse_dependency"
+        );
+        assert_eq!(
+            rust_test_full_id(
+                &workspace_root,
+                &test_item("/home/example/projects/rocks-lib/src/rocks/mod.rs")
+            ),
+            "rocks::tests::parse_dependency"
+        );
+        assert_eq!(
+            rust_test_full_id(
+                &workspace_root,
+                &test_item("/home/example/projects/rocks-lib/src/lib.rs")
+            ),
+            "tests::parse_dependency"
+        );
+        assert_eq!(
+            rust_test_full_id(
+                &workspace_root,
+                &test_item("/home/example/projects/rocks-lib/tests/integration.rs")
+            ),
+            "tests::parse_dependency"
+        );
+    }
+
     #[test]
     fn test_detect_workspaces() {
         let current_dir = std::env::current_dir().unwrap();
@@ -6,6 +6,7 @@ use lsp_types::Position;
 use lsp_types::Range;
 use regex::Regex;
 use std::collections::HashMap;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process::Output;
 use std::str::FromStr;
@@ -16,13 +17,16 @@ use testing_language_server::spec::DiscoverResult;
 use testing_language_server::spec::FileDiagnostics;
 use testing_language_server::spec::FoundFileTests;
 use testing_language_server::spec::RunFileTestResult;
-use testing_language_server::spec::TestItem;
+use testing_language_server::spec::TestResult;
+use testing_language_server::spec::TestStatus;
 
 use crate::model::Runner;
 
 use super::util::clean_ansi;
 use super::util::detect_workspaces_from_file_list;
-use super::util::discover_with_treesitter;
+use super::util::discover_file_with_treesitter;
+use super::util::push_test_result;
+use super::util::test_ids_pattern;
 use super::util::write_result_log;
 use super::util::MAX_CHAR_LENGTH;
 
@@ -40,6 +44,90 @@ fn get_position_from_output(line: &str) -> Option<(String, u32, u32)> {
     }
 }
 
+/// Collects per-test results from the `<name> ... ok (1ms)` lines.
+/// Steps are indented under their parent test, and their ids are joined with `::`.
+fn parse_test_results(contents: &str, workspace_root: &Path) -> Vec<TestResult> {
+    let file_re = Regex::new(r"^running \d+ tests? from (.+)$").unwrap();
+    let test_re =
+        Regex::new(r"^(\s*)(.+?) \.\.\.(?: (ok|FAILED|ignored)(?: \(([\d.]+)(ms|s)\))?)?$")
+            .unwrap();
+    let mut results: Vec<TestResult> = vec![];
+    let mut errors: HashMap<String, String> = HashMap::new();
+    let mut error_name: Option<String> = None;
+    let mut file_path = String::new();
+    let mut parents: Vec<String> = vec![];
+    let mut in_errors = false;
+    for line in contents.lines() {
+        if line.contains(" ERRORS ") {
+            in_errors = true;
+            continue;
+        } else if line.contains(" FAILURES ") {
+            break;
+        }
+        if in_errors {
+            if let Some((name, _)) = line
+                .split_once(" => ")
+                .filter(|_| get_position_from_output(line).is_some())
+            {
+                error_name = Some(name.to_string());
+            } else if let Some(name) = &error_name {
+                let error = errors.entry(name.clone()).or_default();
+                error.push_str(line);
+                error.push('\n');
+            }
+            continue;
+        }
+        if let Some(m) = file_re.captures(line) {
+            file_path = resolve_path(workspace_root, &m[1])
+                .to_str()
+                .unwrap()
+                .to_string();
+            parents.clear();
+            continue;
+        }
+        let Some(m) = test_re.captures(line) else {
+            continue;
+        };
+        parents.truncate(m[1].len() / 2);
+        let name = m[2].to_string();
+        let Some(status) = m.get(3) else {
+            // A test with steps, whose result follows them
+            parents.push(name);
+            continue;
+        };
+        let status = match status.as_str() {
+            "ok" => TestStatus::Passed,
+            "FAILED" => TestStatus::Failed,
+            _ => TestStatus::Skipped,
+        };
+        let duration = m
+            .get(4)
+            .and_then(|value| value.as_str().parse::<f64>().ok());
+        let duration = match m.get(5).map(|unit| unit.as_str()) {
+            Some("s") => duration.map(|seconds| seconds * 1000.0),
+            _ => duration,
+        };
+        let mut names = parents.clone();
+        names.push(name);
+        push_test_result(
+            &mut results,
+            TestResult {
+                id: names.join("::"),
+                path: file_path.clone(),
+                status,
+                duration: duration.map(|duration| duration.round() as u64),
+                output: None,
+            },
+        );
+    }
+    for result in results.iter_mut() {
+        if result.status == TestStatus::Failed {
+            result.output = errors.get(&result.id).map(|error| error.trim().to_string());
+        }
+    }
+    results
+}
+
 fn parse_diagnostics(
     contents: &str,
     workspace_root: PathBuf,
@@ -59,7 +147,7 @@ fn parse_diagnostics(
             continue;
         }
         if let Some(position) = get_position_from_output(line) {
-            if file_name.is_some() {
+            if let Some(file_name) = &file_name {
                 let diagnostic = Diagnostic {
                     range: Range {
                         start: Position {
@@ -75,7 +163,7 @@ fn parse_diagnostics(
                     severity: Some(DiagnosticSeverity::ERROR),
                     ..Diagnostic::default()
                 };
-                let file_path = resolve_path(&workspace_root, file_name.as_ref().unwrap())
+                let file_path = resolve_path(&workspace_root, file_name)
                     .to_str()
                     .unwrap()
                     .to_string();
@@ -95,6 +183,7 @@ fn parse_diagnostics(
             .map(|(path, diagnostics)| FileDiagnostics { path, diagnostics })
             .collect(),
         messages: vec![],
+        tests: parse_test_results(&contents, &workspace_root),
     })
 }
 
@@ -102,7 +191,7 @@ fn detect_workspaces(file_paths: Vec<String>) -> DetectWorkspaceResult {
     detect_workspaces_from_file_list(&file_paths, &["deno.json".to_string()])
 }
 
-fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+fn discover_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/MarkEmmons/neotest-deno/blob/7136b9342aeecb675c7c16a0bde327d7fcb00a1c/lua/neotest-deno/init.lua#L93
     // license: https://github.com/MarkEmmons/neotest-deno/blob/main/LICENSE
     let query = r#"
@@ -154,7 +243,32 @@ fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
 	]
 ) @test.definition
         "#;
-    discover_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
+}
+
+fn run_deno_test(
+    workspace: &str,
+    file_paths: &[String],
+    filter_args: &[String],
+) -> Result<RunFileTestResult, LSError> {
+    let output = std::process::Command::new("deno")
+        .current_dir(workspace)
+        .args(["test", "--no-prompt"])
+        .args(filter_args)
+        .args(file_paths)
+        .output()
+        .unwrap();
+    write_result_log("deno.log", &output)?;
+    let Output { stdout, stderr, .. } = output;
+    if stdout.is_empty() {
+        return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
+    }
+    let test_result = String::from_utf8(stdout)?;
+    parse_diagnostics(
+        &test_result,
+        PathBuf::from_str(workspace).unwrap(),
+        file_paths,
+    )
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -166,10 +280,7 @@ impl Runner for DenoRunner {
         let file_paths = args.file_paths;
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
         for file_path in file_paths {
-            discover_results.data.push(FoundFileTests {
-                tests: discover(&file_path)?,
-                path: file_path,
-            })
+            discover_results.data.push(discover_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -180,25 +291,27 @@ impl Runner for DenoRunner {
         &self,
         args: testing_language_server::spec::RunFileTestArgs,
     ) -> Result<(), LSError> {
-        let file_paths = args.file_paths;
-        let workspace = args.workspace;
-        let output = std::process::Command::new("deno")
-            .current_dir(&workspace)
-            .args(["test", "--no-prompt"])
-            .args(&file_paths)
-            .output()
-            .unwrap();
-        write_result_log("deno.log", &output)?;
-        let Output { stdout, stderr, .. } = output;
-        if stdout.is_empty() {
-            return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
-        }
-        let test_result = String::from_utf8(stdout)?;
-        let diagnostics: RunFileTestResult = parse_diagnostics(
-            &test_result,
-            PathBuf::from_str(&workspace).unwrap(),
-            &file_paths,
-        )?;
+        let diagnostics = run_deno_test(&args.workspace, &args.file_paths, &[])?;
+        send_stdout(&diagnostics)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        // `--filter` only applies to top-level tests, so BDD `it`s run with their `describe`
+        let test_ids = args
+            .test_ids
+            .iter()
+            .map(|id| {
+                let top_level = id.split("::").next().unwrap_or(id);
+                top_level.trim_matches(['"', '\'', '`']).to_string()
+            })
+            .collect::<Vec<_>>();
+        let filter_args = [
+            "--filter".to_string(),
+            format!("/{}/", test_ids_pattern(&test_ids, "::")),
+        ];
+        let diagnostics = run_deno_test(&args.workspace, &args.file_paths, &filter_args)?;
         send_stdout(&diagnostics)?;
         Ok(())
     }
@@ -221,6 +334,7 @@ mod tests {
     use std::env::current_dir;
 
     use super::*;
+    use testing_language_server::spec::TestItem;
 
     #[test]
     fn test_parse_diagnostics() {
@@ -233,6 +347,49 @@ mod tests {
         let diagnostics =
             parse_diagnostics(&test_result, workspace, &[target_file_path.to_string()]).unwrap();
         assert_eq!(diagnostics.data.len(), 1);
+        let summary = diagnostics
+            .tests
+            .iter()
+            .map(|test| (test.id.as_str(), test.path.as_str(), test.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("addTest", target_file_path, TestStatus::Passed),
+                ("fail1", target_file_path, TestStatus::Failed),
+            ]
+        );
+        assert!(diagnostics.tests[1]
+            .output
+            .as_ref()
+            .unwrap()
+            .contains("AssertionError: Values are not equal."));
+    }
+
+    #[test]
+    fn parse_test_steps() {
+        let contents = r#"running 2 tests from ./steps_test.ts
+outer ...
+  inner ok ... ok (2ms)
+  inner ignored ... ignored (0ms)
+outer ... ok (1.5s)
+plain ... ok (3ms)
+"#;
+        let results = parse_test_results(contents, Path::new("/home/demo/deno"));
+        let summary = results
+            .iter()
+            .map(|test| (test.id.as_str(), test.status, test.duration))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("outer::inner ok", TestStatus::Passed, Some(2)),
+                ("outer::inner ignored", TestStatus::Skipped, Some(0)),
+                ("outer", TestStatus::Passed, Some(1500)),
+                ("plain", TestStatus::Passed, Some(3)),
+            ]
+        );
+        assert_eq!(results[0].path, "/home/demo/deno/steps_test.ts");
     }
 
     #[test]
@@ -255,7 +412,7 @@ mod tests {
     fn test_discover() {
         let file_path = current_dir().unwrap().join("../../demo/deno/main_test.ts");
         let file_path = file_path.to_str().unwrap();
-        let test_items = discover(file_path).unwrap();
+        let test_items = discover_file(file_path).unwrap().tests;
         assert_eq!(test_items.len(), 3);
         assert_eq!(
             test_items,
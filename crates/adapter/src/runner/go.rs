@@ -12,14 +12,21 @@ use std::path::PathBuf;
 use std::process::Output;
 use std::str::FromStr;
 use testing_language_server::error::LSError;
+use testing_language_server::spec::AdapterEvent;
 use testing_language_server::spec::DiscoverResult;
 use testing_language_server::spec::FileDiagnostics;
 use testing_language_server::spec::FoundFileTests;
 use testing_language_server::spec::RunFileTestResult;
 use testing_language_server::spec::TestItem;
+use testing_language_server::spec::TestResult;
+use testing_language_server::spec::TestStatus;
+use testing_language_server::util::OutputStream;
 
 use super::util::detect_workspaces_from_file_list;
-use super::util::discover_with_treesitter;
+use super::util::discover_file_with_treesitter;
+use super::util::output_with_lines;
+use super::util::push_test_result;
+use super::util::send_event;
 use super::util::write_result_log;
 use super::util::MAX_CHAR_LENGTH;
 
@@ -28,9 +35,13 @@ use super::util::MAX_CHAR_LENGTH;
 enum Action {
     Start,
     Run,
+    Pause,
+    Cont,
     Output,
+    Bench,
     Fail,
     Pass,
+    Skip,
 }
 
 #[allow(dead_code)]
@@ -42,6 +53,8 @@ struct TestResultLine {
     package: String,
     test: Option<String>,
     output: Option<String>,
+    /// Seconds
+    elapsed: Option<f64>,
 }
 
 fn get_position_from_output(output: &str) -> Option<(String, u32)> {
@@ -61,14 +74,145 @@ fn get_log_from_output(output: &str) -> String {
     output.replace("        ", "")
 }
 
+/// Finds the discovered test for a `go test` name.
+/// Subtests are reported as `TestName/sub_test` and discovered as the quoted `t.Run` name.
+fn find_test_item<'a>(test_items: &'a [TestItem], name: &str) -> Option<&'a TestItem> {
+    match name.rsplit_once('/') {
+        Some((_, subtest)) => test_items
+            .iter()
+            .find(|item| item.id.trim_matches('"').replace(' ', "_") == subtest),
+        None => test_items.iter().find(|item| item.id == name),
+    }
+}
+
+/// Turns the lines of `go test -json` into adapter events while the tests run
+struct GoEvents<'a> {
+    workspace_root: PathBuf,
+    file_paths: &'a [String],
+    test_items: &'a [TestItem],
+    /// Output of the running tests by name
+    outputs: HashMap<String, String>,
+}
+
+impl<'a> GoEvents<'a> {
+    fn new(workspace_root: &str, file_paths: &'a [String], test_items: &'a [TestItem]) -> Self {
+        Self {
+            workspace_root: PathBuf::from(workspace_root),
+            file_paths,
+            test_items,
+            outputs: HashMap::new(),
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Vec<AdapterEvent> {
+        let Ok(TestResultLine {
+            action,
+            test: Some(test),
+            output,
+            elapsed,
+            ..
+        }) = serde_json::from_str::<TestResultLine>(line)
+        else {
+            return vec![];
+        };
+        let test_item = find_test_item(self.test_items, &test);
+        match action {
+            Action::Run => test_item
+                .map(|test_item| AdapterEvent::TestStarted {
+                    id: test_item.id.clone(),
+                    path: test_item.path.clone(),
+                })
+                .into_iter()
+                .collect(),
+            Action::Output => {
+                self.outputs
+                    .entry(test)
+                    .or_default()
+                    .push_str(&output.unwrap_or_default());
+                vec![]
+            }
+            Action::Pass | Action::Fail | Action::Skip => {
+                let output = self.outputs.remove(&test).unwrap_or_default();
+                let Some(test_item) = test_item else {
+                    return vec![];
+                };
+                let mut events = vec![];
+                let status = match action {
+                    Action::Pass => TestStatus::Passed,
+                    Action::Fail => TestStatus::Failed,
+                    _ => TestStatus::Skipped,
+                };
+                if status == TestStatus::Failed {
+                    events.extend(self.diagnostics_from_output(&output));
+                }
+                events.push(AdapterEvent::TestFinished(TestResult {
+                    id: test_item.id.clone(),
+                    path: test_item.path.clone(),
+                    status,
+                    duration: elapsed.map(|seconds| (seconds * 1000.0).round() as u64),
+                    output: Some(output).filter(|_| status != TestStatus::Passed),
+                }));
+                events
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Diagnostics at the `file_test.go:12:` lines of a failed test,
+    /// with the lines logged after each of them as the message
+    fn diagnostics_from_output(&self, output: &str) -> Vec<AdapterEvent> {
+        let mut diagnostics: Vec<(String, Diagnostic)> = vec![];
+        for line in output.lines() {
+            if let Some((file_name, lnum)) = get_position_from_output(line) {
+                let diagnostic = Diagnostic {
+                    range: Range {
+                        start: Position {
+                            line: lnum,
+                            character: 1,
+                        },
+                        end: Position {
+                            line: lnum,
+                            character: MAX_CHAR_LENGTH,
+                        },
+                    },
+                    message: String::new(),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    ..Diagnostic::default()
+                };
+                let file_path = self.workspace_root.join(file_name);
+                diagnostics.push((file_path.to_string_lossy().to_string(), diagnostic));
+            } else if let Some((_, diagnostic)) = diagnostics.last_mut() {
+                let trimmed = line.trim_start();
+                if !trimmed.starts_with("=== ") && !trimmed.starts_with("--- ") {
+                    diagnostic.message += &get_log_from_output(line);
+                    diagnostic.message.push('\n');
+                }
+            }
+        }
+        diagnostics
+            .into_iter()
+            .filter(|(path, _)| self.file_paths.contains(path))
+            .map(|(path, diagnostic)| {
+                AdapterEvent::FileDiagnostics(FileDiagnostics {
+                    path,
+                    diagnostics: vec![diagnostic],
+                })
+            })
+            .collect()
+    }
+}
+
 fn parse_diagnostics(
     contents: &str,
     workspace_root: PathBuf,
     file_paths: &[String],
+    test_items: &[TestItem],
 ) -> Result<RunFileTestResult, LSError> {
     let contents = contents.replace("\r\n", "\n");
     let lines = contents.lines();
     let mut result_map: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+    let mut test_results: Vec<TestResult> = vec![];
+    let mut test_outputs: HashMap<String, String> = HashMap::new();
     let mut file_name: Option<String> = None;
     let mut lnum: Option<u32> = None;
     let mut message = String::new();
@@ -82,6 +226,12 @@ fn parse_diagnostics(
             }
             Action::Output => {
                 let output = &value.output.unwrap();
+                if let Some(test) = &value.test {
+                    test_outputs
+                        .entry(test.clone())
+                        .or_default()
+                        .push_str(output);
+                }
                 if let Some((detected_file_name, detected_lnum)) = get_position_from_output(output)
                 {
                     file_name = Some(detected_file_name);
@@ -91,6 +241,36 @@ fn parse_diagnostics(
                     message += &get_log_from_output(output);
                 }
             }
+            Action::Pass | Action::Fail | Action::Skip => {
+                let test_item = value
+                    .test
+                    .as_deref()
+                    .and_then(|test| find_test_item(test_items, test));
+                if let Some(test_item) = test_item {
+                    let status = match value.action {
+                        Action::Pass => TestStatus::Passed,
+                        Action::Fail => TestStatus::Failed,
+                        _ => TestStatus::Skipped,
+                    };
+                    let output = value
+                        .test
+                        .as_ref()
+                        .and_then(|test| test_outputs.remove(test))
+                        .filter(|_| status != TestStatus::Passed);
+                    push_test_result(
+                        &mut test_results,
+                        TestResult {
+                            id: test_item.id.clone(),
+                            path: test_item.path.clone(),
+                            status,
+                            duration: value
+                                .elapsed
+                                .map(|seconds| (seconds * 1000.0).round() as u64),
+                            output,
+                        },
+                    );
+                }
+            }
             _ => {}
         }
         let current_action = value.action;
@@ -136,10 +316,15 @@ fn parse_diagnostics(
             .map(|(path, diagnostics)| FileDiagnostics { path, diagnostics })
             .collect(),
         messages: vec![],
+        tests: test_results,
     })
 }
 
 fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+    Ok(discover_file(file_path)?.tests)
+}
+
+fn discover_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/nvim-neotest/neotest-go/blob/92950ad7be2ca02a41abca5c6600ff6ffaf5b5d6/lua/neotest-go/init.lua#L54
     // license: https://github.com/nvim-neotest/neotest-go/blob/92950ad7be2ca02a41abca5c6600ff6ffaf5b5d6/README.md
     let query = r#"
@@ -223,7 +408,102 @@ fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
                 ((identifier) @test.key.name1
                 (#eq? @test.key.name @test.key.name1))))))))
 "#;
-    discover_with_treesitter(file_path, &tree_sitter_go::language(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_go::language(), query)
+}
+
+/// Builds the `-run` patterns for the given test ids, one `go test` invocation each.
+/// `go test` matches each level of subtests separately, so a test and a subtest of
+/// another test cannot be selected by one pattern: the selected tests share a pattern
+/// and the subtests of each parent test get their own. Subtest ids are the quoted
+/// `t.Run` names, whose parent is the test containing them, and spaces in them are
+/// replaced with underscores by `go test`.
+fn go_run_patterns(test_ids: &[String], test_items: &[TestItem]) -> Vec<String> {
+    let (subtests, tests): (Vec<&String>, Vec<&String>) =
+        test_ids.iter().partition(|id| id.starts_with('"'));
+    let mut patterns = vec![];
+    if !tests.is_empty() {
+        let tests = tests.iter().map(|id| regex::escape(id)).collect::<Vec<_>>();
+        patterns.push(format!("^(?:{})$", tests.join("|")));
+    }
+    // Subtests grouped by their parent test, `None` when it is not found
+    let mut parents: Vec<(Option<&str>, Vec<String>)> = vec![];
+    for subtest in subtests {
+        let parent = test_items
+            .iter()
+            .filter(|item| item.id == *subtest)
+            .find_map(|item| parent_test(item, test_items))
+            .map(|parent| parent.id.as_str());
+        if parent.is_some_and(|parent| tests.iter().any(|id| *id == parent)) {
+            // Runs with all subtests of its parent
+            continue;
+        }
+        let name = regex::escape(&subtest.trim_matches('"').replace(' ', "_"));
+        match parents.iter_mut().find(|(id, _)| *id == parent) {
+            Some((_, names)) => names.push(name),
+            None => parents.push((parent, vec![name])),
+        }
+    }
+    for (parent, names) in parents {
+        let parent = parent.map_or(String::new(), |id| format!("^{}$", regex::escape(id)));
+        patterns.push(format!("{parent}/^(?:{})$", names.join("|")));
+    }
+    patterns
+}
+
+/// The innermost top-level test containing the subtest `item`
+fn parent_test<'a>(item: &TestItem, test_items: &'a [TestItem]) -> Option<&'a TestItem> {
+    test_items
+        .iter()
+        .filter(|test| !test.id.starts_with('"') && test.path == item.path)
+        .filter(|test| {
+            test.start_position.start <= item.start_position.start
+                && item.end_position.end <= test.end_position.end
+        })
+        .max_by_key(|test| test.start_position.start)
+}
+
+/// Runs `go test` and writes its output to the log `log_name`
+fn run_go_test(
+    workspace: &str,
+    file_paths: &[String],
+    extra: &[String],
+    run_args: &[String],
+    log_name: &str,
+) -> Result<RunFileTestResult, LSError> {
+    let default_args = ["-v", "-json", "", "-count=1", "-timeout=60s"];
+    let test_items = file_paths
+        .iter()
+        .map(|path| discover(path))
+        .filter_map(Result::ok)
+        .flatten()
+        .collect::<Vec<_>>();
+    let mut events = GoEvents::new(workspace, file_paths, &test_items);
+    let output = output_with_lines(
+        std::process::Command::new("go")
+            .current_dir(workspace)
+            .arg("test")
+            .args(default_args)
+            .args(run_args)
+            .args(extra),
+        |stream, line| {
+            if stream == OutputStream::Stdout {
+                events.parse_line(line).iter().for_each(send_event);
+            }
+        },
+    )
+    .unwrap();
+    write_result_log(log_name, &output)?;
+    let Output { stdout, stderr, .. } = output;
+    if stdout.is_empty() && !stderr.is_empty() {
+        return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
+    }
+    let test_result = String::from_utf8(stdout)?;
+    parse_diagnostics(
+        &test_result,
+        PathBuf::from_str(workspace).unwrap(),
+        file_paths,
+        &test_items,
+    )
 }
 
 #[derive(Eq, PartialEq, Hash, Debug)]
@@ -238,11 +518,7 @@ impl Runner for GoTestRunner {
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
 
         for file_path in file_paths {
-            let tests = discover(&file_path)?;
-            discover_results.data.push(FoundFileTests {
-                tests,
-                path: file_path,
-            });
+            discover_results.data.push(discover_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -253,31 +529,59 @@ impl Runner for GoTestRunner {
         &self,
         args: testing_language_server::spec::RunFileTestArgs,
     ) -> Result<(), testing_language_server::error::LSError> {
-        let file_paths = args.file_paths;
-        let default_args = ["-v", "-json", "", "-count=1", "-timeout=60s"];
-        let workspace = args.workspace;
-        let output = std::process::Command::new("go")
-            .current_dir(&workspace)
-            .arg("test")
-            .args(default_args)
-            .args(args.extra)
-            .output()
-            .unwrap();
-        write_result_log("go.log", &output)?;
-        let Output { stdout, stderr, .. } = output;
-        if stdout.is_empty() && !stderr.is_empty() {
-            return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
-        }
-        let test_result = String::from_utf8(stdout)?;
-        let diagnostics: RunFileTestResult = parse_diagnostics(
-            &test_result,
-            PathBuf::from_str(&workspace).unwrap(),
-            &file_paths,
+        let diagnostics = run_go_test(
+            &args.workspace,
+            &args.file_paths,
+            &args.extra,
+            &[],
+            "go.log",
         )?;
         send_stdout(&diagnostics)?;
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    fn run_test(
+        &self,
+        args: testing_language_server::spec::RunTestArgs,
+    ) -> Result<(), testing_language_server::error::LSError> {
+        let test_items = args
+            .file_paths
+            .iter()
+            .map(|path| discover(path))
+            .filter_map(Result::ok)
+            .flatten()
+            .collect::<Vec<_>>();
+        let mut diagnostics = RunFileTestResult {
+            data: vec![],
+            messages: vec![],
+            tests: vec![],
+        };
+        for (index, pattern) in go_run_patterns(&args.test_ids, &test_items)
+            .into_iter()
+            .enumerate()
+        {
+            let run_args = ["-run".to_string(), pattern];
+            // Each `go test` has its own log, the server offers all the logs of the run
+            let log_name = match index {
+                0 => "go.log".to_string(),
+                _ => format!("go-{}.log", index + 1),
+            };
+            let result = run_go_test(
+                &args.workspace,
+                &args.file_paths,
+                &args.extra,
+                &run_args,
+                &log_name,
+            )?;
+            diagnostics.data.extend(result.data);
+            diagnostics.messages.extend(result.messages);
+            diagnostics.tests.extend(result.tests);
+        }
+        send_stdout(&diagnostics)?;
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     fn detect_workspaces(
         &self,
@@ -294,10 +598,17 @@ impl Runner for GoTestRunner {
 #[cfg(test)]
 mod tests {
     use crate::runner::go::discover;
+    use crate::runner::go::go_run_patterns;
+    use crate::runner::go::GoEvents;
     use std::str::FromStr;
     use std::{fs::read_to_string, path::PathBuf};
 
     use crate::runner::go::parse_diagnostics;
+    use lsp_types::Position;
+    use lsp_types::Range;
+    use testing_language_server::spec::AdapterEvent;
+    use testing_language_server::spec::TestItem;
+    use testing_language_server::spec::TestStatus;
 
     #[test]
     fn test_parse_diagnostics() {
@@ -307,7 +618,7 @@ mod tests {
         let workspace = PathBuf::from_str("/home/demo/test/go/src/test").unwrap();
         let target_file_path = "/home/demo/test/go/src/test/cases_test.go";
         let result =
-            parse_diagnostics(&contents, workspace, &[target_file_path.to_string()]).unwrap();
+            parse_diagnostics(&contents, workspace, &[target_file_path.to_string()], &[]).unwrap();
         let result = result.data.first().unwrap();
         assert_eq!(result.path, target_file_path);
         let diagnostic = result.diagnostics.first().unwrap();
@@ -317,6 +628,145 @@ mod tests {
         assert_eq!(diagnostic.message, "\tError Trace:\tcases_test.go:31\n\tError:      \tNot equal: \n\t    \texpected: 7\n\t    \tactual  : -1\n\tTest:       \tTestSubtract/test_two\n--- FAIL: TestSubtract (0.00s)\n    --- FAIL: TestSubtract/test_one (0.00s)\n");
     }
 
+    #[test]
+    fn stream_events_while_tests_run() {
+        let current_dir = std::env::current_dir().unwrap();
+        let contents = read_to_string(current_dir.join("tests/go-test.txt")).unwrap();
+        let target_file_path = "/home/demo/test/go/src/test/cases_test.go".to_string();
+        let test_items = ["TestSubtract", "\"test two\""].map(|id| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: target_file_path.clone(),
+            start_position: Range::default(),
+            end_position: Range::default(),
+        });
+        let file_paths = [target_file_path.clone()];
+        let mut events = GoEvents::new("/home/demo/test/go/src/test", &file_paths, &test_items);
+        let events = contents
+            .lines()
+            .flat_map(|line| events.parse_line(line))
+            .collect::<Vec<_>>();
+        let started = events
+            .iter()
+            .filter(|event| matches!(event, AdapterEvent::TestStarted { .. }))
+            .count();
+        // TestSubtract, TestSubtract/test_two and TestAdd/test_two
+        assert_eq!(started, 3);
+        let diagnostics = events
+            .iter()
+            .filter_map(|event| match event {
+                AdapterEvent::FileDiagnostics(file_diagnostics) => Some(file_diagnostics),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|file| file.path == target_file_path));
+        let first = &diagnostics[0].diagnostics[0];
+        assert_eq!(first.range.start.line, 30);
+        assert!(first
+            .message
+            .starts_with("\tError Trace:\tcases_test.go:31\n\tError:      \tNot equal: \n"));
+        assert_eq!(diagnostics[1].diagnostics[0].range.start.line, 41);
+        let finished = events
+            .iter()
+            .filter_map(|event| match event {
+                AdapterEvent::TestFinished(test) => Some((test.id.as_str(), test.status)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            finished,
+            vec![
+                ("\"test two\"", TestStatus::Failed),
+                ("TestSubtract", TestStatus::Failed),
+                ("\"test two\"", TestStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_test_results() {
+        let current_dir = std::env::current_dir().unwrap();
+        let test_file_path = current_dir.join("tests/go-test.txt");
+        let contents = read_to_string(test_file_path).unwrap();
+        let workspace = PathBuf::from_str("/home/demo/test/go/src/test").unwrap();
+        let target_file_path = "/home/demo/test/go/src/test/cases_test.go";
+        let test_item = |id: &str| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: target_file_path.to_string(),
+            start_position: Range::default(),
+            end_position: Range::default(),
+        };
+        let test_items = [
+            test_item("TestAddOne"),
+            test_item("TestSubtract"),
+            test_item("\"test one\""),
+            test_item("\"string\""),
+        ];
+        let result = parse_diagnostics(
+            &contents,
+            workspace,
+            &[target_file_path.to_string()],
+            &test_items,
+        )
+        .unwrap();
+        let summary = result
+            .tests
+            .iter()
+            .map(|test| (test.id.as_str(), test.status, test.duration))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("\"test one\"", TestStatus::Failed, Some(0)),
+                ("TestSubtract", TestStatus::Failed, Some(0)),
+                ("\"string\"", TestStatus::Passed, Some(0)),
+                ("TestAddOne", TestStatus::Passed, Some(0)),
+            ]
+        );
+        let subtract = &result.tests[1];
+        assert!(subtract
+            .output
+            .as_ref()
+            .unwrap()
+            .contains("--- FAIL: TestSubtract"));
+    }
+
+    #[test]
+    fn test_go_run_patterns() {
+        let item = |id: &str, start: u32, end: u32| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: "/demo/cases_test.go".to_string(),
+            start_position: Range::new(Position::new(start, 0), Position::new(start, 10)),
+            end_position: Range::new(Position::new(end, 0), Position::new(end, 1)),
+        };
+        let test_items = [
+            item("TestAdd", 0, 10),
+            item("\"adds\"", 3, 5),
+            item("TestSubtract", 12, 30),
+            item("\"test one\"", 15, 18),
+            item("\"test two\"", 20, 24),
+        ];
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            go_run_patterns(&ids(&["TestAdd", "TestSubtract"]), &test_items),
+            ["^(?:TestAdd|TestSubtract)$"]
+        );
+        assert_eq!(
+            go_run_patterns(
+                &ids(&["TestAdd", "\"adds\"", "\"test one\"", "\"test two\""]),
+                &test_items
+            ),
+            ["^(?:TestAdd)$", "^TestSubtract$/^(?:test_one|test_two)$"]
+        );
+        assert_eq!(
+            go_run_patterns(&ids(&["\"unknown\""]), &[]),
+            ["/^(?:unknown)$"]
+        );
+    }
+
     #[test]
     fn test_discover() {
         let file_path = "../../demo/go/cases_test.go";
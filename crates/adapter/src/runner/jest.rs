@@ -11,13 +11,14 @@ use testing_language_server::spec::DiscoverResult;
 use testing_language_server::spec::FileDiagnostics;
 use testing_language_server::spec::FoundFileTests;
 use testing_language_server::spec::RunFileTestResult;
-use testing_language_server::spec::TestItem;
 
 use crate::model::Runner;
 
 use super::util::clean_ansi;
 use super::util::detect_workspaces_from_file_list;
-use super::util::discover_with_treesitter;
+use super::util::discover_file_with_treesitter;
+use super::util::jest_test_result;
+use super::util::test_ids_pattern;
 use super::util::LOG_LOCATION;
 use super::util::MAX_CHAR_LENGTH;
 
@@ -26,6 +27,7 @@ fn parse_diagnostics(
     file_paths: Vec<String>,
 ) -> Result<RunFileTestResult, LSError> {
     let mut result_map: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+    let mut tests = vec![];
     let json: Value = serde_json::from_str(test_result)?;
     let test_results = json["testResults"].as_array().unwrap();
     for test_result in test_results {
@@ -35,6 +37,7 @@ fn parse_diagnostics(
         }
         let assertion_results = test_result["assertionResults"].as_array().unwrap();
         'assertion: for assertion_result in assertion_results {
+            tests.extend(jest_test_result(file_path, assertion_result));
             let status = assertion_result["status"].as_str().unwrap();
             if status != "failed" {
                 continue 'assertion;
@@ -73,6 +76,7 @@ fn parse_diagnostics(
             .map(|(path, diagnostics)| FileDiagnostics { path, diagnostics })
             .collect(),
         messages: vec![],
+        tests,
     })
 }
 
@@ -80,7 +84,7 @@ fn detect_workspaces(file_paths: Vec<String>) -> DetectWorkspaceResult {
     detect_workspaces_from_file_list(&file_paths, &["package.json".to_string()])
 }
 
-fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+fn discover_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/nvim-neotest/neotest-jest/blob/514fd4eae7da15fd409133086bb8e029b65ac43f/lua/neotest-jest/init.lua#L162
     // license: https://github.com/nvim-neotest/neotest-jest/blob/514fd4eae7da15fd409133086bb8e029b65ac43f/LICENSE.md
     let query = r#"
@@ -152,7 +156,31 @@ fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
       arguments: (arguments (string (string_fragment) @test.name) [(arrow_function) (function_expression)])
     )) @test.definition
         "#;
-    discover_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
+}
+
+fn run_jest(
+    workspace_root: &str,
+    file_paths: Vec<String>,
+    filter_args: &[String],
+) -> Result<RunFileTestResult, LSError> {
+    let log_path = LOG_LOCATION.join("jest.json");
+    std::process::Command::new("jest")
+        .current_dir(workspace_root)
+        .args([
+            "--testLocationInResults",
+            "--forceExit",
+            "--no-coverage",
+            "--verbose",
+            "--json",
+            "--outputFile",
+            log_path.to_str().unwrap(),
+        ])
+        .args(filter_args)
+        .output()
+        .unwrap();
+    let test_result = fs::read_to_string(log_path)?;
+    parse_diagnostics(&test_result, file_paths)
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -164,10 +192,7 @@ impl Runner for JestRunner {
         let file_paths = args.file_paths;
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
         for file_path in file_paths {
-            discover_results.data.push(FoundFileTests {
-                tests: discover(&file_path)?,
-                path: file_path,
-            })
+            discover_results.data.push(discover_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -178,24 +203,19 @@ impl Runner for JestRunner {
         &self,
         args: testing_language_server::spec::RunFileTestArgs,
     ) -> Result<(), LSError> {
-        let file_paths = args.file_paths;
-        let workspace_root = args.workspace;
-        let log_path = LOG_LOCATION.join("jest.json");
-        std::process::Command::new("jest")
-            .current_dir(&workspace_root)
-            .args([
-                "--testLocationInResults",
-                "--forceExit",
-                "--no-coverage",
-                "--verbose",
-                "--json",
-                "--outputFile",
-                log_path.to_str().unwrap(),
-            ])
-            .output()
-            .unwrap();
-        let test_result = fs::read_to_string(log_path)?;
-        let diagnostics: RunFileTestResult = parse_diagnostics(&test_result, file_paths)?;
+        let diagnostics = run_jest(&args.workspace, args.file_paths, &[])?;
+        send_stdout(&diagnostics)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        let mut filter_args = vec![
+            "--testNamePattern".to_string(),
+            test_ids_pattern(&args.test_ids, " "),
+        ];
+        filter_args.extend(args.file_paths.clone());
+        let diagnostics = run_jest(&args.workspace, args.file_paths, &filter_args)?;
         send_stdout(&diagnostics)?;
         Ok(())
     }
@@ -215,8 +235,10 @@ impl Runner for JestRunner {
 #[cfg(test)]
 mod tests {
     use lsp_types::{Position, Range};
+    use testing_language_server::spec::TestStatus;
 
     use super::*;
+    use testing_language_server::spec::TestItem;
 
     #[test]
     fn test_parse_diagnostics() {
@@ -233,6 +255,33 @@ mod tests {
         )
         .unwrap();
         assert_eq!(diagnostics.data.len(), 2);
+        let summary = diagnostics
+            .tests
+            .iter()
+            .map(|test| (test.id.as_str(), test.status, test.duration))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("index::fail", TestStatus::Failed, Some(3)),
+                ("another::fail", TestStatus::Failed, Some(2)),
+                ("another::pass", TestStatus::Passed, Some(1)),
+            ]
+        );
+        assert!(diagnostics.tests[0]
+            .output
+            .as_ref()
+            .unwrap()
+            .contains("Expected: 0"));
+    }
+
+    #[test]
+    fn test_test_ids_pattern() {
+        let test_ids = vec!["index::fail".to_string(), "index::pass (1+1)".to_string()];
+        assert_eq!(
+            test_ids_pattern(&test_ids, " "),
+            "^(?:index fail|index pass \\(1\\+1\\))$"
+        );
     }
 
     #[test]
@@ -254,7 +303,7 @@ mod tests {
     #[test]
     fn test_discover() {
         let file_path = "../../demo/jest/index.spec.js";
-        let test_items = discover(file_path).unwrap();
+        let test_items = discover_file(file_path).unwrap().tests;
         assert_eq!(test_items.len(), 1);
         assert_eq!(
             test_items,
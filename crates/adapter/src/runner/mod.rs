@@ -1,9 +1,9 @@
 pub mod cargo_nextest;
 pub mod cargo_test;
-pub mod node_test;
 pub mod deno;
 pub mod go;
 pub mod jest;
+pub mod node_test;
 pub mod phpunit;
 pub mod util;
 pub mod vitest;
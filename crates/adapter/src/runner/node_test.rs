@@ -1,26 +1,33 @@
 use std::process::Output;
+use std::sync::LazyLock;
 
 use regex::Regex;
 use testing_language_server::{
     error::LSError,
     spec::{
-        DetectWorkspaceResult, DiscoverResult, FileDiagnostics, FoundFileTests, RunFileTestResult,
-        TestItem,
+        AdapterEvent, DetectWorkspaceResult, DiscoverResult, FileDiagnostics, FoundFileTests,
+        RunFileTestResult, TestItem, TestResult, TestStatus,
     },
+    util::OutputStream,
 };
 use xml::{reader::XmlEvent, ParserConfig};
 
 use crate::model::Runner;
 
 use super::util::{
-    detect_workspaces_from_file_list, discover_with_treesitter, send_stdout, write_result_log,
-    ResultFromXml,
+    detect_workspaces_from_file_list, discover_file_with_treesitter, output_with_lines,
+    parse_junit_test_cases, send_event, send_stdout, test_ids_pattern, write_result_log,
+    ResultFromXml, STREAM_EVENTS,
 };
 
 #[derive(Eq, PartialEq, Debug)]
 pub struct NodeTestRunner;
 
 fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+    Ok(discover_file(file_path)?.tests)
+}
+
+fn discover_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/nvim-neotest/neotest-jest/blob/514fd4eae7da15fd409133086bb8e029b65ac43f/lua/neotest-jest/init.lua#L162
     // license: https://github.com/nvim-neotest/neotest-jest/blob/514fd4eae7da15fd409133086bb8e029b65ac43f/LICENSE.md
     let query = r#"
@@ -83,7 +90,7 @@ fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
     )) @test.definition
 
     "#;
-    discover_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
 }
 
 // characters can be like
@@ -129,10 +136,8 @@ fn get_result_from_xml(
     loop {
         match reader.next() {
             Ok(e) => match e {
-                XmlEvent::StartElement { name, .. } => {
-                    if name.local_name.starts_with(local_name) {
-                        in_failure = true;
-                    }
+                XmlEvent::StartElement { name, .. } if name.local_name.starts_with(local_name) => {
+                    in_failure = true;
                 }
                 XmlEvent::EndElement { .. } => {
                     in_failure = false;
@@ -159,16 +164,155 @@ fn get_result_from_xml(
     Ok(result)
 }
 
+/// The junit reporter does not tell which file a test is in,
+/// so results are matched with the discovered tests by their names.
+fn get_test_results_from_xml(output: &str, test_items: &[TestItem]) -> Vec<TestResult> {
+    parse_junit_test_cases(output.as_bytes())
+        .into_iter()
+        .filter_map(|test_case| {
+            let mut names = test_case.suites.clone();
+            names.push(test_case.name.clone());
+            let full_id = names.join("::");
+            let test_item = test_items
+                .iter()
+                .find(|item| item.id == full_id)
+                .or_else(|| test_items.iter().find(|item| item.id == test_case.name))?;
+            Some(TestResult {
+                id: test_item.id.clone(),
+                path: test_item.path.clone(),
+                status: test_case.status,
+                duration: test_case.duration,
+                output: test_case.output,
+            })
+        })
+        .collect()
+}
+
+/// Turns the output of the TAP reporter into adapter events while the tests run
+struct TapEvents<'a> {
+    test_items: &'a [TestItem],
+    /// Names of the enclosing subtests, one per indentation level
+    subtests: Vec<String>,
+}
+
+impl<'a> TapEvents<'a> {
+    fn new(test_items: &'a [TestItem]) -> Self {
+        Self {
+            test_items,
+            subtests: vec![],
+        }
+    }
+
+    fn find_test_item(&self, depth: usize, name: &str) -> Option<&'a TestItem> {
+        let mut names = self.subtests[..depth.min(self.subtests.len())].to_vec();
+        names.push(name.to_string());
+        let full_id = names.join("::");
+        self.test_items
+            .iter()
+            .find(|item| item.id == full_id)
+            .or_else(|| self.test_items.iter().find(|item| item.id == name))
+    }
+
+    fn parse_line(&mut self, line: &str) -> Option<AdapterEvent> {
+        // Example:
+        //     # Subtest: adds numbers
+        static SUBTEST_RE: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"^( *)# Subtest: (.*)$").unwrap());
+        // Example:
+        //     not ok 2 - adds numbers # SKIP
+        static RESULT_RE: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^( *)(not ok|ok) \d+ - (.*?)(?: # (SKIP|TODO)\b.*)?$").unwrap()
+        });
+        if let Some(m) = SUBTEST_RE.captures(line) {
+            let depth = m[1].len() / 4;
+            self.subtests.truncate(depth);
+            let test_item = self.find_test_item(depth, &m[2]);
+            self.subtests.push(m[2].to_string());
+            return test_item.map(|test_item| AdapterEvent::TestStarted {
+                id: test_item.id.clone(),
+                path: test_item.path.clone(),
+            });
+        }
+        let m = RESULT_RE.captures(line)?;
+        let depth = m[1].len() / 4;
+        let test_item = self.find_test_item(depth, &m[3])?;
+        let status = match (&m[2], m.get(4)) {
+            (_, Some(_)) => TestStatus::Skipped,
+            ("ok", None) => TestStatus::Passed,
+            _ => TestStatus::Failed,
+        };
+        Some(AdapterEvent::TestFinished(TestResult {
+            id: test_item.id.clone(),
+            path: test_item.path.clone(),
+            status,
+            duration: None,
+            output: None,
+        }))
+    }
+}
+
+fn run_node_test(
+    workspace_root: &str,
+    file_paths: &[String],
+    extra: &[String],
+) -> Result<RunFileTestResult, LSError> {
+    let test_items = file_paths
+        .iter()
+        .map(|path| discover(path))
+        .filter_map(Result::ok)
+        .flatten()
+        .collect::<Vec<_>>();
+    let mut command = std::process::Command::new("node");
+    command
+        .current_dir(workspace_root)
+        .args(["--test", "--test-reporter", "junit"]);
+    if *STREAM_EVENTS {
+        // The junit report is only written at the end, so the progress is read from TAP
+        command.args([
+            "--test-reporter-destination",
+            "stdout",
+            "--test-reporter",
+            "tap",
+            "--test-reporter-destination",
+            "stderr",
+        ]);
+    }
+    let mut events = TapEvents::new(&test_items);
+    let output = output_with_lines(command.args(extra).args(file_paths), |stream, line| {
+        if stream == OutputStream::Stderr {
+            events.parse_line(line).iter().for_each(send_event);
+        }
+    })
+    .unwrap();
+    write_result_log("node-test.xml", &output)?;
+    let Output { stdout, stderr, .. } = output;
+    if stdout.is_empty() && !stderr.is_empty() {
+        return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
+    }
+    let stdout = String::from_utf8(stdout).unwrap();
+    let result_from_xml = get_result_from_xml(&stdout, file_paths)?;
+    let tests = get_test_results_from_xml(&stdout, &test_items);
+    let result_item: Vec<FileDiagnostics> = result_from_xml
+        .into_iter()
+        .map(|result_from_xml| {
+            let result_item: FileDiagnostics = result_from_xml.into();
+            result_item
+        })
+        .collect();
+    Ok(RunFileTestResult {
+        data: result_item,
+        messages: vec![],
+        tests,
+    })
+}
+
 impl Runner for NodeTestRunner {
     #[tracing::instrument(skip(self))]
     fn discover(&self, args: testing_language_server::spec::DiscoverArgs) -> Result<(), LSError> {
         let file_paths = args.file_paths;
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
         for file_path in file_paths {
-            discover_results.data.push(FoundFileTests {
-                tests: discover(&file_path)?,
-                path: file_path,
-            })
+            discover_results.data.push(discover_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -179,33 +323,19 @@ impl Runner for NodeTestRunner {
         &self,
         args: testing_language_server::spec::RunFileTestArgs,
     ) -> Result<(), LSError> {
-        let file_paths = args.file_paths;
-        let workspace_root = args.workspace;
-        let output = std::process::Command::new("node")
-            .current_dir(&workspace_root)
-            .args(["--test", "--test-reporter", "junit"])
-            .args(args.extra)
-            .args(&file_paths)
-            .output()
-            .unwrap();
-        write_result_log("node-test.xml", &output)?;
-        let Output { stdout, stderr, .. } = output;
-        if stdout.is_empty() && !stderr.is_empty() {
-            return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
-        }
-        let stdout = String::from_utf8(stdout).unwrap();
-        let result_from_xml = get_result_from_xml(&stdout, &file_paths)?;
-        let result_item: Vec<FileDiagnostics> = result_from_xml
-            .into_iter()
-            .map(|result_from_xml| {
-                let result_item: FileDiagnostics = result_from_xml.into();
-                result_item
-            })
-            .collect();
-        let result = RunFileTestResult {
-            data: result_item,
-            messages: vec![],
-        };
+        let result = run_node_test(&args.workspace, &args.file_paths, &args.extra)?;
+        send_stdout(&result)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        let mut extra = vec![
+            "--test-name-pattern".to_string(),
+            test_ids_pattern(&args.test_ids, " "),
+        ];
+        extra.extend(args.extra);
+        let result = run_node_test(&args.workspace, &args.file_paths, &extra)?;
         send_stdout(&result)?;
         Ok(())
     }
@@ -260,6 +390,79 @@ mod tests {
         assert_eq!(cols, [10, 10, 14, 10, 9, 9, 9, 11, 3]);
     }
 
+    #[test]
+    fn parse_test_results_from_xml() {
+        let mut xml_path = std::env::current_dir().unwrap();
+        xml_path.push("../../demo/node-test/output.xml");
+        let content = std::fs::read_to_string(&xml_path).unwrap();
+        let test_items = discover("../../demo/node-test/index.test.js").unwrap();
+        let result = get_test_results_from_xml(&content, &test_items);
+        let status_of = |id: &str| {
+            result
+                .iter()
+                .find(|test| test.id == id)
+                .map(|test| test.status)
+        };
+        assert_eq!(
+            status_of("synchronous passing test"),
+            Some(TestStatus::Passed)
+        );
+        assert_eq!(
+            status_of("synchronous failing test"),
+            Some(TestStatus::Failed)
+        );
+        assert_eq!(status_of("skip option"), Some(TestStatus::Skipped));
+        assert_eq!(status_of("todo option"), Some(TestStatus::Skipped));
+        let failed = result
+            .iter()
+            .find(|test| test.id == "synchronous failing test")
+            .unwrap();
+        assert_eq!(failed.duration, Some(1));
+        assert!(failed.output.as_ref().unwrap().contains("1 !== 2"));
+    }
+
+    #[test]
+    fn stream_events_from_tap() {
+        let test_items = discover("../../demo/node-test/index.test.js").unwrap();
+        let tap = r#"TAP version 13
+# Subtest: synchronous failing test
+not ok 2 - synchronous failing test
+  ---
+  duration_ms: 0.812
+  failureType: 'testCodeFailure'
+  ...
+# Subtest: skip option
+ok 9 - skip option # SKIP
+  ---
+  duration_ms: 0.034
+  ...
+# Subtest: A thing
+    # Subtest: should work
+    ok 1 - should work
+      ---
+      duration_ms: 0.051
+      ...
+ok 17 - A thing
+"#;
+        let mut events = TapEvents::new(&test_items);
+        let finished = tap
+            .lines()
+            .filter_map(|line| events.parse_line(line))
+            .filter_map(|event| match event {
+                AdapterEvent::TestFinished(test) => Some((test.id, test.status)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            finished,
+            vec![
+                ("synchronous failing test".to_string(), TestStatus::Failed),
+                ("skip option".to_string(), TestStatus::Skipped),
+                ("A thing::should work".to_string(), TestStatus::Passed),
+            ]
+        );
+    }
+
     #[test]
     fn test_discover() {
         let file_path = "../../demo/node-test/index.test.js";
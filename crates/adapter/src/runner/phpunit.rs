@@ -4,15 +4,15 @@ use std::process::Output;
 use testing_language_server::error::LSError;
 use testing_language_server::spec::{
     DetectWorkspaceResult, DiscoverResult, FileDiagnostics, FoundFileTests, RunFileTestResult,
-    TestItem,
+    TestItem, TestResult,
 };
 use xml::reader::{ParserConfig, XmlEvent};
 
 use crate::model::Runner;
 
 use super::util::{
-    detect_workspaces_from_file_list, discover_with_treesitter, send_stdout, ResultFromXml,
-    LOG_LOCATION,
+    detect_workspaces_from_file_list, discover_file_with_treesitter, parse_junit_test_cases,
+    send_stdout, ResultFromXml, LOG_LOCATION,
 };
 
 fn detect_workspaces(file_paths: Vec<String>) -> DetectWorkspaceResult {
@@ -57,10 +57,8 @@ fn get_result_from_xml(path: &str) -> Result<Vec<ResultFromXml>, anyhow::Error>
     loop {
         match reader.next() {
             Ok(e) => match e {
-                XmlEvent::StartElement { name, .. } => {
-                    if name.local_name.starts_with(local_name) {
-                        in_failure = true;
-                    }
+                XmlEvent::StartElement { name, .. } if name.local_name.starts_with(local_name) => {
+                    in_failure = true;
                 }
                 XmlEvent::EndElement { .. } => {
                     in_failure = false;
@@ -85,7 +83,30 @@ fn get_result_from_xml(path: &str) -> Result<Vec<ResultFromXml>, anyhow::Error>
     Ok(result)
 }
 
+fn get_test_results_from_xml(path: &str) -> Result<Vec<TestResult>, anyhow::Error> {
+    let file = File::open(path)?;
+    Ok(parse_junit_test_cases(BufReader::new(file))
+        .into_iter()
+        .map(|test_case| {
+            // `class` is like Tests\CalculatorTest, while discovered ids start with the class name
+            let class = test_case.class.unwrap_or_default();
+            let class = class.rsplit('\\').next().unwrap_or_default();
+            TestResult {
+                id: format!("{}::{}", class, test_case.name),
+                path: test_case.file.unwrap_or_default(),
+                status: test_case.status,
+                duration: test_case.duration,
+                output: test_case.output,
+            }
+        })
+        .collect())
+}
+
 fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+    Ok(discover_file(file_path)?.tests)
+}
+
+fn discover_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/olimorris/neotest-phpunit/blob/bbd79d95e927ccd16f0e1d765060058d34838e2e/lua/neotest-phpunit/init.lua#L111
     // license: https://github.com/olimorris/neotest-phpunit/blob/bbd79d95e927ccd16f0e1d765060058d34838e2e/LICENSE
     let query = r#"
@@ -115,7 +136,46 @@ fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
       ) @test.definition
     ))
         "#;
-    discover_with_treesitter(file_path, &tree_sitter_php::language_php(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_php::language_php(), query)
+}
+
+fn run_phpunit(
+    workspace_root: &str,
+    file_paths: Vec<String>,
+    filter_pattern: &str,
+) -> Result<RunFileTestResult, LSError> {
+    let log_path = LOG_LOCATION.join("phpunit.xml");
+    let output = std::process::Command::new("phpunit")
+        .current_dir(workspace_root)
+        .args([
+            "--log-junit",
+            log_path.to_str().unwrap(),
+            "--filter",
+            filter_pattern,
+        ])
+        .args(file_paths)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    let Output { stdout, stderr, .. } = output;
+    if stdout.is_empty() && !stderr.is_empty() {
+        return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
+    }
+    let result_from_xml = get_result_from_xml(log_path.to_str().unwrap())?;
+    let tests = get_test_results_from_xml(log_path.to_str().unwrap())?;
+    let result_item: Vec<FileDiagnostics> = result_from_xml
+        .into_iter()
+        .map(|result_from_xml| {
+            let result_item: FileDiagnostics = result_from_xml.into();
+            result_item
+        })
+        .collect();
+    Ok(RunFileTestResult {
+        data: result_item,
+        messages: vec![],
+        tests,
+    })
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -127,10 +187,7 @@ impl Runner for PhpunitRunner {
         let file_paths = args.file_paths;
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
         for file_path in file_paths {
-            discover_results.data.push(FoundFileTests {
-                tests: discover(&file_path)?,
-                path: file_path,
-            })
+            discover_results.data.push(discover_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -142,8 +199,6 @@ impl Runner for PhpunitRunner {
         args: testing_language_server::spec::RunFileTestArgs,
     ) -> Result<(), LSError> {
         let file_paths = args.file_paths;
-        let workspace_root = args.workspace;
-        let log_path = LOG_LOCATION.join("phpunit.xml");
         let tests = file_paths
             .iter()
             .map(|path| {
@@ -159,35 +214,23 @@ impl Runner for PhpunitRunner {
             .collect::<Vec<_>>();
         let test_names = tests.join("|");
         let filter_pattern = format!("/{test_names}/");
-        let output = std::process::Command::new("phpunit")
-            .current_dir(&workspace_root)
-            .args([
-                "--log-junit",
-                log_path.to_str().unwrap(),
-                "--filter",
-                &filter_pattern,
-            ])
-            .args(file_paths)
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .output()
-            .unwrap();
-        let Output { stdout, stderr, .. } = output;
-        if stdout.is_empty() && !stderr.is_empty() {
-            return Err(LSError::Adapter(String::from_utf8(stderr).unwrap()));
-        }
-        let result_from_xml = get_result_from_xml(log_path.to_str().unwrap())?;
-        let result_item: Vec<FileDiagnostics> = result_from_xml
-            .into_iter()
-            .map(|result_from_xml| {
-                let result_item: FileDiagnostics = result_from_xml.into();
-                result_item
-            })
-            .collect();
-        let result = RunFileTestResult {
-            data: result_item,
-            messages: vec![],
-        };
+        let result = run_phpunit(&args.workspace, file_paths, &filter_pattern)?;
+        send_stdout(&result)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        let test_names = args
+            .test_ids
+            .iter()
+            .map(|id| regex::escape(id))
+            .collect::<Vec<_>>()
+            .join("|");
+        // test ids do not contain the PHP namespace, so only the end is anchored.
+        // Cases of a data provider are named like `testAdd with data set #0`.
+        let filter_pattern = format!("/(?:{test_names})(?:\\s|$)/");
+        let result = run_phpunit(&args.workspace, args.file_paths, &filter_pattern)?;
         send_stdout(&result)?;
         Ok(())
     }
@@ -207,6 +250,7 @@ impl Runner for PhpunitRunner {
 #[cfg(test)]
 mod tests {
     use lsp_types::{Position, Range};
+    use testing_language_server::spec::TestStatus;
 
     use crate::runner::util::MAX_CHAR_LENGTH;
 
@@ -229,6 +273,34 @@ mod tests {
         assert_eq!(result[0].line, 28);
     }
 
+    #[test]
+    fn parse_test_results_from_xml() {
+        let mut path = std::env::current_dir().unwrap();
+        path.push("../../demo/phpunit/output.xml");
+        let result = get_test_results_from_xml(path.to_str().unwrap()).unwrap();
+        let summary = result
+            .iter()
+            .map(|test| (test.id.as_str(), test.status, test.duration))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("CalculatorTest::testAdd", TestStatus::Passed, Some(1)),
+                ("CalculatorTest::testSubtract", TestStatus::Passed, Some(0)),
+                ("CalculatorTest::testFail1", TestStatus::Failed, Some(2)),
+            ]
+        );
+        assert_eq!(
+            result[2].path,
+            "/home/kbwo/testing-language-server/demo/phpunit/src/CalculatorTest.php"
+        );
+        assert!(result[2]
+            .output
+            .as_ref()
+            .unwrap()
+            .contains("Failed asserting that 8 matches expected 1."));
+    }
+
     #[test]
     fn test_discover() {
         let file_path = "../../demo/phpunit/src/CalculatorTest.php";
@@ -1,22 +1,29 @@
 use std::collections::{HashMap, HashSet};
-use std::io;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::Output;
+use std::process::{Command, Output, Stdio};
 use std::str::FromStr;
 use std::sync::LazyLock;
 
 use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
 use regex::Regex;
 use serde::Serialize;
-use testing_language_server::spec::{DetectWorkspaceResult, FileDiagnostics, TestItem};
+use serde_json::Value;
+use testing_language_server::spec::{
+    AdapterEvent, DetectWorkspaceResult, FileDiagnostics, FoundFileTests, TestItem, TestResult,
+    TestStatus, ADAPTER_LOG_DIR, STREAM_EVENTS_ENV,
+};
+use testing_language_server::util::{wait_with_lines, OutputStream};
 use testing_language_server::{error::LSError, spec::RunFileTestResult};
 use tree_sitter::{Language, Point, Query, QueryCursor};
+use xml::reader::XmlEvent;
+use xml::ParserConfig;
 
 pub struct DiscoverWithTSOption {}
 
 pub static LOG_LOCATION: LazyLock<PathBuf> = LazyLock::new(|| {
     let home_dir = dirs::home_dir().unwrap();
-    home_dir.join(".config/testing_language_server/adapter/")
+    home_dir.join(ADAPTER_LOG_DIR)
 });
 
 // If the character value is greater than the line length it defaults back to the line length.
@@ -62,7 +69,7 @@ fn detect_workspace_from_file(file_path: PathBuf, file_names: &[String]) -> Opti
             .iter()
             .any(|file_name| parent.join(file_name).exists())
         {
-            return Some(parent.to_string_lossy().to_string());
+            Some(parent.to_string_lossy().to_string())
         } else {
             detect_workspace_from_file(parent.to_path_buf(), file_names)
         }
@@ -118,12 +125,46 @@ where
     Ok(())
 }
 
+/// Whether the server reads stdout as a stream of events
+pub static STREAM_EVENTS: LazyLock<bool> =
+    LazyLock::new(|| std::env::var(STREAM_EVENTS_ENV).is_ok_and(|value| value == "1"));
+
+/// Writes `event` as a line of stdout if the server asked for a stream.
+/// Events are only for reporting progress, so a failure to write one is just logged.
+pub fn send_event(event: &AdapterEvent) {
+    if !*STREAM_EVENTS {
+        return;
+    }
+    tracing::info!("adapter event: {:?}", event);
+    let mut stdout = std::io::stdout().lock();
+    let result = serde_json::to_writer(&mut stdout, event)
+        .map_err(io::Error::from)
+        .and_then(|_| writeln!(stdout))
+        .and_then(|_| stdout.flush());
+    if let Err(err) = result {
+        tracing::error!("Failed to send event: {:?}", err);
+    }
+}
+
+/// Runs `command` like `Command::output`,
+/// calling `on_line` with every line of its output as soon as the test tool writes it.
+pub fn output_with_lines(
+    command: &mut Command,
+    on_line: impl FnMut(OutputStream, &str),
+) -> io::Result<Output> {
+    let child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_with_lines(child, on_line)
+}
+
 pub fn clean_ansi(input: &str) -> String {
-    let re = Regex::new(r"\x1B\[([0-9]{1,2}(;[0-9]{1,2})*)?[m|K]").unwrap();
+    let re = Regex::new(r"\x1B\[([0-9]{1,3}(;[0-9]{1,3})*)?[m|K]").unwrap();
     re.replace_all(input, "").to_string()
 }
 
-pub fn discover_rust_tests(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+pub fn discover_rust_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/rouge8/neotest-rust/blob/0418811e1e3499b2501593f2e131d02f5e6823d4/lua/neotest-rust/init.lua#L167
     // license: https://github.com/rouge8/neotest-rust/blob/0418811e1e3499b2501593f2e131d02f5e6823d4/LICENSE
     let query = r#"
@@ -160,16 +201,22 @@ pub fn discover_rust_tests(file_path: &str) -> Result<Vec<TestItem>, LSError> {
 )
 (mod_item name: (identifier) @namespace.name)? @namespace.definition
 "#;
-    discover_with_treesitter(file_path, &tree_sitter_rust::language(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_rust::language(), query)
 }
 
-pub fn discover_with_treesitter(
+pub fn discover_rust_tests(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+    Ok(discover_rust_file(file_path)?.tests)
+}
+
+/// Discovers the tests of a file together with the namespaces containing them
+pub fn discover_file_with_treesitter(
     file_path: &str,
     language: &Language,
     query: &str,
-) -> Result<Vec<TestItem>, LSError> {
+) -> Result<FoundFileTests, LSError> {
     let mut parser = tree_sitter::Parser::new();
     let mut test_items: Vec<TestItem> = vec![];
+    let mut namespaces: Vec<TestItem> = vec![];
     parser
         .set_language(language)
         .expect("Error loading Rust grammar");
@@ -213,6 +260,38 @@ pub fn discover_with_treesitter(
                     } else {
                         namespace_name = value.to_string();
                     }
+                    if let Some((ns_start, ns_end)) = namespace_position_stack.last() {
+                        if !namespaces
+                            .iter()
+                            .any(|namespace| namespace.id == namespace_name)
+                        {
+                            namespaces.push(TestItem {
+                                id: namespace_name.clone(),
+                                name: value.to_string(),
+                                path: file_path.to_string(),
+                                start_position: Range {
+                                    start: Position {
+                                        line: ns_start.row as u32,
+                                        character: ns_start.column as u32,
+                                    },
+                                    end: Position {
+                                        line: ns_start.row as u32,
+                                        character: MAX_CHAR_LENGTH,
+                                    },
+                                },
+                                end_position: Range {
+                                    start: Position {
+                                        line: ns_end.row as u32,
+                                        character: 0,
+                                    },
+                                    end: Position {
+                                        line: ns_end.row as u32,
+                                        character: ns_end.column as u32,
+                                    },
+                                },
+                            });
+                        }
+                    }
                 }
                 "test.definition" => {
                     if let Some((ns_start, ns_end)) = namespace_position_stack.first() {
@@ -271,7 +350,16 @@ pub fn discover_with_treesitter(
         }
     }
 
-    Ok(test_items)
+    // Only namespaces containing tests, e.g. not every `mod` of a Rust file
+    namespaces.retain(|namespace| {
+        let prefix = format!("{}::", namespace.id);
+        test_items.iter().any(|test| test.id.starts_with(&prefix))
+    });
+    Ok(FoundFileTests {
+        path: file_path.to_string(),
+        tests: test_items,
+        namespaces,
+    })
 }
 
 pub fn parse_cargo_diagnostics(
@@ -283,10 +371,11 @@ pub fn parse_cargo_diagnostics(
     let contents = contents.replace("\r\n", "\n");
     let lines = contents.lines();
     let mut result_map: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+    let mut panic_messages: HashMap<String, String> = HashMap::new();
+    // Example:
+    // thread 'server::tests::test_panic' panicked at src/server.rs:584:9:
+    let re = Regex::new(r"thread '([^']+)' panicked at ([^:]+):(\d+):(\d+):").unwrap();
     for (i, line) in lines.clone().enumerate() {
-        // Example:
-        // thread 'server::tests::test_panic' panicked at src/server.rs:584:9:
-        let re = Regex::new(r"thread '([^']+)' panicked at ([^:]+):(\d+):(\d+):").unwrap();
         if let Some(m) = re.captures(line) {
             let mut message = String::new();
             // <filename>::<id>
@@ -319,6 +408,7 @@ pub fn parse_cargo_diagnostics(
                     message = format!("{}{}\n", message, lines.clone().nth(next_i).unwrap());
                     next_i += 1;
                 }
+                panic_messages.insert(id_with_file.clone(), message.clone());
                 let diagnostic = Diagnostic {
                     range: Range {
                         start: Position {
@@ -384,7 +474,288 @@ pub fn parse_cargo_diagnostics(
     RunFileTestResult {
         data,
         messages: vec![],
+        tests: parse_cargo_test_results(&contents, &workspace_root, test_items, &panic_messages),
+    }
+}
+
+/// Collects per-test results from the status lines of `cargo test` and `cargo nextest`.
+/// Only discovered tests are reported, because results are identified by `TestItem.id`.
+fn parse_cargo_test_results(
+    contents: &str,
+    workspace_root: &Path,
+    test_items: &[TestItem],
+    panic_messages: &HashMap<String, String>,
+) -> Vec<TestResult> {
+    let full_ids = test_items
+        .iter()
+        .map(|item| (rust_test_full_id(workspace_root, item), item))
+        .collect::<HashMap<_, _>>();
+    let mut results: Vec<TestResult> = vec![];
+    for line in contents.lines() {
+        let Some((name, status, duration)) = parse_cargo_test_line(line) else {
+            continue;
+        };
+        let Some(test_item) = full_ids.get(name) else {
+            continue;
+        };
+        // nextest prints the status again in its summary
+        if results
+            .iter()
+            .any(|result| result.id == test_item.id && result.path == test_item.path)
+        {
+            continue;
+        }
+        results.push(TestResult {
+            id: test_item.id.clone(),
+            path: test_item.path.clone(),
+            status,
+            duration,
+            output: panic_messages.get(name).cloned(),
+        });
+    }
+    results
+}
+
+/// Parses a status line of `cargo test` or `cargo nextest` into the full test name, status and duration.
+pub fn parse_cargo_test_line(line: &str) -> Option<(&str, TestStatus, Option<u64>)> {
+    // Example:
+    // test tests::success ... ok
+    static LIBTEST_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^test (\S+) \.\.\. (ok|FAILED|ignored)").unwrap());
+    // Example:
+    //         PASS [   0.004s] rocks-lib rocks::dependency::tests::parse_dependency
+    static NEXTEST_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*([A-Z]+) \[\s*(?:([\d.]+)s)?\s*\] \S+ (\S+)\s*$").unwrap()
+    });
+    if let Some(m) = LIBTEST_RE.captures(line) {
+        let status = match &m[2] {
+            "ok" => TestStatus::Passed,
+            "FAILED" => TestStatus::Failed,
+            _ => TestStatus::Skipped,
+        };
+        Some((m.get(1).unwrap().as_str(), status, None))
+    } else if let Some(m) = NEXTEST_RE.captures(line) {
+        let status = match &m[1] {
+            "PASS" => TestStatus::Passed,
+            "FAIL" => TestStatus::Failed,
+            "SKIP" => TestStatus::Skipped,
+            "TIMEOUT" | "SIGSEGV" | "SIGABRT" | "SIGKILL" | "ABORT" | "LEAK" => TestStatus::Errored,
+            _ => return None,
+        };
+        let duration = m
+            .get(2)
+            .and_then(|seconds| seconds.as_str().parse::<f64>().ok())
+            .map(|seconds| (seconds * 1000.0).round() as u64);
+        Some((m.get(3).unwrap().as_str(), status, duration))
+    } else {
+        None
+    }
+}
+
+/// Builds the fully qualified libtest name of a discovered Rust test
+/// (e.g. `rocks::dependency::tests::parse_dependency` for `src/rocks/dependency.rs`),
+/// which is what `cargo test -- --exact` expects.
+pub fn rust_test_full_id(workspace_root: &Path, test_item: &TestItem) -> String {
+    let item_path = Path::new(&test_item.path);
+    let relative_path = item_path
+        .strip_prefix(workspace_root)
+        .unwrap_or(item_path)
+        .with_extension("");
+    let components: Vec<String> = relative_path
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    // Files outside of `src` (e.g. `tests/*.rs`) and binaries are crate roots
+    let mut module_path: Vec<String> = match components.iter().rposition(|c| c == "src") {
+        Some(index) if components.get(index + 1).map(String::as_str) != Some("bin") => {
+            components[index + 1..].to_vec()
+        }
+        _ => vec![],
+    };
+    if matches!(
+        module_path.last().map(String::as_str),
+        Some("lib") | Some("main") | Some("mod")
+    ) {
+        module_path.pop();
+    }
+    module_path.push(test_item.id.clone());
+    module_path.join("::")
+}
+
+/// Builds the libtest arguments which run exactly the given tests.
+/// Ids that were not discovered are passed through unchanged.
+pub fn rust_exact_test_filters(
+    workspace_root: &Path,
+    discovered_tests: &[TestItem],
+    test_ids: &[String],
+) -> Vec<String> {
+    let mut filters = vec!["--exact".to_string()];
+    filters.extend(test_ids.iter().map(|test_id| {
+        discovered_tests
+            .iter()
+            .find(|item| &item.id == test_id)
+            .map(|item| rust_test_full_id(workspace_root, item))
+            .unwrap_or(test_id.clone())
+    }));
+    filters
+}
+
+/// Builds a regex that exactly matches any of the given test ids.
+/// Namespace separators (`::`) are replaced by `separator`,
+/// because each test tool joins nested names in its own way.
+pub fn test_ids_pattern(test_ids: &[String], separator: &str) -> String {
+    let names = test_ids
+        .iter()
+        .map(|id| regex::escape(&id.replace("::", separator)))
+        .collect::<Vec<_>>()
+        .join("|");
+    format!("^(?:{names})$")
+}
+
+/// Builds the result of a test from an entry of `assertionResults`
+/// in the JSON report of jest and vitest.
+/// The id joins `ancestorTitles` and `title` with `::`, like the ids returned by `discover`.
+pub fn jest_test_result(path: &str, assertion_result: &Value) -> Option<TestResult> {
+    let status = match assertion_result["status"].as_str()? {
+        "passed" => TestStatus::Passed,
+        "failed" => TestStatus::Failed,
+        // pending, skipped, todo, disabled
+        _ => TestStatus::Skipped,
+    };
+    let mut names = assertion_result["ancestorTitles"]
+        .as_array()
+        .map(|titles| titles.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+    names.push(assertion_result["title"].as_str()?);
+    let failure_messages = assertion_result["failureMessages"]
+        .as_array()
+        .map(|messages| {
+            messages
+                .iter()
+                .filter_map(Value::as_str)
+                .map(clean_ansi)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    Some(TestResult {
+        id: names.join("::"),
+        path: path.to_string(),
+        status,
+        duration: assertion_result["duration"]
+            .as_f64()
+            .map(|duration| duration.round() as u64),
+        output: (!failure_messages.is_empty()).then(|| failure_messages.join("\n")),
+    })
+}
+
+/// Adds `result` unless a test with the same id already ran,
+/// e.g. subtests with the same name in several go tests share one id.
+/// A failure of any of them marks the test as failed.
+pub fn push_test_result(results: &mut Vec<TestResult>, result: TestResult) {
+    match results
+        .iter_mut()
+        .find(|existing| existing.id == result.id && existing.path == result.path)
+    {
+        Some(existing) => {
+            if result.status == TestStatus::Failed {
+                *existing = result;
+            }
+        }
+        None => results.push(result),
+    }
+}
+
+/// A `<testcase>` of a JUnit XML report
+#[derive(Debug, PartialEq, Eq)]
+pub struct JunitTestCase {
+    /// Names of the enclosing `<testsuite>`s, outermost first
+    pub suites: Vec<String>,
+    pub name: String,
+    pub class: Option<String>,
+    pub file: Option<String>,
+    pub status: TestStatus,
+    /// Milliseconds
+    pub duration: Option<u64>,
+    pub output: Option<String>,
+}
+
+/// Reads every `<testcase>` of a JUnit XML report, as written by phpunit and node's test runner.
+pub fn parse_junit_test_cases(xml: impl io::Read) -> Vec<JunitTestCase> {
+    use xml::common::Position;
+
+    let mut reader = ParserConfig::default().create_reader(xml);
+    let mut suites: Vec<String> = vec![];
+    let mut current: Option<JunitTestCase> = None;
+    let mut in_output = false;
+    let mut test_cases = vec![];
+    loop {
+        match reader.next() {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == key)
+                        .map(|attribute| attribute.value.clone())
+                };
+                match name.local_name.as_str() {
+                    "testsuite" => suites.push(attribute("name").unwrap_or_default()),
+                    "testcase" => {
+                        current = Some(JunitTestCase {
+                            suites: suites.clone(),
+                            name: attribute("name").unwrap_or_default(),
+                            class: attribute("class").or_else(|| attribute("classname")),
+                            file: attribute("file"),
+                            status: TestStatus::Passed,
+                            duration: attribute("time")
+                                .and_then(|time| time.parse::<f64>().ok())
+                                .map(|seconds| (seconds * 1000.0).round() as u64),
+                            output: None,
+                        })
+                    }
+                    status @ ("failure" | "error" | "skipped") => {
+                        if let Some(test_case) = current.as_mut() {
+                            // node reports failures of todo tests, which do not fail them
+                            if test_case.status == TestStatus::Passed {
+                                test_case.status = match status {
+                                    "failure" => TestStatus::Failed,
+                                    "error" => TestStatus::Errored,
+                                    _ => TestStatus::Skipped,
+                                };
+                            }
+                            test_case.output = attribute("message");
+                            in_output = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::CData(data)) => {
+                if let (true, Some(test_case)) = (in_output, current.as_mut()) {
+                    test_case.output = Some(data.trim().to_string());
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) => match name.local_name.as_str() {
+                "testsuite" => {
+                    suites.pop();
+                }
+                "testcase" => test_cases.extend(current.take()),
+                "failure" | "error" | "skipped" => in_output = false,
+                _ => {}
+            },
+            Ok(XmlEvent::EndDocument) => break,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Error at {}: {e}", reader.position());
+                break;
+            }
+        }
     }
+    test_cases
 }
 
 /// remove this function because duplicate implementation
@@ -417,5 +788,8 @@ pub fn write_result_log(file_name: &str, output: &Output) -> io::Result<()> {
     let content = format!("stdout:\n{}\nstderr:\n{}", stdout, stderr);
     let log_path = LOG_LOCATION.join(file_name);
     std::fs::write(&log_path, content)?;
+    send_event(&AdapterEvent::LogWritten {
+        path: log_path.to_string_lossy().into_owned(),
+    });
     Ok(())
 }
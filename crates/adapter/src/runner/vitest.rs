@@ -7,20 +7,20 @@ use lsp_types::{Diagnostic, DiagnosticSeverity};
 use serde_json::Value;
 use testing_language_server::{
     error::LSError,
-    spec::{DiscoverResult, FileDiagnostics, FoundFileTests, RunFileTestResult, TestItem},
+    spec::{DiscoverResult, FileDiagnostics, FoundFileTests, RunFileTestResult},
 };
 
 use crate::model::Runner;
 
 use super::util::{
-    clean_ansi, detect_workspaces_from_file_list, discover_with_treesitter, send_stdout,
-    LOG_LOCATION, MAX_CHAR_LENGTH,
+    clean_ansi, detect_workspaces_from_file_list, discover_file_with_treesitter, jest_test_result,
+    send_stdout, test_ids_pattern, LOG_LOCATION, MAX_CHAR_LENGTH,
 };
 
 #[derive(Eq, PartialEq, Hash, Debug)]
 pub struct VitestRunner;
 
-fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
+fn discover_file(file_path: &str) -> Result<FoundFileTests, LSError> {
     // from https://github.com/marilari88/neotest-vitest/blob/353364aa05b94b09409cbef21b79c97c5564e2ce/lua/neotest-vitest/init.lua#L101
     let query = r#"
     ; -- Namespaces --
@@ -69,7 +69,7 @@ fn discover(file_path: &str) -> Result<Vec<TestItem>, LSError> {
       arguments: (arguments (string (string_fragment) @test.name) (arrow_function))
     )) @test.definition
 "#;
-    discover_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
+    discover_file_with_treesitter(file_path, &tree_sitter_javascript::language(), query)
 }
 
 fn parse_diagnostics(
@@ -77,6 +77,7 @@ fn parse_diagnostics(
     file_paths: Vec<String>,
 ) -> Result<RunFileTestResult, LSError> {
     let mut result_map: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+    let mut tests = vec![];
     let json: Value = serde_json::from_str(test_result)?;
     let test_results = json["testResults"].as_array().unwrap();
     for test_result in test_results {
@@ -86,6 +87,7 @@ fn parse_diagnostics(
         }
         let assertion_results = test_result["assertionResults"].as_array().unwrap();
         'assertion: for assertion_result in assertion_results {
+            tests.extend(jest_test_result(file_path, assertion_result));
             let status = assertion_result["status"].as_str().unwrap();
             if status != "failed" {
                 continue 'assertion;
@@ -128,9 +130,32 @@ fn parse_diagnostics(
             .map(|(path, diagnostics)| FileDiagnostics { path, diagnostics })
             .collect(),
         messages: vec![],
+        tests,
     })
 }
 
+fn run_vitest(
+    workspace_root: &str,
+    file_paths: Vec<String>,
+    filter_args: &[String],
+) -> Result<RunFileTestResult, LSError> {
+    let log_path = LOG_LOCATION.join("vitest.json");
+    let log_path = log_path.to_str().unwrap();
+    std::process::Command::new("vitest")
+        .current_dir(workspace_root)
+        .args([
+            "--watch=false",
+            "--reporter=json",
+            "--outputFile=",
+            log_path,
+        ])
+        .args(filter_args)
+        .output()
+        .unwrap();
+    let test_result = fs::read_to_string(log_path)?;
+    parse_diagnostics(&test_result, file_paths)
+}
+
 impl Runner for VitestRunner {
     #[tracing::instrument(skip(self))]
     fn discover(&self, args: testing_language_server::spec::DiscoverArgs) -> Result<(), LSError> {
@@ -138,11 +163,7 @@ impl Runner for VitestRunner {
         let mut discover_results: DiscoverResult = DiscoverResult { data: vec![] };
 
         for file_path in file_paths {
-            let tests = discover(&file_path)?;
-            discover_results.data.push(FoundFileTests {
-                tests,
-                path: file_path,
-            });
+            discover_results.data.push(discover_file(&file_path)?);
         }
         send_stdout(&discover_results)?;
         Ok(())
@@ -153,22 +174,19 @@ impl Runner for VitestRunner {
         &self,
         args: testing_language_server::spec::RunFileTestArgs,
     ) -> Result<(), LSError> {
-        let file_paths = args.file_paths;
-        let workspace_root = args.workspace;
-        let log_path = LOG_LOCATION.join("vitest.json");
-        let log_path = log_path.to_str().unwrap();
-        std::process::Command::new("vitest")
-            .current_dir(&workspace_root)
-            .args([
-                "--watch=false",
-                "--reporter=json",
-                "--outputFile=",
-                log_path,
-            ])
-            .output()
-            .unwrap();
-        let test_result = fs::read_to_string(log_path)?;
-        let diagnostics: RunFileTestResult = parse_diagnostics(&test_result, file_paths)?;
+        let diagnostics = run_vitest(&args.workspace, args.file_paths, &[])?;
+        send_stdout(&diagnostics)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn run_test(&self, args: testing_language_server::spec::RunTestArgs) -> Result<(), LSError> {
+        let mut filter_args = vec![
+            "--testNamePattern".to_string(),
+            test_ids_pattern(&args.test_ids, " "),
+        ];
+        filter_args.extend(args.file_paths.clone());
+        let diagnostics = run_vitest(&args.workspace, args.file_paths, &filter_args)?;
         send_stdout(&diagnostics)?;
         Ok(())
     }
@@ -201,11 +219,12 @@ mod tests {
     use lsp_types::{Position, Range};
 
     use super::*;
+    use testing_language_server::spec::TestItem;
 
     #[test]
     fn test_discover() {
         let file_path = "../../demo/vitest/basic.test.ts";
-        let test_items = discover(file_path).unwrap();
+        let test_items = discover_file(file_path).unwrap().tests;
         assert_eq!(test_items.len(), 2);
         assert_eq!(
             test_items,
@@ -0,0 +1,364 @@
+use crate::config::TOML_FILE_NAME;
+use crate::sarif::sarif_log;
+use crate::sarif::FileReport;
+use crate::server::TestingLS;
+use clap::Args;
+use clap::ValueEnum;
+use lsp_types::Diagnostic;
+use lsp_types::DiagnosticSeverity;
+use lsp_types::MessageType;
+use lsp_types::PublishDiagnosticsParams;
+use lsp_types::ShowMessageParams;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use testing_language_server::error::LSError;
+use testing_language_server::spec::AdapterId;
+use testing_language_server::spec::TestResult;
+use testing_language_server::spec::TestStatus;
+use testing_language_server::util::resolve_path;
+use testing_language_server::util::set_message_handler;
+
+/// Arguments for `testing-language-server check`
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Files to test. All files of the configured adapters are tested when none is given.
+    pub paths: Vec<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+    /// Directory of the project, defaults to the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Diagnostics grouped by file and a summary
+    Human,
+    /// One JSON object with the diagnostics and the test results
+    Json,
+    /// One `path:line:column: severity: message` line per diagnostic
+    Machine,
+    /// SARIF 2.1.0 log with a result for every failing test
+    Sarif,
+}
+
+/// Results collected from the messages the server publishes
+#[derive(Default)]
+struct Report {
+    /// Last diagnostics of every tested file, by path
+    files: BTreeMap<String, Vec<Diagnostic>>,
+    tests: Vec<TestResult>,
+    /// Errors of the adapters or of the configuration, printed as they arrive
+    errors: Vec<String>,
+    /// Adapter of every tested file
+    adapters: HashMap<String, AdapterId>,
+}
+
+impl Report {
+    fn record(&mut self, message: &Value) {
+        let params = &message["params"];
+        match message["method"].as_str() {
+            Some("textDocument/publishDiagnostics") => {
+                if let Ok(params) = PublishDiagnosticsParams::deserialize(params) {
+                    let path = params.uri.to_file_path().map_or_else(
+                        |_| params.uri.to_string(),
+                        |path| path.to_string_lossy().into_owned(),
+                    );
+                    // The configuration is published even without problems, to clear old ones
+                    if params.diagnostics.is_empty() && path.ends_with(TOML_FILE_NAME) {
+                        return;
+                    }
+                    self.files.insert(path, params.diagnostics);
+                }
+            }
+            Some("$/testResults") => {
+                if let Ok(tests) = Vec::<TestResult>::deserialize(&params["tests"]) {
+                    self.tests.extend(tests);
+                }
+            }
+            Some("window/showMessage") => {
+                if let Ok(params) = ShowMessageParams::deserialize(params) {
+                    eprintln!("{}", params.message);
+                    if params.typ == MessageType::ERROR {
+                        self.errors.push(params.message);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn count(&self, status: TestStatus) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.status == status)
+            .count()
+    }
+
+    /// Whether a test failed or an error was reported, which makes the command exit with 1
+    fn failed(&self) -> bool {
+        let has_error = self.files.values().flatten().any(is_error);
+        has_error
+            || !self.errors.is_empty()
+            || self.count(TestStatus::Failed) > 0
+            || self.count(TestStatus::Errored) > 0
+    }
+
+    fn render(&self, format: Format, project_dir: &Path) -> String {
+        let relative = |path: &str| {
+            Path::new(path)
+                .strip_prefix(project_dir)
+                .map_or(path.to_string(), |path| path.display().to_string())
+        };
+        match format {
+            Format::Human => {
+                let mut lines = vec![];
+                for (path, diagnostics) in &self.files {
+                    if diagnostics.is_empty() {
+                        continue;
+                    }
+                    let label = if diagnostics.iter().any(is_error) {
+                        "FAIL"
+                    } else {
+                        "WARN"
+                    };
+                    lines.push(format!("{label} {}", relative(path)));
+                    for diagnostic in diagnostics {
+                        let mut message = diagnostic.message.lines();
+                        lines.push(format!(
+                            "  {}:{} {}: {}",
+                            diagnostic.range.start.line + 1,
+                            diagnostic.range.start.character + 1,
+                            severity(diagnostic),
+                            message.next().unwrap_or_default()
+                        ));
+                        lines.extend(message.map(|line| format!("      {line}")));
+                    }
+                    lines.push(String::new());
+                }
+                let mut summary = vec![format!("{} files tested", self.files.len())];
+                for (status, name) in [
+                    (TestStatus::Passed, "passed"),
+                    (TestStatus::Failed, "failed"),
+                    (TestStatus::Skipped, "skipped"),
+                    (TestStatus::Errored, "errored"),
+                ] {
+                    let count = self.count(status);
+                    if count > 0 {
+                        summary.push(format!("{count} {name}"));
+                    }
+                }
+                let diagnostics = self.files.values().flatten().count();
+                summary.push(format!("{diagnostics} diagnostics"));
+                lines.push(summary.join(", "));
+                lines.join("\n") + "\n"
+            }
+            Format::Json => {
+                let files = self
+                    .files
+                    .iter()
+                    .map(|(path, diagnostics)| json!({ "path": path, "diagnostics": diagnostics }))
+                    .collect::<Vec<_>>();
+                let report = json!({
+                    "files": files,
+                    "tests": self.tests,
+                    "summary": {
+                        "files": self.files.len(),
+                        "diagnostics": self.files.values().flatten().count(),
+                        "passed": self.count(TestStatus::Passed),
+                        "failed": self.count(TestStatus::Failed),
+                        "skipped": self.count(TestStatus::Skipped),
+                        "errored": self.count(TestStatus::Errored),
+                    },
+                    "errors": self.errors,
+                    "success": !self.failed(),
+                });
+                serde_json::to_string_pretty(&report).unwrap() + "\n"
+            }
+            Format::Machine => self
+                .files
+                .iter()
+                .flat_map(|(path, diagnostics)| {
+                    diagnostics.iter().map(move |diagnostic| {
+                        format!(
+                            "{}:{}:{}: {}: {}\n",
+                            relative(path),
+                            diagnostic.range.start.line + 1,
+                            diagnostic.range.start.character + 1,
+                            severity(diagnostic),
+                            diagnostic.message.lines().next().unwrap_or_default()
+                        )
+                    })
+                })
+                .collect(),
+            Format::Sarif => {
+                let files = self
+                    .files
+                    .iter()
+                    .map(|(path, diagnostics)| FileReport {
+                        adapter: self.adapters.get(path).cloned(),
+                        path: path.clone(),
+                        diagnostics: diagnostics.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::to_string_pretty(&sarif_log(&files, project_dir)).unwrap() + "\n"
+            }
+        }
+    }
+}
+
+/// Diagnostics without a severity are errors, as clients show them
+fn is_error(diagnostic: &Diagnostic) -> bool {
+    matches!(diagnostic.severity, None | Some(DiagnosticSeverity::ERROR))
+}
+
+fn severity(diagnostic: &Diagnostic) -> &'static str {
+    match diagnostic.severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "error",
+    }
+}
+
+/// Runs the tests like the server does for a client and prints the results.
+/// Returns whether every test passed.
+pub fn run(args: CheckArgs) -> Result<bool, LSError> {
+    if let Some(project_dir) = &args.project_dir {
+        std::env::set_current_dir(project_dir)?;
+    }
+    let project_dir = std::env::current_dir()?;
+    let paths = args
+        .paths
+        .iter()
+        .map(|path| {
+            resolve_path(&project_dir, &path.to_string_lossy())
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect::<Vec<_>>();
+
+    let report = Arc::new(Mutex::new(Report::default()));
+    {
+        let report = Arc::clone(&report);
+        set_message_handler(move |message| report.lock().unwrap().record(&message));
+    }
+    let mut server = TestingLS::new();
+    let tested = server.check_headless(&paths)?;
+
+    let mut report = report.lock().unwrap();
+    report.adapters = server.file_adapters();
+    for path in tested {
+        if !report.files.contains_key(&path) {
+            let message = format!("The adapter returned no result for {path}");
+            eprintln!("{message}");
+            report.errors.push(message);
+        }
+    }
+    print!("{}", report.render(args.format, &project_dir));
+    Ok(!report.failed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+    use lsp_types::Range;
+    use lsp_types::Url;
+
+    fn report() -> Report {
+        let mut report = Report::default();
+        let diagnostic = Diagnostic {
+            range: Range::new(Position::new(11, 4), Position::new(11, 20)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            message: "assertion failed\nleft: 1\nright: 2".to_string(),
+            ..Diagnostic::default()
+        };
+        report.record(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": PublishDiagnosticsParams::new(
+                Url::from_file_path("/demo/src/lib.rs").unwrap(),
+                vec![diagnostic],
+                None,
+            ),
+        }));
+        report.record(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": PublishDiagnosticsParams::new(
+                Url::from_file_path("/demo/src/main.rs").unwrap(),
+                vec![],
+                None,
+            ),
+        }));
+        report.record(&json!({
+            "jsonrpc": "2.0",
+            "method": "$/testResults",
+            "params": { "tests": [
+                { "id": "tests::fail", "path": "/demo/src/lib.rs", "status": "failed" },
+                { "id": "tests::pass", "path": "/demo/src/lib.rs", "status": "passed", "duration": 3 },
+            ] },
+        }));
+        report
+    }
+
+    #[test]
+    fn render_human_and_machine() {
+        let report = report();
+        assert!(report.failed());
+        assert_eq!(
+            report.render(Format::Human, Path::new("/demo")),
+            "FAIL src/lib.rs
+  12:5 error: assertion failed
+      left: 1
+      right: 2
+
+2 files tested, 1 passed, 1 failed, 1 diagnostics
+"
+        );
+        assert_eq!(
+            report.render(Format::Machine, Path::new("/demo")),
+            "src/lib.rs:12:5: error: assertion failed\n"
+        );
+    }
+
+    #[test]
+    fn render_json_summary() {
+        let report = report();
+        let json: Value =
+            serde_json::from_str(&report.render(Format::Json, Path::new("/demo"))).unwrap();
+        assert_eq!(
+            json["summary"],
+            json!({ "files": 2, "diagnostics": 1, "passed": 1, "failed": 1, "skipped": 0, "errored": 0 })
+        );
+        assert_eq!(json["success"], json!(false));
+        assert_eq!(json["tests"][1]["duration"], json!(3));
+    }
+
+    #[test]
+    fn passed_tests_and_warnings_do_not_fail() {
+        let mut report = Report::default();
+        report.files.insert(
+            "/demo/src/lib.rs".to_string(),
+            vec![Diagnostic {
+                severity: Some(DiagnosticSeverity::WARNING),
+                ..Diagnostic::default()
+            }],
+        );
+        assert!(!report.failed());
+        report.record(&json!({
+            "jsonrpc": "2.0",
+            "method": "window/showMessage",
+            "params": { "type": 1, "message": "Error occurred when running test via adapter" },
+        }));
+        assert!(report.failed());
+    }
+}
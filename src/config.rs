@@ -0,0 +1,850 @@
+use crate::file_index::FileIndex;
+use crate::file_index::Matcher;
+use crate::spec::AdapterConfiguration;
+use crate::spec::AdapterId;
+use crate::util::resolve_path;
+use glob::Pattern;
+use lsp_types::Diagnostic;
+use lsp_types::DiagnosticSeverity;
+use lsp_types::Position;
+use lsp_types::Range;
+use serde::de::DeserializeOwned;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use toml_edit::ImDocument;
+use toml_edit::Item;
+use toml_edit::Key;
+
+pub const TOML_FILE_NAME: &str = ".testingls.toml";
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializedOptions {
+    pub adapter_command: HashMap<AdapterId, AdapterConfiguration>,
+    pub enable_workspace_diagnostics: Option<bool>,
+    /// Maximum number of adapters running at the same time. Defaults to the number of CPUs.
+    pub concurrency: Option<usize>,
+    /// File to write a JUnit report of all adapters to, after the whole workspace is tested
+    pub junit_report: Option<String>,
+}
+
+const OPTION_KEYS: [&str; 4] = [
+    "adapterCommand",
+    "enableWorkspaceDiagnostics",
+    "concurrency",
+    "junitReport",
+];
+const ADAPTER_KEYS: [&str; 7] = [
+    "path",
+    "extra_arg",
+    "env",
+    "include",
+    "exclude",
+    "workspace_dir",
+    "timeout",
+];
+const REQUIRED_ADAPTER_KEYS: [&str; 3] = ["path", "include", "exclude"];
+
+/// Parses the content of `.testingls.toml`.
+/// Invalid adapters are left out of the options, and every problem is returned
+/// as a diagnostic on the file, so that the server keeps running with the valid adapters.
+pub fn parse_config(content: &str, project_dir: &Path) -> (InitializedOptions, Vec<Diagnostic>) {
+    let mut validator = Validator {
+        content,
+        diagnostics: vec![],
+    };
+    let mut options = InitializedOptions::default();
+    let document = match ImDocument::parse(content) {
+        Ok(document) => document,
+        Err(err) => {
+            validator.error(err.span(), err.message().to_string());
+            return (options, validator.diagnostics);
+        }
+    };
+    let root = document.as_table();
+    for (name, item) in root.iter() {
+        match name {
+            "adapterCommand" => {}
+            "enableWorkspaceDiagnostics" => {
+                options.enable_workspace_diagnostics = validator.value(item)
+            }
+            "concurrency" => options.concurrency = validator.value(item),
+            "junitReport" => options.junit_report = validator.value(item),
+            _ => validator.unknown_key(root.key(name).unwrap(), &OPTION_KEYS),
+        }
+    }
+    match root.get("adapterCommand") {
+        Some(item) => match item.as_table_like() {
+            Some(adapters) => {
+                for (id, adapter) in adapters.iter() {
+                    let key = adapters.key(id).unwrap();
+                    if let Some(adapter) = validator.adapter(key, adapter, project_dir) {
+                        options.adapter_command.insert(id.to_string(), adapter);
+                    }
+                }
+            }
+            None => validator.error(item.span(), "`adapterCommand` must be a table".to_string()),
+        },
+        None => validator.error(None, "Missing required key `adapterCommand`".to_string()),
+    }
+    (options, validator.diagnostics)
+}
+
+struct Validator<'a> {
+    content: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    /// Items without a span are reported at the start of the file
+    fn error(&mut self, span: Option<std::ops::Range<usize>>, message: String) {
+        let span = span.unwrap_or(0..0);
+        self.diagnostics.push(Diagnostic {
+            range: Range::new(
+                self.position(span.start),
+                self.position(span.end.max(span.start)),
+            ),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("testing-language-server".to_string()),
+            message,
+            ..Diagnostic::default()
+        });
+    }
+
+    /// Converts a byte offset of the content to an LSP position, whose character is counted in UTF-16
+    fn position(&self, offset: usize) -> Position {
+        let mut offset = offset.min(self.content.len());
+        while !self.content.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &self.content[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        Position::new(
+            before.matches('\n').count() as u32,
+            before[line_start..].encode_utf16().count() as u32,
+        )
+    }
+
+    fn unknown_key(&mut self, key: &Key, expected: &[&str]) {
+        let expected = expected
+            .iter()
+            .map(|key| format!("`{key}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.error(
+            key.span(),
+            format!("Unknown key `{}`, expected one of {expected}", key.get()),
+        );
+    }
+
+    fn value<T: DeserializeOwned>(&mut self, item: &Item) -> Option<T> {
+        let result = match item.clone().into_value() {
+            Ok(value) => T::deserialize(value.into_deserializer()).map_err(|err| err.to_string()),
+            Err(_) => Err("expected a value".to_string()),
+        };
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.error(item.span(), format!("Invalid value: {}", err.trim()));
+                None
+            }
+        }
+    }
+
+    fn adapter(
+        &mut self,
+        id: &Key,
+        item: &Item,
+        project_dir: &Path,
+    ) -> Option<AdapterConfiguration> {
+        let Some(table) = item.as_table_like() else {
+            self.error(
+                item.span().or(id.span()),
+                format!("Adapter `{}` must be a table", id.get()),
+            );
+            return None;
+        };
+        let errors = self.diagnostics.len();
+        for (name, value) in table.iter() {
+            match name {
+                "path" | "workspace_dir" => self.value::<String>(value).map(|_| ()),
+                "extra_arg" | "include" | "exclude" => self.value::<Vec<String>>(value).map(|_| ()),
+                "env" => self.value::<HashMap<String, String>>(value).map(|_| ()),
+                "timeout" => self.value::<u64>(value).map(|_| ()),
+                _ => {
+                    self.unknown_key(table.key(name).unwrap(), &ADAPTER_KEYS);
+                    None
+                }
+            };
+        }
+        for required in REQUIRED_ADAPTER_KEYS {
+            if !table.contains_key(required) {
+                self.error(
+                    id.span(),
+                    format!(
+                        "Adapter `{}` is missing the required key `{required}`",
+                        id.get()
+                    ),
+                );
+            }
+        }
+        if self.diagnostics.len() > errors {
+            return None;
+        }
+
+        let value = item.clone().into_value().ok()?;
+        let adapter = match AdapterConfiguration::deserialize(value.into_deserializer()) {
+            Ok(adapter) => adapter,
+            Err(err) => {
+                self.error(
+                    id.span(),
+                    format!("Invalid adapter `{}`: {}", id.get(), err.message()),
+                );
+                return None;
+            }
+        };
+        if adapter.include.is_empty() {
+            self.error(
+                table.get("include").and_then(Item::span),
+                format!("Adapter `{}` has no `include` patterns", id.get()),
+            );
+        }
+        // Paths with variables are only known when the adapter runs
+        let has_variables = adapter.path.contains("${") || adapter.path.starts_with('~');
+        if !has_variables && !command_exists(&adapter.path, project_dir) {
+            self.error(
+                table.get("path").and_then(Item::span),
+                format!("`{}` is not found on PATH", adapter.path),
+            );
+        }
+        for pattern in &adapter.include {
+            // Same as how `include` is matched by the server
+            if let Err(err) = Matcher::new(project_dir, std::slice::from_ref(pattern), &[]) {
+                let span = Self::pattern_span(table.get("include"), pattern);
+                self.error(span, format!("Invalid glob pattern `{pattern}`: {err}"));
+            }
+        }
+        for pattern in &adapter.exclude {
+            if let Err(err) = Pattern::new(pattern) {
+                let span = Self::pattern_span(table.get("exclude"), pattern);
+                self.error(span, format!("Invalid glob pattern `{pattern}`: {err}"));
+            }
+        }
+        (self.diagnostics.len() == errors).then_some(adapter)
+    }
+
+    /// Span of `pattern` in the array of patterns, or of the whole array if it is not found
+    fn pattern_span(patterns: Option<&Item>, pattern: &str) -> Option<std::ops::Range<usize>> {
+        let patterns = patterns?;
+        patterns
+            .as_array()
+            .and_then(|array| {
+                array
+                    .iter()
+                    .find(|value| value.as_str() == Some(pattern))
+                    .and_then(|value| value.span())
+            })
+            .or(patterns.span())
+    }
+}
+
+/// Adapters which test the files below `dir`, the directory of a `.testingls.toml`.
+/// The adapters of the parent directories are inherited, unless they are overridden
+/// by an adapter with the same id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigScope {
+    pub dir: PathBuf,
+    /// Adapters with the directory which their globs are relative to
+    pub adapters: HashMap<AdapterId, (PathBuf, AdapterConfiguration)>,
+}
+
+/// Directories below `project_dir` which have their own `.testingls.toml`
+pub fn nested_config_dirs(project_dir: &Path, files: &FileIndex) -> Vec<PathBuf> {
+    let mut dirs = indexed_files(project_dir, files, &[format!("**/{TOML_FILE_NAME}")])
+        .into_iter()
+        .filter_map(|path| Some(path.parent()?.to_path_buf()))
+        .filter(|dir| dir != project_dir)
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs
+}
+
+/// Scope of the project configuration and of every nested one, parents before their children
+pub fn config_scopes(
+    project_dir: &Path,
+    adapters: &HashMap<AdapterId, AdapterConfiguration>,
+    nested: &[(PathBuf, InitializedOptions)],
+) -> Vec<ConfigScope> {
+    let own_adapters = |dir: &Path, adapters: &HashMap<AdapterId, AdapterConfiguration>| {
+        adapters
+            .iter()
+            .map(|(id, adapter)| {
+                let mut adapter = adapter.clone();
+                // Commands given as a relative path are relative to the configuration, like globs.
+                // Bare names are looked up on PATH, and variables are expanded when it runs.
+                if Path::new(&adapter.path).components().count() > 1
+                    && !adapter.path.starts_with(['$', '~'])
+                {
+                    adapter.path = resolve_path(dir, &adapter.path)
+                        .to_string_lossy()
+                        .into_owned();
+                }
+                (id.clone(), (dir.to_path_buf(), adapter))
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut scopes = vec![ConfigScope {
+        dir: project_dir.to_path_buf(),
+        adapters: own_adapters(project_dir, adapters).into_iter().collect(),
+    }];
+    let mut nested = nested.iter().collect::<Vec<_>>();
+    // An ancestor sorts before its descendants
+    nested.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (dir, options) in nested {
+        let mut adapters = nearest_scope(&scopes, dir)
+            .map(|parent| parent.adapters.clone())
+            .unwrap_or_default();
+        adapters.extend(own_adapters(dir, &options.adapter_command));
+        scopes.push(ConfigScope {
+            dir: dir.clone(),
+            adapters,
+        });
+    }
+    scopes
+}
+
+/// The scope of the configuration nearest to `path`
+pub fn nearest_scope<'a>(scopes: &'a [ConfigScope], path: &Path) -> Option<&'a ConfigScope> {
+    scopes
+        .iter()
+        .filter(|scope| path.starts_with(&scope.dir))
+        .max_by_key(|scope| scope.dir.components().count())
+}
+
+/// Command of the adapter bundled with the server
+const ADAPTER_COMMAND: &str = "testing-ls-adapter";
+
+/// Test tools which are detected from the files of a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum DetectedKind {
+    CargoTest,
+    CargoNextest,
+    Jest,
+    Vitest,
+    Deno,
+    GoTest,
+    Phpunit,
+    NodeTest,
+}
+
+impl DetectedKind {
+    /// Same as the `--test-kind` of the adapter
+    fn name(&self) -> &'static str {
+        match self {
+            DetectedKind::CargoTest => "cargo-test",
+            DetectedKind::CargoNextest => "cargo-nextest",
+            DetectedKind::Jest => "jest",
+            DetectedKind::Vitest => "vitest",
+            DetectedKind::Deno => "deno",
+            DetectedKind::GoTest => "go-test",
+            DetectedKind::Phpunit => "phpunit",
+            DetectedKind::NodeTest => "node-test",
+        }
+    }
+
+    /// Test files below `dir`, which is relative to the project with a leading `/`
+    fn include(&self, dir: &str) -> Vec<String> {
+        let patterns: &[&str] = match self {
+            DetectedKind::CargoTest | DetectedKind::CargoNextest => &["**/*.rs"],
+            DetectedKind::Jest | DetectedKind::Vitest => {
+                &["**/*.{test,spec}.{js,jsx,ts,tsx,mjs,cjs,mts,cts}"]
+            }
+            DetectedKind::Deno => &["**/*{_,.}test.{ts,tsx,js,jsx,mts,mjs}"],
+            DetectedKind::GoTest => &["**/*_test.go"],
+            DetectedKind::Phpunit => &["**/*Test.php"],
+            DetectedKind::NodeTest => &[NODE_TEST_FILES],
+        };
+        patterns
+            .iter()
+            .map(|pattern| format!("{dir}/{pattern}"))
+            .collect()
+    }
+
+    fn exclude(&self) -> Vec<String> {
+        let patterns: &[&str] = match self {
+            DetectedKind::CargoTest | DetectedKind::CargoNextest => &["/**/target/**"],
+            DetectedKind::Jest
+            | DetectedKind::Vitest
+            | DetectedKind::Deno
+            | DetectedKind::NodeTest => &["/**/node_modules/**"],
+            DetectedKind::GoTest | DetectedKind::Phpunit => &["/**/vendor/**"],
+        };
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    /// Kinds of the project whose marker file is `path`
+    fn from_marker(path: &Path) -> Vec<DetectedKind> {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        match file_name {
+            Some("Cargo.toml") => vec![DetectedKind::CargoTest],
+            Some("nextest.toml") => vec![DetectedKind::CargoNextest],
+            Some("deno.json") | Some("deno.jsonc") => vec![DetectedKind::Deno],
+            Some("go.mod") => vec![DetectedKind::GoTest],
+            Some("package.json") => {
+                let dependencies = json_dependencies(path, &["dependencies", "devDependencies"]);
+                [
+                    ("jest", DetectedKind::Jest),
+                    ("vitest", DetectedKind::Vitest),
+                ]
+                .into_iter()
+                .filter(|(name, _)| dependencies.iter().any(|dependency| dependency == name))
+                .map(|(_, kind)| kind)
+                .collect()
+            }
+            Some("composer.json") => {
+                let dependencies = json_dependencies(path, &["require", "require-dev"]);
+                if dependencies.iter().any(|name| name == "phpunit/phpunit") {
+                    vec![DetectedKind::Phpunit]
+                } else {
+                    vec![]
+                }
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// Files which are run by `node --test` when no other javascript test tool is detected
+const NODE_TEST_FILES: &str = "**/*.test.{js,mjs,cjs}";
+
+/// Names of the packages in the dependency `sections` of a JSON manifest
+fn json_dependencies(path: &Path, sections: &[&str]) -> Vec<String> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return vec![];
+    };
+    let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&content) else {
+        tracing::warn!("Failed to parse {:?}", path);
+        return vec![];
+    };
+    sections
+        .iter()
+        .filter_map(|section| manifest.get(section).and_then(|deps| deps.as_object()))
+        .flat_map(|deps| deps.keys().cloned())
+        .collect()
+}
+
+/// Indexed files of `project_dir` matching `patterns`
+fn indexed_files(project_dir: &Path, files: &FileIndex, patterns: &[String]) -> Vec<PathBuf> {
+    files
+        .files(project_dir, patterns, &[])
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Path relative to the project with a leading `/`, or an empty string for the project itself
+fn relative_dir(project_dir: &Path, dir: &Path) -> Option<String> {
+    let dir = dir.strip_prefix(project_dir).ok()?;
+    Some(
+        dir.components()
+            .map(|component| format!("/{}", component.as_os_str().to_string_lossy()))
+            .collect(),
+    )
+}
+
+/// An adapter configured from the files of a project
+pub struct DetectedAdapter {
+    pub id: AdapterId,
+    pub config: AdapterConfiguration,
+    /// Files the adapter was detected from, relative to the project
+    pub sources: Vec<String>,
+}
+
+/// Configures an adapter for every test tool whose marker file is found in the project,
+/// e.g. `Cargo.toml` or a `package.json` which depends on jest.
+/// Test files named like `*.test.js` are run by `node --test` when no other javascript test tool is found.
+pub fn detect_adapters(project_dir: &Path, files: &FileIndex) -> Vec<DetectedAdapter> {
+    let patterns = [
+        "Cargo.toml",
+        ".config/nextest.toml",
+        "package.json",
+        "deno.json",
+        "deno.jsonc",
+        "go.mod",
+        "composer.json",
+    ]
+    .iter()
+    .map(|marker| format!("**/{marker}"))
+    .collect::<Vec<_>>();
+
+    // Directories of the markers and the markers themselves, relative to the project
+    let mut kind_dirs: BTreeMap<DetectedKind, (Vec<String>, Vec<String>)> = BTreeMap::new();
+    for path in indexed_files(project_dir, files, &patterns) {
+        let Some(source) = relative_dir(project_dir, &path) else {
+            continue;
+        };
+        let mut dir = path.parent();
+        if path.ends_with(".config/nextest.toml") {
+            dir = dir.and_then(Path::parent);
+        }
+        let Some(dir) = dir.and_then(|dir| relative_dir(project_dir, dir)) else {
+            continue;
+        };
+        for kind in DetectedKind::from_marker(&path) {
+            let (dirs, sources) = kind_dirs.entry(kind).or_default();
+            dirs.push(dir.clone());
+            sources.push(source.clone());
+        }
+    }
+    // nextest runs the same tests as cargo test
+    if let Some((dirs, sources)) = kind_dirs.remove(&DetectedKind::CargoTest) {
+        match kind_dirs.get_mut(&DetectedKind::CargoNextest) {
+            Some((_, nextest_sources)) => nextest_sources.extend(sources),
+            None => {
+                kind_dirs.insert(DetectedKind::CargoTest, (dirs, sources));
+            }
+        }
+    }
+    let has_javascript_tool = [DetectedKind::Jest, DetectedKind::Vitest, DetectedKind::Deno]
+        .iter()
+        .any(|kind| kind_dirs.contains_key(kind));
+    if !has_javascript_tool {
+        let test_files = indexed_files(project_dir, files, &[NODE_TEST_FILES.to_string()]);
+        if !test_files.is_empty() {
+            let sources = test_files
+                .iter()
+                .filter_map(|path| relative_dir(project_dir, path))
+                .collect();
+            kind_dirs.insert(DetectedKind::NodeTest, (vec![String::new()], sources));
+        }
+    }
+
+    kind_dirs
+        .into_iter()
+        .map(|(kind, (mut dirs, mut sources))| {
+            dirs.sort();
+            dirs.dedup();
+            sources.sort();
+            // Members of a cargo workspace or of a monorepo are covered by the root project
+            let roots = dirs
+                .iter()
+                .filter(|dir| {
+                    !dirs.iter().any(|root| {
+                        root != *dir && (root.is_empty() || dir.starts_with(&format!("{root}/")))
+                    })
+                })
+                .collect::<Vec<_>>();
+            DetectedAdapter {
+                id: kind.name().to_string(),
+                config: AdapterConfiguration {
+                    path: ADAPTER_COMMAND.to_string(),
+                    extra_arg: vec![format!("--test-kind={}", kind.name())],
+                    include: roots.iter().flat_map(|dir| kind.include(dir)).collect(),
+                    exclude: kind.exclude(),
+                    ..AdapterConfiguration::default()
+                },
+                sources,
+            }
+        })
+        .collect()
+}
+
+/// Options for projects without `.testingls.toml` or initialization options
+pub fn detect_options(project_dir: &Path, files: &FileIndex) -> InitializedOptions {
+    InitializedOptions {
+        adapter_command: detect_adapters(project_dir, files)
+            .into_iter()
+            .map(|adapter| (adapter.id, adapter.config))
+            .collect(),
+        ..InitializedOptions::default()
+    }
+}
+
+/// Whether `command` can be spawned, either as a path or as a name found on PATH
+fn command_exists(command: &str, project_dir: &Path) -> bool {
+    if Path::new(command).components().count() > 1 {
+        return Path::new(command).is_file() || resolve_path(project_dir, command).is_file();
+    }
+    let Some(paths) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&paths).any(|dir| {
+        let candidate = dir.join(command);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<(u32, u32, u32, &str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.range.start.line,
+                    diagnostic.range.start.character,
+                    diagnostic.range.end.character,
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_adapters_are_kept() {
+        let content = r#"enableWorkspaceDiagnostics = true
+concurency = 2
+
+[adapterCommand.rust]
+path = "${workspaceFolder}/target/debug/testing-ls-adapter"
+extra_arg = ["--test-kind=cargo-test"]
+include = ["/**/*.rs"]
+exclude = ["/**/target/**"]
+
+[adapterCommand.jest]
+path = "sh"
+include = ["/**/*.js"]
+exlude = []
+"#;
+        let (options, diagnostics) = parse_config(content, Path::new("/"));
+        assert_eq!(options.enable_workspace_diagnostics, Some(true));
+        assert_eq!(
+            options.adapter_command.keys().collect::<Vec<_>>(),
+            vec!["rust"]
+        );
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                (
+                    1,
+                    0,
+                    10,
+                    "Unknown key `concurency`, expected one of `adapterCommand`, `enableWorkspaceDiagnostics`, `concurrency`, `junitReport`"
+                ),
+                (
+                    12,
+                    0,
+                    6,
+                    "Unknown key `exlude`, expected one of `path`, `extra_arg`, `env`, `include`, `exclude`, `workspace_dir`, `timeout`"
+                ),
+                (
+                    9,
+                    16,
+                    20,
+                    "Adapter `jest` is missing the required key `exclude`"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_values_command_and_globs() {
+        let content = r#"[adapterCommand.deno]
+path = "sh"
+include = ["/**/*.ts"]
+exclude = []
+timeout = "60"
+
+[adapterCommand.go]
+path = "sh"
+include = []
+exclude = []
+
+[adapterCommand.rust]
+path = "testing-ls-missing-adapter"
+include = ["/**/*.rs", "/[*.rs"]
+exclude = ["/**/target/**", "/a**b"]
+"#;
+        let (options, diagnostics) = parse_config(content, Path::new("/"));
+        assert!(options.adapter_command.is_empty());
+        let messages = messages(&diagnostics);
+        assert_eq!(messages.len(), 5);
+        assert_eq!(
+            messages[0],
+            (
+                4,
+                10,
+                14,
+                "Invalid value: invalid type: string \"60\", expected u64"
+            )
+        );
+        assert_eq!(
+            messages[1],
+            (8, 10, 12, "Adapter `go` has no `include` patterns")
+        );
+        assert_eq!(
+            messages[2],
+            (
+                12,
+                7,
+                35,
+                "`testing-ls-missing-adapter` is not found on PATH"
+            )
+        );
+        assert_eq!((messages[3].0, messages[3].1, messages[3].2), (13, 23, 31));
+        assert!(messages[3].3.starts_with("Invalid glob pattern `/[*.rs`"));
+        assert_eq!((messages[4].0, messages[4].1, messages[4].2), (14, 28, 35));
+        assert!(messages[4].3.starts_with("Invalid glob pattern `/a**b`"));
+    }
+
+    #[test]
+    fn syntax_error_has_position() {
+        let (options, diagnostics) = parse_config("[adapterCommand.rust\npath = 1", Path::new("/"));
+        assert!(options.adapter_command.is_empty());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 0);
+    }
+
+    #[test]
+    fn nested_configs_inherit_and_override_adapters() {
+        let adapter = |kind: &str| AdapterConfiguration {
+            path: "testing-ls-adapter".to_string(),
+            extra_arg: vec![format!("--test-kind={kind}")],
+            include: vec!["/**/*".to_string()],
+            ..Default::default()
+        };
+        let options = |adapters: &[(&str, &str)]| InitializedOptions {
+            adapter_command: adapters
+                .iter()
+                .map(|(id, kind)| (id.to_string(), adapter(kind)))
+                .collect(),
+            ..InitializedOptions::default()
+        };
+        let root = options(&[("rust", "cargo-test"), ("js", "jest")]);
+        let nested = [
+            (
+                PathBuf::from("/demo/packages/web/legacy"),
+                options(&[("js", "jest")]),
+            ),
+            (
+                PathBuf::from("/demo/packages/web"),
+                options(&[("js", "vitest")]),
+            ),
+        ];
+        let scopes = config_scopes(Path::new("/demo"), &root.adapter_command, &nested);
+        let summary = |path: &str| {
+            let scope = nearest_scope(&scopes, Path::new(path)).unwrap();
+            let mut adapters = scope
+                .adapters
+                .iter()
+                .map(|(id, (dir, adapter))| {
+                    (
+                        id.as_str(),
+                        dir.to_str().unwrap(),
+                        adapter.extra_arg[0].as_str(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            adapters.sort();
+            (scope.dir.to_str().unwrap(), adapters)
+        };
+        assert_eq!(
+            summary("/demo/packages/web/src/index.test.ts"),
+            (
+                "/demo/packages/web",
+                vec![
+                    ("js", "/demo/packages/web", "--test-kind=vitest"),
+                    ("rust", "/demo", "--test-kind=cargo-test"),
+                ]
+            )
+        );
+        assert_eq!(
+            summary("/demo/packages/web/legacy/index.spec.js"),
+            (
+                "/demo/packages/web/legacy",
+                vec![
+                    ("js", "/demo/packages/web/legacy", "--test-kind=jest"),
+                    ("rust", "/demo", "--test-kind=cargo-test"),
+                ]
+            )
+        );
+        assert_eq!(summary("/demo/src/lib.rs").0, "/demo");
+        assert!(nearest_scope(&scopes, Path::new("/other/lib.rs")).is_none());
+    }
+
+    #[test]
+    fn detect_adapters_from_markers() {
+        let dir = std::env::temp_dir().join(format!("testing-ls-detect-{}", std::process::id()));
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("Cargo.toml", "[workspace]");
+        write("crates/adapter/Cargo.toml", "[package]");
+        write(
+            "web/package.json",
+            r#"{"devDependencies": {"vitest": "^2.0.0"}}"#,
+        );
+        write(
+            "web/node_modules/jest/package.json",
+            r#"{"dependencies": {"jest": "*"}}"#,
+        );
+        write("web/.gitignore", "node_modules/\n");
+        write("api/go.mod", "module api");
+        write("php/composer.json", r#"{"require": {"php": "^8.2"}}"#);
+
+        let options = detect_options(&dir, &FileIndex::new(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut adapters = options
+            .adapter_command
+            .iter()
+            .map(|(id, adapter)| (id.as_str(), adapter.include.clone()))
+            .collect::<Vec<_>>();
+        adapters.sort();
+        assert_eq!(
+            adapters,
+            vec![
+                ("cargo-test", vec!["/**/*.rs".to_string()]),
+                ("go-test", vec!["/api/**/*_test.go".to_string()]),
+                (
+                    "vitest",
+                    vec!["/web/**/*.{test,spec}.{js,jsx,ts,tsx,mjs,cjs,mts,cts}".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(
+            options.adapter_command["vitest"].extra_arg,
+            vec!["--test-kind=vitest"]
+        );
+    }
+
+    #[test]
+    fn detect_nextest_and_node_test() {
+        let dir = std::env::temp_dir().join(format!("testing-ls-nextest-{}", std::process::id()));
+        let write = |path: &str, content: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("Cargo.toml", "[package]");
+        write(".config/nextest.toml", "[profile.default]");
+        write("scripts/build.test.mjs", "");
+
+        let adapters = detect_adapters(&dir, &FileIndex::new(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let adapters = adapters
+            .iter()
+            .map(|adapter| (adapter.id.as_str(), adapter.sources.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            adapters,
+            vec![
+                (
+                    "cargo-nextest",
+                    vec![
+                        "/.config/nextest.toml".to_string(),
+                        "/Cargo.toml".to_string()
+                    ]
+                ),
+                ("node-test", vec!["/scripts/build.test.mjs".to_string()]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use crate::results::TestRecord;
+use std::time::Duration;
+use std::time::SystemTime;
+use testing_language_server::spec::TestStatus;
+
+/// Lines of output shown in a hover, the last ones are kept
+const MAX_OUTPUT_LINES: usize = 20;
+
+pub fn status_symbol(status: TestStatus) -> &'static str {
+    match status {
+        TestStatus::Passed => "✓",
+        TestStatus::Failed => "✗",
+        TestStatus::Skipped => "○",
+        TestStatus::Errored => "⚠",
+    }
+}
+
+pub fn status_name(status: TestStatus) -> &'static str {
+    match status {
+        TestStatus::Passed => "passed",
+        TestStatus::Failed => "failed",
+        TestStatus::Skipped => "skipped",
+        TestStatus::Errored => "errored",
+    }
+}
+
+/// Run time of a test, e.g. `12ms`, `1.52s` or `2m 3s`
+pub fn format_duration(milliseconds: u64) -> String {
+    match milliseconds {
+        0..=999 => format!("{milliseconds}ms"),
+        1_000..=59_999 => format!("{:.2}s", milliseconds as f64 / 1000.0),
+        _ => format!(
+            "{}m {}s",
+            milliseconds / 60_000,
+            milliseconds % 60_000 / 1000
+        ),
+    }
+}
+
+/// How long ago `time` was, e.g. `just now` or `2m ago`
+pub fn format_ago(time: SystemTime, now: SystemTime) -> String {
+    let seconds = now.duration_since(time).unwrap_or(Duration::ZERO).as_secs();
+    match seconds {
+        0..=9 => "just now".to_string(),
+        10..=59 => format!("{seconds}s ago"),
+        60..=3_599 => format!("{}m ago", seconds / 60),
+        3_600..=86_399 => format!("{}h ago", seconds / 3_600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+/// Label of the inlay hint after a test, e.g. `✓ 12ms` or `✗ failed 2m ago`
+pub fn inlay_label(record: &TestRecord, now: SystemTime) -> String {
+    let status = record.result.status;
+    match (status, record.result.duration) {
+        (TestStatus::Passed, Some(duration)) => {
+            format!("{} {}", status_symbol(status), format_duration(duration))
+        }
+        _ => format!(
+            "{} {} {}",
+            status_symbol(status),
+            status_name(status),
+            format_ago(record.finished_at, now)
+        ),
+    }
+}
+
+/// The last `MAX_OUTPUT_LINES` lines of `text`
+fn truncate_output(text: &str) -> String {
+    let lines = text.trim_end().lines().collect::<Vec<_>>();
+    if lines.len() <= MAX_OUTPUT_LINES {
+        return lines.join("\n");
+    }
+    let omitted = lines.len() - MAX_OUTPUT_LINES;
+    format!("… {omitted} lines omitted\n{}", lines[omitted..].join("\n"))
+}
+
+/// Markdown of `textDocument/hover` for the test `name`, with its last result when it has run
+pub fn hover_markdown(name: &str, record: Option<&TestRecord>, now: SystemTime) -> String {
+    let Some(record) = record else {
+        return format!("**{name}**\n\nNot run yet");
+    };
+    let status = record.result.status;
+    let mut markdown = format!(
+        "**{name}** {} {}",
+        status_symbol(status),
+        status_name(status)
+    );
+    if let Some(duration) = record.result.duration {
+        markdown.push_str(&format!(" in {}", format_duration(duration)));
+    }
+    let finished_at = chrono::DateTime::<chrono::Local>::from(record.finished_at);
+    markdown.push_str(&format!(
+        "\n\nLast run {}, {}",
+        format_ago(record.finished_at, now),
+        finished_at.format("%Y-%m-%d %H:%M:%S")
+    ));
+    let output = record.result.output.as_deref().unwrap_or_default();
+    for (title, text) in [("Output", output), ("Adapter stderr", &record.stderr)] {
+        if !text.trim().is_empty() {
+            markdown.push_str(&format!(
+                "\n\n{title}:\n```text\n{}\n```",
+                truncate_output(text)
+            ));
+        }
+    }
+    markdown.push_str(&format!("\n\nCommand:\n```sh\n{}\n```", record.command));
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing_language_server::spec::TestResult;
+
+    #[test]
+    fn hover_and_inlay_hint_of_a_test() {
+        let now = SystemTime::now();
+        let output = (1..=25)
+            .map(|line| format!("line {line}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut record = TestRecord {
+            result: TestResult {
+                id: "tests::fails".to_string(),
+                path: "/demo/src/lib.rs".to_string(),
+                status: TestStatus::Failed,
+                duration: Some(1520),
+                output: Some(output),
+            },
+            finished_at: now - Duration::from_secs(150),
+            command: "cd /demo && adapter run-test --test-ids tests::fails".to_string(),
+            stderr: String::new(),
+        };
+        let markdown = hover_markdown("fails", Some(&record), now);
+        assert!(markdown.starts_with("**fails** ✗ failed in 1.52s\n\nLast run 2m ago, "));
+        assert!(markdown.contains("Output:\n```text\n… 5 lines omitted\nline 6\n"));
+        assert!(markdown.contains("line 25\n```"));
+        assert!(!markdown.contains("Adapter stderr"));
+        assert!(markdown.ends_with(
+            "Command:\n```sh\ncd /demo && adapter run-test --test-ids tests::fails\n```"
+        ));
+        assert_eq!(
+            hover_markdown("fails", None, now),
+            "**fails**\n\nNot run yet"
+        );
+        assert_eq!(inlay_label(&record, now), "✗ failed 2m ago");
+        record.result.status = TestStatus::Passed;
+        record.result.duration = Some(12);
+        assert_eq!(inlay_label(&record, now), "✓ 12ms");
+        assert_eq!(format_duration(12), "12ms");
+        assert_eq!(format_duration(123_000), "2m 3s");
+    }
+}
@@ -13,6 +13,12 @@ pub enum LSError {
     #[error("Adapter error")]
     Adapter(String),
 
+    #[error("Request cancelled")]
+    Cancelled,
+
+    #[error("Adapter timed out")]
+    Timeout,
+
     #[error("UTF8 error")]
     UTF8(#[from] std::str::Utf8Error),
 
@@ -22,3 +28,19 @@ pub enum LSError {
     #[error("Unknown error")]
     Any(#[from] anyhow::Error),
 }
+
+impl LSError {
+    /// JSON-RPC error code of a request which failed with this error
+    pub fn code(&self) -> i64 {
+        match self {
+            // Invalid params
+            LSError::Serialization(_) => -32602,
+            // Request cancelled
+            LSError::Cancelled => -32800,
+            // Request failed
+            LSError::Adapter(_) | LSError::Timeout => -32803,
+            // Internal error
+            _ => -32603,
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use glob::Pattern;
+use ignore::gitignore::Gitignore;
+use ignore::overrides::Override;
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Files of a workspace folder, without the ones ignored by `.gitignore` or `.ignore`.
+/// It is walked once, then kept up to date from the file events of the client.
+#[derive(Debug, Default, Clone)]
+pub struct FileIndex {
+    root: PathBuf,
+    files: BTreeSet<PathBuf>,
+}
+
+impl FileIndex {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            files: walk(root, root).collect(),
+        }
+    }
+
+    /// Adds a created file, or the files of a created directory, unless they are ignored
+    pub fn add(&mut self, path: &Path) {
+        if path.starts_with(&self.root) {
+            self.files.extend(walk(&self.root, path));
+        }
+    }
+
+    /// Removes a deleted file, or the files of a deleted directory
+    pub fn remove(&mut self, path: &Path) {
+        self.files.retain(|file| !file.starts_with(path));
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    /// Whether `path` is an indexed file, or a directory with indexed files
+    pub fn contains_below(&self, path: &Path) -> bool {
+        self.files
+            .range(path.to_path_buf()..)
+            .next()
+            .is_some_and(|file| file.starts_with(path))
+    }
+
+    /// Whether `path` is left out of the index, because it is ignored, in `.git` or outside of the root.
+    /// Unlike `add`, the root is not walked, so that the many events of ignored directories are cheap.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+        if relative
+            .components()
+            .any(|component| component.as_os_str() == ".git")
+        {
+            return true;
+        }
+        let is_dir = path.is_dir();
+        // The ignore files of deeper directories take precedence, and `.ignore` over `.gitignore`
+        let ignore_files = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .flat_map(|dir| [dir.join(".ignore"), dir.join(".gitignore")])
+            .filter(|file| file.is_file());
+        for ignore_file in ignore_files {
+            let (gitignore, _) = Gitignore::new(&ignore_file);
+            let matched = gitignore.matched_path_or_any_parents(path, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+        false
+    }
+
+    /// Files which match `include` and none of `exclude`
+    pub fn files(&self, base_dir: &Path, include: &[String], exclude: &[String]) -> Vec<String> {
+        let Some(matcher) = Matcher::logged(base_dir, include, exclude) else {
+            return vec![];
+        };
+        self.files
+            .iter()
+            .filter(|path| matcher.matches(path))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// Whether `path` is indexed and matches `include` and none of `exclude`
+    pub fn matches(
+        &self,
+        path: &Path,
+        base_dir: &Path,
+        include: &[String],
+        exclude: &[String],
+    ) -> bool {
+        self.contains(path)
+            && Matcher::logged(base_dir, include, exclude)
+                .is_some_and(|matcher| matcher.matches(path))
+    }
+}
+
+/// Files below `path`, or `path` itself, which are not ignored.
+/// The walk starts at `root`, so that the ignore files of every parent of `path` apply.
+fn walk(root: &Path, path: &Path) -> impl Iterator<Item = PathBuf> {
+    let path = path.to_path_buf();
+    WalkBuilder::new(root)
+        .hidden(false)
+        // `.gitignore` is respected in directories which are not a git repository too
+        .require_git(false)
+        .follow_links(true)
+        .filter_entry(move |entry| {
+            entry.file_name() != ".git"
+                && (path.starts_with(entry.path()) || entry.path().starts_with(&path))
+        })
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| entry.into_path())
+}
+
+/// `include` and `exclude` patterns of an adapter
+pub struct Matcher {
+    base_dir: PathBuf,
+    include: Override,
+    exclude: Vec<Pattern>,
+}
+
+impl Matcher {
+    /// Fails on an invalid `include` pattern. Invalid `exclude` patterns are left out.
+    pub fn new(
+        base_dir: &Path,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Self, ignore::Error> {
+        // Include patterns are matched like gitignore globs with `/` anchored at `base_dir`
+        let mut builder = OverrideBuilder::new(base_dir);
+        for pattern in include {
+            builder.add(pattern)?;
+        }
+        let include = builder.build()?;
+        let exclude = exclude
+            .iter()
+            .filter_map(|pattern| Pattern::new(base_dir.join(pattern).to_str()?).ok())
+            .collect();
+        Ok(Self {
+            base_dir: base_dir.to_path_buf(),
+            include,
+            exclude,
+        })
+    }
+
+    /// Like `new`, logging the error of an invalid pattern
+    fn logged(base_dir: &Path, include: &[String], exclude: &[String]) -> Option<Self> {
+        Self::new(base_dir, include, exclude)
+            .inspect_err(|err| tracing::error!("Invalid include patterns {:?}: {:?}", include, err))
+            .ok()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.starts_with(&self.base_dir)
+            && self.include.matched(path, false).is_whitelist()
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches_path(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignored_files_are_not_indexed() {
+        let dir = std::env::temp_dir().join(format!("testing-ls-index-{}", std::process::id()));
+        let write = |path: &str| {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        };
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".gitignore"), "/target\n*.snap\n").unwrap();
+        write("src/lib.rs");
+        write("src/lib.snap");
+        write("target/debug/build.rs");
+        write("web/index.spec.js");
+        write("web/another.spec.js");
+
+        let mut index = FileIndex::new(&dir);
+        let include = ["/**/*.rs".to_string()];
+        let path = |path: &str| dir.join(path).to_string_lossy().into_owned();
+        assert_eq!(index.files(&dir, &include, &[]), vec![path("src/lib.rs")]);
+        let include = ["/web/*.spec.js".to_string()];
+        assert_eq!(
+            index.files(&dir, &include, &["web/another.spec.js".to_string()]),
+            vec![path("web/index.spec.js")]
+        );
+
+        write("src/tests/new.rs");
+        write("target/debug/new.rs");
+        index.add(&dir.join("src/tests"));
+        index.add(&dir.join("target/debug/new.rs"));
+        assert!(index.is_ignored(&dir.join("target/debug/new.rs")));
+        assert!(index.is_ignored(&dir.join("src/tests/new.snap")));
+        assert!(index.is_ignored(&dir.join(".git/HEAD")));
+        assert!(!index.is_ignored(&dir.join("src/tests/new.rs")));
+        assert!(index.contains_below(&dir.join("src/tests")));
+        assert!(!index.contains_below(&dir.join("target")));
+        std::fs::remove_dir_all(dir.join("web")).unwrap();
+        index.remove(&dir.join("web"));
+        let all = ["/**/*".to_string()];
+        let files = index.files(&dir, &all, &[]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                path(".gitignore"),
+                path("src/lib.rs"),
+                path("src/tests/new.rs")
+            ]
+        );
+        assert!(index.matches(Path::new(&path("src/tests/new.rs")), &dir, &all, &[]));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::config::detect_adapters;
+use crate::config::DetectedAdapter;
+use crate::config::TOML_FILE_NAME;
+use crate::file_index::FileIndex;
+use clap::Args;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use testing_language_server::error::LSError;
+
+/// Number of matched files listed for a pattern
+const LISTED_FILES: usize = 5;
+
+/// Arguments for `testing-language-server init`
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    /// Print the configuration and the files matched by each pattern instead of writing it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Ask which of the detected adapters to add
+    #[arg(short, long)]
+    pub interactive: bool,
+    /// Overwrite an existing .testingls.toml
+    #[arg(long)]
+    pub force: bool,
+    /// Directory of the project, defaults to the current directory
+    #[arg(long)]
+    pub project_dir: Option<PathBuf>,
+}
+
+pub fn run(args: InitArgs) -> Result<(), LSError> {
+    let project_dir = match args.project_dir {
+        Some(project_dir) => project_dir,
+        None => std::env::current_dir()?,
+    };
+    let toml_path = project_dir.join(TOML_FILE_NAME);
+    if toml_path.exists() && !args.force && !args.dry_run {
+        return Err(LSError::Any(anyhow::anyhow!(
+            "{} already exists, use --force to overwrite it",
+            toml_path.display()
+        )));
+    }
+    let index = FileIndex::new(&project_dir);
+    let mut adapters = detect_adapters(&project_dir, &index);
+    if adapters.is_empty() {
+        println!("No test tool was detected in {}", project_dir.display());
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+    if args.interactive {
+        let mut stdin = io::stdin().lock();
+        let mut selected = vec![];
+        for adapter in adapters {
+            write!(
+                stdout,
+                "{}",
+                describe_matches(&project_dir, &index, &adapter)
+            )?;
+            if ask(&mut stdin, &mut stdout, &format!("Add `{}`?", adapter.id))? {
+                selected.push(adapter);
+            }
+        }
+        adapters = selected;
+    } else if args.dry_run {
+        for adapter in &adapters {
+            write!(
+                stdout,
+                "{}",
+                describe_matches(&project_dir, &index, adapter)
+            )?;
+        }
+    }
+
+    let content = render_config(&adapters);
+    if args.dry_run {
+        writeln!(stdout, "\n# {}\n{content}", toml_path.display())?;
+    } else {
+        std::fs::write(&toml_path, content)?;
+        writeln!(stdout, "Wrote {}", toml_path.display())?;
+    }
+    Ok(())
+}
+
+/// Asks a yes/no question, where an empty answer is yes
+fn ask(input: &mut impl BufRead, output: &mut impl Write, question: &str) -> io::Result<bool> {
+    loop {
+        write!(output, "{question} [Y/n] ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(false);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "" | "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+/// Files matched by each `include` and `exclude` pattern, as they are filtered by the server
+fn describe_matches(project_dir: &Path, index: &FileIndex, adapter: &DetectedAdapter) -> String {
+    let relative = |path: &String| {
+        Path::new(path)
+            .strip_prefix(project_dir)
+            .map_or(path.clone(), |path| path.display().to_string())
+    };
+    let list = |lines: &mut Vec<String>, files: &[String]| {
+        for file in files.iter().take(LISTED_FILES) {
+            lines.push(format!("      {}", relative(file)));
+        }
+        if files.len() > LISTED_FILES {
+            lines.push(format!("      ... and {} more", files.len() - LISTED_FILES));
+        }
+    };
+    let config = &adapter.config;
+    let mut lines = vec![format!(
+        "{} (detected from {})",
+        adapter.id,
+        adapter.sources.join(", ")
+    )];
+    for pattern in &config.include {
+        let files = index.files(project_dir, std::slice::from_ref(pattern), &[]);
+        lines.push(format!("  include {pattern}: {} files", files.len()));
+        list(&mut lines, &files);
+    }
+    let included = index.files(project_dir, &config.include, &[]);
+    for pattern in &config.exclude {
+        let kept = index.files(project_dir, &config.include, std::slice::from_ref(pattern));
+        let excluded = included
+            .iter()
+            .filter(|file| !kept.contains(file))
+            .cloned()
+            .collect::<Vec<_>>();
+        lines.push(format!("  exclude {pattern}: {} files", excluded.len()));
+        list(&mut lines, &excluded);
+    }
+    let tested = index.files(project_dir, &config.include, &config.exclude);
+    lines.push(format!("  {} files are tested\n", tested.len()));
+    lines.join("\n") + "\n"
+}
+
+fn render_config(adapters: &[DetectedAdapter]) -> String {
+    let string = |value: &str| toml::Value::from(value).to_string();
+    let array = |values: &[String]| {
+        toml::Value::Array(values.iter().map(|value| value.as_str().into()).collect()).to_string()
+    };
+    let mut content = String::from(
+        "# Configuration of testing-language-server, generated by `testing-language-server init`
+# Patterns are relative to the directory of this file.
+
+# Run the tests of every file when the workspace is opened
+enableWorkspaceDiagnostics = true
+# Maximum number of adapters running at the same time (default: number of CPUs)
+# concurrency = 4
+",
+    );
+    for adapter in adapters {
+        let config = &adapter.config;
+        let mut sources = adapter.sources.iter().take(3).cloned().collect::<Vec<_>>();
+        if adapter.sources.len() > 3 {
+            sources.push("...".to_string());
+        }
+        content.push_str(&format!(
+            "
+# Detected from {sources}
+[adapterCommand.{id}]
+path = {path}
+extra_arg = {extra_arg}
+# Test files
+include = {include}
+# Files which are never tested, even if they match `include`
+exclude = {exclude}
+# Stop the tests after this many seconds and report the ones still running
+# timeout = 60
+",
+            sources = sources.join(", "),
+            id = adapter.id,
+            path = string(&config.path),
+            extra_arg = array(&config.extra_arg),
+            include = array(&config.include),
+            exclude = array(&config.exclude),
+        ));
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+    use testing_language_server::spec::AdapterConfiguration;
+
+    #[test]
+    fn rendered_config_is_valid() {
+        let adapter = DetectedAdapter {
+            id: "cargo-test".to_string(),
+            config: AdapterConfiguration {
+                path: "sh".to_string(),
+                extra_arg: vec!["--test-kind=cargo-test".to_string()],
+                include: vec!["/**/*.rs".to_string()],
+                exclude: vec!["/**/target/**".to_string()],
+                ..AdapterConfiguration::default()
+            },
+            sources: vec!["/Cargo.toml".to_string()],
+        };
+        let content = render_config(&[adapter]);
+        assert!(content.contains("# Detected from /Cargo.toml\n[adapterCommand.cargo-test]\n"));
+        let (options, diagnostics) = parse_config(&content, Path::new("/"));
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(options.enable_workspace_diagnostics, Some(true));
+        assert_eq!(
+            options.adapter_command["cargo-test"].include,
+            vec!["/**/*.rs"]
+        );
+    }
+
+    #[test]
+    fn empty_answer_is_yes() {
+        let mut output = vec![];
+        let mut answers = "\nmaybe\nn\n".as_bytes();
+        assert!(ask(&mut answers, &mut output, "Add?").unwrap());
+        assert!(!ask(&mut answers, &mut output, "Add?").unwrap());
+        assert!(!ask(&mut answers, &mut output, "Add?").unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Add? [Y/n] Add? [Y/n] Add? [Y/n] Add? [Y/n] "
+        );
+    }
+}
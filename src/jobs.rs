@@ -0,0 +1,168 @@
+use std::num::NonZeroUsize;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+use testing_language_server::util::set_thread_sink;
+use testing_language_server::util::thread_sink;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed number of worker threads running queued jobs in order of arrival.
+/// Adapter runs are pushed here so that the main loop keeps serving requests while tests run.
+/// A job sends its messages to the sink of the thread which pushed it, that is to its client.
+pub struct JobQueue {
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl JobQueue {
+    /// Starts `concurrency` workers, or one per available CPU when not set.
+    pub fn new(concurrency: Option<usize>) -> Self {
+        let concurrency = concurrency
+            .and_then(NonZeroUsize::new)
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let workers = (0..concurrency)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let cancelled = Arc::clone(&cancelled);
+                thread::spawn(move || loop {
+                    // The lock is released before the job runs
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        // Left out after `cancel`
+                        Ok(_) if cancelled.load(Ordering::Relaxed) => {}
+                        Ok(job) => {
+                            // A panicking job must not take its worker down with it
+                            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                                tracing::error!("A job panicked");
+                            }
+                        }
+                        // The queue is dropped
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+            cancelled,
+        }
+    }
+
+    pub fn push<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(sender) = &self.sender {
+            let sink = thread_sink();
+            sender
+                .send(Box::new(move || {
+                    set_thread_sink(sink);
+                    job();
+                }))
+                .unwrap();
+        }
+    }
+
+    /// Drops the queued jobs and the ones pushed later. The running jobs are not stopped.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for JobQueue {
+    /// Waits for the queued jobs to finish.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+    use testing_language_server::util::send_stdout;
+
+    #[test]
+    fn runs_jobs_up_to_concurrency_limit() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+        let queue = JobQueue::new(Some(2));
+        for _ in 0..6 {
+            let running = Arc::clone(&running);
+            let max_running = Arc::clone(&max_running);
+            let finished = Arc::clone(&finished);
+            queue.push(move || {
+                let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now_running, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                running.fetch_sub(1, Ordering::SeqCst);
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(queue);
+        assert_eq!(finished.load(Ordering::SeqCst), 6);
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn cancelled_jobs_do_not_run_and_keep_their_sink() {
+        let queue = JobQueue::new(Some(1));
+        let (started, running) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel();
+        let sent = Arc::new(Mutex::new(vec![]));
+        let sink = {
+            let sent = Arc::clone(&sent);
+            Arc::new(move |message| sent.lock().unwrap().push(message))
+        };
+        let previous = set_thread_sink(Some(sink));
+        queue.push(move || {
+            started.send(()).unwrap();
+            receiver.recv().unwrap();
+            send_stdout(&"first").unwrap();
+        });
+        queue.push(|| send_stdout(&"second").unwrap());
+        set_thread_sink(previous);
+        // The first job is running, the second one is queued
+        running.recv().unwrap();
+        queue.cancel();
+        sender.send(()).unwrap();
+        drop(queue);
+        assert_eq!(*sent.lock().unwrap(), vec!["first"]);
+    }
+
+    #[test]
+    fn push_does_not_wait_for_job() {
+        let queue = JobQueue::new(Some(1));
+        let (sender, receiver) = mpsc::channel();
+        queue.push(move || {
+            receiver.recv().unwrap();
+        });
+        // The job is still blocked, but pushing returned
+        sender.send(()).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+use lsp_types::Diagnostic;
+use std::path::Path;
+use testing_language_server::error::LSError;
+use testing_language_server::spec::AdapterId;
+use testing_language_server::spec::TestResult;
+use testing_language_server::spec::TestStatus;
+use xml::common::XmlVersion;
+use xml::writer::EmitterConfig;
+use xml::writer::EventWriter;
+use xml::writer::XmlEvent;
+
+/// Results of the files an adapter tested in a workspace, which become a `testsuite`
+pub struct SuiteReport {
+    pub adapter: AdapterId,
+    pub workspace: String,
+    pub files: Vec<FileTests>,
+}
+
+pub struct FileTests {
+    pub path: String,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Empty when the adapter does not report single tests
+    pub tests: Vec<TestResult>,
+}
+
+enum Outcome {
+    Passed,
+    Failed { message: String, text: String },
+    Errored { message: String, text: String },
+    Skipped,
+}
+
+struct TestCase {
+    name: String,
+    /// The file of the test, relative to the project
+    classname: String,
+    /// Seconds
+    time: Option<f64>,
+    outcome: Outcome,
+}
+
+/// First line of `text`, used as the `message` attribute
+fn summary(text: &str, default: &str) -> String {
+    text.lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or(default)
+        .to_string()
+}
+
+fn test_cases(file: &FileTests, project_dir: &Path) -> Vec<TestCase> {
+    let classname = Path::new(&file.path)
+        .strip_prefix(project_dir)
+        .map_or(file.path.clone(), |path| path.display().to_string());
+    // Adapters which do not report single tests get one case for the whole file
+    if file.tests.is_empty() {
+        let outcome = match file.diagnostics.first() {
+            None => Outcome::Passed,
+            Some(first) => Outcome::Failed {
+                message: summary(&first.message, "failed"),
+                text: file
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        format!(
+                            "{}:{}: {}",
+                            diagnostic.range.start.line + 1,
+                            diagnostic.range.start.character + 1,
+                            diagnostic.message
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+        };
+        return vec![TestCase {
+            name: classname.clone(),
+            classname,
+            time: None,
+            outcome,
+        }];
+    }
+    file.tests
+        .iter()
+        .map(|test| {
+            let text = test.output.clone().unwrap_or_default();
+            let outcome = match test.status {
+                TestStatus::Passed => Outcome::Passed,
+                TestStatus::Skipped => Outcome::Skipped,
+                TestStatus::Failed => Outcome::Failed {
+                    message: summary(&text, "failed"),
+                    text,
+                },
+                TestStatus::Errored => Outcome::Errored {
+                    message: summary(&text, "errored"),
+                    text,
+                },
+            };
+            TestCase {
+                name: test.id.clone(),
+                classname: classname.clone(),
+                time: test.duration.map(|duration| duration as f64 / 1000.0),
+                outcome,
+            }
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Counts {
+    tests: usize,
+    failures: usize,
+    errors: usize,
+    skipped: usize,
+    time: f64,
+}
+
+impl Counts {
+    fn add(&mut self, case: &TestCase) {
+        self.tests += 1;
+        self.time += case.time.unwrap_or_default();
+        match case.outcome {
+            Outcome::Passed => {}
+            Outcome::Failed { .. } => self.failures += 1,
+            Outcome::Errored { .. } => self.errors += 1,
+            Outcome::Skipped => self.skipped += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &Counts) {
+        self.tests += other.tests;
+        self.failures += other.failures;
+        self.errors += other.errors;
+        self.skipped += other.skipped;
+        self.time += other.time;
+    }
+
+    /// Attributes of `testsuites` and `testsuite`
+    fn attributes(&self) -> [(&'static str, String); 5] {
+        [
+            ("tests", self.tests.to_string()),
+            ("failures", self.failures.to_string()),
+            ("errors", self.errors.to_string()),
+            ("skipped", self.skipped.to_string()),
+            ("time", format!("{:.3}", self.time)),
+        ]
+    }
+}
+
+fn start_element<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    name: &str,
+    attributes: &[(&str, String)],
+) -> xml::writer::Result<()> {
+    let mut element = XmlEvent::start_element(name);
+    for (key, value) in attributes {
+        element = element.attr(*key, value);
+    }
+    writer.write(element)
+}
+
+/// JUnit XML report with a `testsuite` for every adapter and workspace.
+/// Paths are relative to `project_dir`.
+pub fn junit_xml(suites: &[SuiteReport], project_dir: &Path) -> Result<String, LSError> {
+    let suites = suites
+        .iter()
+        .map(|suite| {
+            let cases = suite
+                .files
+                .iter()
+                .flat_map(|file| test_cases(file, project_dir))
+                .collect::<Vec<_>>();
+            let mut counts = Counts::default();
+            cases.iter().for_each(|case| counts.add(case));
+            let workspace = Path::new(&suite.workspace)
+                .strip_prefix(project_dir)
+                .map_or(suite.workspace.clone(), |path| path.display().to_string());
+            let workspace = if workspace.is_empty() {
+                ".".to_string()
+            } else {
+                workspace
+            };
+            (format!("{} ({workspace})", suite.adapter), counts, cases)
+        })
+        .collect::<Vec<_>>();
+    let mut total = Counts::default();
+    suites.iter().for_each(|(_, counts, _)| total.merge(counts));
+
+    let mut buffer = vec![];
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(&mut buffer);
+    let write = |writer: &mut EventWriter<&mut Vec<u8>>| -> xml::writer::Result<()> {
+        // The implicit declaration spells the encoding differently across xml-rs versions
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("UTF-8"),
+            standalone: None,
+        })?;
+        let mut attributes = vec![("name", "testing-language-server".to_string())];
+        attributes.extend(total.attributes());
+        start_element(writer, "testsuites", &attributes)?;
+        for (name, counts, cases) in &suites {
+            let mut attributes = vec![("name", name.clone())];
+            attributes.extend(counts.attributes());
+            start_element(writer, "testsuite", &attributes)?;
+            for case in cases {
+                let mut attributes = vec![
+                    ("name", case.name.clone()),
+                    ("classname", case.classname.clone()),
+                    ("file", case.classname.clone()),
+                ];
+                if let Some(time) = case.time {
+                    attributes.push(("time", format!("{time:.3}")));
+                }
+                start_element(writer, "testcase", &attributes)?;
+                match &case.outcome {
+                    Outcome::Passed => {}
+                    Outcome::Skipped => {
+                        start_element(writer, "skipped", &[])?;
+                        writer.write(XmlEvent::end_element())?;
+                    }
+                    Outcome::Failed { message, text } | Outcome::Errored { message, text } => {
+                        let element = if matches!(case.outcome, Outcome::Failed { .. }) {
+                            "failure"
+                        } else {
+                            "error"
+                        };
+                        start_element(writer, element, &[("message", message.clone())])?;
+                        if !text.is_empty() {
+                            writer.write(XmlEvent::characters(text))?;
+                        }
+                        writer.write(XmlEvent::end_element())?;
+                    }
+                }
+                writer.write(XmlEvent::end_element())?;
+            }
+            writer.write(XmlEvent::end_element())?;
+        }
+        writer.write(XmlEvent::end_element())
+    };
+    write(&mut writer).map_err(|err| LSError::Any(err.into()))?;
+    String::from_utf8(buffer).map_err(LSError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+    use lsp_types::Range;
+
+    #[test]
+    fn suites_per_adapter_and_workspace() {
+        let test = |id: &str, status, duration, output: Option<&str>| TestResult {
+            id: id.to_string(),
+            path: "/demo/crates/core/src/lib.rs".to_string(),
+            status,
+            duration,
+            output: output.map(str::to_string),
+        };
+        let suites = [
+            SuiteReport {
+                adapter: "cargo-test".to_string(),
+                workspace: "/demo/crates/core".to_string(),
+                files: vec![FileTests {
+                    path: "/demo/crates/core/src/lib.rs".to_string(),
+                    diagnostics: vec![],
+                    tests: vec![
+                        test(
+                            "tests::fails",
+                            TestStatus::Failed,
+                            Some(12),
+                            Some("assertion `left == right` failed\n  left: 1"),
+                        ),
+                        test("tests::passes", TestStatus::Passed, Some(3), None),
+                        test("tests::ignored", TestStatus::Skipped, None, None),
+                    ],
+                }],
+            },
+            SuiteReport {
+                adapter: "go-test".to_string(),
+                workspace: "/demo".to_string(),
+                files: vec![FileTests {
+                    path: "/demo/main_test.go".to_string(),
+                    diagnostics: vec![Diagnostic {
+                        range: Range::new(Position::new(9, 1), Position::new(9, 5)),
+                        message: "expected <1>".to_string(),
+                        ..Diagnostic::default()
+                    }],
+                    tests: vec![],
+                }],
+            },
+        ];
+        assert_eq!(
+            junit_xml(&suites, Path::new("/demo")).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="testing-language-server" tests="4" failures="2" errors="0" skipped="1" time="0.015">
+  <testsuite name="cargo-test (crates/core)" tests="3" failures="1" errors="0" skipped="1" time="0.015">
+    <testcase name="tests::fails" classname="crates/core/src/lib.rs" file="crates/core/src/lib.rs" time="0.012">
+      <failure message="assertion `left == right` failed">assertion `left == right` failed
+  left: 1</failure>
+    </testcase>
+    <testcase name="tests::passes" classname="crates/core/src/lib.rs" file="crates/core/src/lib.rs" time="0.003" />
+    <testcase name="tests::ignored" classname="crates/core/src/lib.rs" file="crates/core/src/lib.rs">
+      <skipped />
+    </testcase>
+  </testsuite>
+  <testsuite name="go-test (.)" tests="1" failures="1" errors="0" skipped="0" time="0.000">
+    <testcase name="main_test.go" classname="main_test.go" file="main_test.go">
+      <failure message="expected &lt;1&gt;">10:2: expected &lt;1&gt;</failure>
+    </testcase>
+  </testsuite>
+</testsuites>"#
+        );
+    }
+}
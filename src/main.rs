@@ -1,20 +1,56 @@
-mod error;
+mod check;
+mod config;
+mod details;
+mod file_index;
+mod init;
+mod jobs;
+mod junit;
 mod log;
+mod process;
+mod results;
+mod sarif;
 mod server;
-mod spec;
-mod util;
+mod transport;
+mod variables;
 
-use std::io::{self, BufRead, Read};
+use std::io::{self, BufRead, BufReader};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
-use error::LSError;
+use clap::Parser;
+use clap::Subcommand;
+use lsp_types::CodeLens;
+use lsp_types::CreateFilesParams;
+use lsp_types::DeleteFilesParams;
+use lsp_types::DidChangeConfigurationParams;
+use lsp_types::DidChangeWatchedFilesParams;
+use lsp_types::DidChangeWorkspaceFoldersParams;
+use lsp_types::DocumentDiagnosticParams;
+use lsp_types::ExecuteCommandParams;
 use lsp_types::InitializeParams;
+use lsp_types::LogMessageParams;
+use lsp_types::MessageType;
+use lsp_types::NumberOrString;
+use lsp_types::Position;
+use lsp_types::Range;
+use lsp_types::RenameFilesParams;
+use lsp_types::WorkspaceDiagnosticParams;
 use serde::de::Error;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use util::{format_uri, send_stdout};
+use testing_language_server::error::LSError;
+use testing_language_server::util::{format_uri, send_stdout, set_thread_sink, thread_sink};
+use testing_language_server::{error, spec, util};
 
+use crate::check::CheckArgs;
+use crate::init::InitArgs;
 use crate::log::Log;
+use crate::process::{CancelKey, ProcessRegistry};
+use crate::server::ExportResultsParams;
 use crate::server::TestingLS;
+use crate::transport::{parse_listen, read_message, BatchSink, Connection, Endpoint, Listener};
 use crate::util::send_error;
 
 fn extract_textdocument_uri(params: &Value) -> Result<String, serde_json::Error> {
@@ -31,128 +67,595 @@ fn extract_uri(params: &Value) -> Result<String, serde_json::Error> {
     Ok(format_uri(uri))
 }
 
-fn main_loop(server: &mut TestingLS) -> Result<(), LSError> {
-    let mut is_workspace_checked = false;
-    loop {
-        let mut size = 0;
-        'read_header: loop {
-            let mut buffer = String::new();
-            let stdin = io::stdin();
-            let mut handle = stdin.lock();
-            handle.read_line(&mut buffer)?;
-
-            if buffer.is_empty() {
-                tracing::warn!("buffer is empty")
-            }
-
-            // The end of header section
-            if buffer == "\r\n" {
-                break 'read_header;
-            }
-
-            let split: Vec<&str> = buffer.split(' ').collect();
-
-            if split.len() != 2 {
-                tracing::warn!("unexpected");
-            }
-
-            let header_name = split[0].to_lowercase();
-            let header_value = split[1].trim();
-
-            match header_name.as_ref() {
-                "content-length" => {}
-                "content-type:" => {}
-                _ => {}
-            }
-
-            size = header_value.parse::<usize>().unwrap();
-        }
+/// Accepts either a single `testId` or a list of `testIds`
+fn extract_test_ids(params: &Value) -> Vec<String> {
+    if let Some(test_id) = params["testId"].as_str() {
+        return vec![test_id.to_string()];
+    }
+    params["testIds"]
+        .as_array()
+        .map(|test_ids| {
+            test_ids
+                .iter()
+                .filter_map(|test_id| test_id.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-        let stdin = io::stdin();
-        let mut handle = stdin.lock();
-        let mut buf = vec![0u8; size];
-        handle.read_exact(&mut buf).unwrap();
-        let message = String::from_utf8(buf).unwrap();
-
-        let received_json: Value = serde_json::from_str(&message)?;
-        tracing::info!("received json={:#?}", received_json);
-        let method = &received_json["method"].as_str();
-        let params = &received_json["params"];
-
-        if let Some(method) = method {
-            match *method {
-                "$/cancelRequest" => {}
-                "initialized" => {
-                    is_workspace_checked = true;
-                    server.diagnose_workspace()?;
-                }
-                "initialize" => {
-                    let initialize_params = InitializeParams::deserialize(params)?;
-                    let id = received_json["id"].as_i64().unwrap();
-                    server.initialize(id, initialize_params)?;
-                }
-                "shutdown" => {
-                    let id = received_json["id"].as_i64().unwrap();
-                    server.shutdown(id)?;
-                }
-                "exit" => {
-                    std::process::exit(0);
-                }
-                "workspace/diagnostic" => {
-                    is_workspace_checked = true;
-                    server.diagnose_workspace()?;
+/// Reads messages on a dedicated thread so that cancellation is handled
+/// even while the main loop is waiting for an adapter.
+/// A batch is passed on as an array of its messages.
+fn spawn_reader(
+    processes: ProcessRegistry,
+    mut reader: impl BufRead + Send + 'static,
+) -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    let sink = thread_sink();
+    thread::spawn(move || {
+        set_thread_sink(sink);
+        loop {
+            let received = match read_message(&mut reader) {
+                Ok(Some(received)) => received,
+                Ok(None) => break,
+                // The whole message was read, so the next one can still be handled
+                Err(LSError::Serialization(err)) => {
+                    tracing::error!("Failed to parse message: {:?}", err);
+                    // Parse error
+                    if send_error(None, -32700, err.to_string()).is_err() {
+                        break;
+                    }
+                    continue;
                 }
-                "textDocument/diagnostic" | "textDocument/didSave" => {
-                    let uri = extract_textdocument_uri(params)?;
-                    server.check_file(&uri, false)?;
+                Err(err) => {
+                    tracing::error!("Failed to read message: {:?}", err);
+                    break;
                 }
-                "textDocument/didOpen" => {
-                    if !is_workspace_checked {
-                        is_workspace_checked = true;
-                        server.diagnose_workspace()?;
+            };
+            let is_cancel = |received_json: &Value| {
+                let params = &received_json["params"];
+                match received_json["method"].as_str() {
+                    Some("$/cancelRequest") => {
+                        if let Ok(id) = NumberOrString::deserialize(&params["id"]) {
+                            processes.cancel(&CancelKey::Request(id));
+                        }
+                        true
                     }
-                    let uri = extract_textdocument_uri(params)?;
-                    if server.refreshing_needed(&uri) {
-                        server.refresh_workspaces_cache()?;
+                    Some("window/workDoneProgress/cancel") => {
+                        if let Ok(token) = NumberOrString::deserialize(&params["token"]) {
+                            processes.cancel(&CancelKey::Progress(token));
+                        }
+                        true
                     }
+                    _ => false,
                 }
-                "$/runFileTest" => {
-                    let uri = extract_uri(params)?;
-                    server.check_file(&uri, false)?;
+            };
+            let received = match received {
+                Value::Array(messages) if messages.is_empty() => {
+                    // Invalid Request
+                    if send_error(None, -32600, "The batch is empty").is_err() {
+                        break;
+                    }
+                    continue;
                 }
-                "$/runWorkspaceTest" => {
+                Value::Array(messages) => Value::Array(
+                    messages
+                        .into_iter()
+                        .filter(|received_json| !is_cancel(received_json))
+                        .collect(),
+                ),
+                received_json if is_cancel(&received_json) => continue,
+                received_json => received_json,
+            };
+            if sender.send(received).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn handle_message(
+    server: &mut TestingLS,
+    received_json: &Value,
+    is_workspace_checked: &mut bool,
+) -> Result<(), LSError> {
+    let method = &received_json["method"].as_str();
+    let params = &received_json["params"];
+
+    if let Some(method) = method {
+        match *method {
+            "initialized" => {
+                *is_workspace_checked = true;
+                server.register_file_watchers()?;
+                server.diagnose_workspace()?;
+            }
+            "workspace/didChangeConfiguration" => {
+                let params = DidChangeConfigurationParams::deserialize(params)?;
+                server.reload_configuration(Some(&params.settings))?;
+            }
+            "workspace/didChangeWorkspaceFolders" => {
+                let params = DidChangeWorkspaceFoldersParams::deserialize(params)?;
+                server.change_workspace_folders(params)?;
+            }
+            "workspace/didChangeWatchedFiles" => {
+                let params = DidChangeWatchedFilesParams::deserialize(params)?;
+                server.watched_files_changed(params)?;
+            }
+            "workspace/didCreateFiles" => {
+                let params = CreateFilesParams::deserialize(params)?;
+                server.did_create_files(params)?;
+            }
+            "workspace/didDeleteFiles" => {
+                let params = DeleteFilesParams::deserialize(params)?;
+                server.did_delete_files(params)?;
+            }
+            "workspace/didRenameFiles" => {
+                let params = RenameFilesParams::deserialize(params)?;
+                server.did_rename_files(params)?;
+            }
+            "initialize" => {
+                let initialize_params = InitializeParams::deserialize(params)?;
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                server.initialize(id, initialize_params)?;
+            }
+            "shutdown" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                server.shutdown(id)?;
+            }
+            "workspace/diagnostic" => {
+                *is_workspace_checked = true;
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let workspace_diagnostic_params = WorkspaceDiagnosticParams::deserialize(params)?;
+                server.pull_workspace_diagnostics(id, workspace_diagnostic_params)?;
+            }
+            "textDocument/diagnostic" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let document_diagnostic_params = DocumentDiagnosticParams::deserialize(params)?;
+                server.pull_document_diagnostics(id, document_diagnostic_params)?;
+            }
+            "textDocument/didSave" => {
+                let uri = extract_textdocument_uri(params)?;
+                server.forget_discovered(Path::new(&uri));
+                server.check_file(&uri, false)?;
+            }
+            "textDocument/didOpen" => {
+                if !*is_workspace_checked {
+                    *is_workspace_checked = true;
                     server.diagnose_workspace()?;
                 }
-                "$/discoverFileTest" => {
-                    let id = received_json["id"].as_i64().unwrap();
-                    let uri = extract_uri(params)?;
-                    let result = server.discover_file(&uri)?;
-                    send_stdout(&json!({
-                            "jsonrpc": "2.0",
-                            "id": id,
-                            "result": result,
-                    }))?;
+                let uri = extract_textdocument_uri(params)?;
+                server.index_file(&uri);
+                if server.refreshing_needed(&uri) {
+                    server.refresh_workspaces_cache()?;
                 }
-                _ => {
-                    // https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#responseMessage
-                    let id = received_json["id"].as_i64();
-                    if id.is_some() {
-                        send_error(
-                            id,
-                            -32601, // Method not found
-                            format!("method not found: {}", method),
-                        )?;
-                    }
+            }
+            "$/runFileTest" => {
+                let uri = extract_uri(params)?;
+                server.check_file(&uri, false)?;
+                answer_run(received_json)?;
+            }
+            "$/runTest" => {
+                let uri = extract_uri(params)?;
+                let test_ids = extract_test_ids(params);
+                let position = Position::deserialize(&params["position"]).ok();
+                server.run_test(&uri, &test_ids, position)?;
+                answer_run(received_json)?;
+            }
+            "textDocument/codeLens" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let uri = extract_textdocument_uri(params)?;
+                let result = server.code_lens(&uri)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            "textDocument/hover" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let uri = extract_textdocument_uri(params)?;
+                let position = Position::deserialize(&params["position"])?;
+                let result = server.hover(&uri, position)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            "textDocument/inlayHint" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let uri = extract_textdocument_uri(params)?;
+                let range = Range::deserialize(&params["range"])?;
+                let result = server.inlay_hints(&uri, range)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            "codeLens/resolve" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let code_lens = CodeLens::deserialize(params)?;
+                let result = server.resolve_code_lens(code_lens)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            "workspace/executeCommand" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let execute_command_params = ExecuteCommandParams::deserialize(params)?;
+                server.execute_command(execute_command_params)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": null,
+                }))?;
+            }
+            "$/runWorkspaceTest" => {
+                server.diagnose_workspace()?;
+                answer_run(received_json)?;
+            }
+            "$/discoverWorkspaceTests" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let result = server.discover_workspace_tests()?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            "$/exportResults" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let params = ExportResultsParams::deserialize(params)?;
+                let result = server.export_results(params)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            "$/discoverFileTest" => {
+                let id = NumberOrString::deserialize(&received_json["id"])?;
+                let uri = extract_uri(params)?;
+                let result = server.discover_file(&uri)?;
+                send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                }))?;
+            }
+            _ => {
+                // https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#responseMessage
+                if let Ok(id) = NumberOrString::deserialize(&received_json["id"]) {
+                    send_error(
+                        Some(id),
+                        -32601, // Method not found
+                        format!("method not found: {}", method),
+                    )?;
                 }
             }
         }
+    } else if let Some(id) = received_json["id"].as_str() {
+        // A response to a request of the server
+        server.handle_response(id, &received_json["result"])?;
     }
+    Ok(())
+}
+
+/// Answers a `$/run*` notification with `null` when it is sent as a request.
+/// The run is only queued, its results are published as diagnostics.
+fn answer_run(received_json: &Value) -> Result<(), LSError> {
+    let Ok(id) = NumberOrString::deserialize(&received_json["id"]) else {
+        return Ok(());
+    };
+    send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": null,
+    }))
+}
+
+/// Handles the messages of a client until it sends `exit` or the connection is closed
+fn main_loop(server: &mut TestingLS, reader: impl BufRead + Send + 'static) -> Result<(), LSError> {
+    let mut is_workspace_checked = false;
+    for received in spawn_reader(server.processes.clone(), reader) {
+        tracing::info!("received json={:#?}", received);
+        let Value::Array(messages) = received else {
+            if received["method"] == "exit" {
+                break;
+            }
+            handle_received(server, &received, &mut is_workspace_checked)?;
+            continue;
+        };
+        // The responses to the requests of a batch are sent together in one array
+        let ids = messages
+            .iter()
+            .filter(|received_json| received_json["method"].is_string())
+            .filter_map(|received_json| received_json.get("id").cloned())
+            .collect();
+        let outer = thread_sink();
+        let batch = BatchSink::new(ids, outer.clone());
+        set_thread_sink(Some(batch.sink()));
+        let mut handled = 0;
+        let mut result = Ok(true);
+        for received_json in &messages {
+            if received_json["method"] == "exit" {
+                result = Ok(false);
+                break;
+            }
+            handled += 1;
+            result =
+                handle_received(server, received_json, &mut is_workspace_checked).map(|_| true);
+            if result.is_err() {
+                break;
+            }
+        }
+        if handled < messages.len() {
+            // The requests after `exit` or a failed answer are not handled
+            for skipped in &messages[handled..] {
+                let (Some(method), Ok(id)) = (
+                    skipped["method"].as_str(),
+                    NumberOrString::deserialize(&skipped["id"]),
+                ) else {
+                    continue;
+                };
+                let message = format!("{method} was not handled, the batch stopped before it");
+                if let Err(err) = send_error(Some(id), LSError::Cancelled.code(), message) {
+                    tracing::error!("Failed to answer a skipped request: {:?}", err);
+                }
+            }
+            batch.flush();
+        }
+        set_thread_sink(outer);
+        if !result? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Handles a message which is not `exit`.
+/// Only failing to answer the client is an error, the failure of the handler is reported instead.
+fn handle_received(
+    server: &mut TestingLS,
+    received_json: &Value,
+    is_workspace_checked: &mut bool,
+) -> Result<(), LSError> {
+    server.set_request_id(NumberOrString::deserialize(&received_json["id"]).ok());
+    // A failing or panicking handler only fails its own message
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        handle_message(server, received_json, is_workspace_checked)
+    }))
+    .unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(LSError::Any(anyhow::anyhow!("panicked: {message}")))
+    });
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) => report_error(received_json, err),
+    }
+}
+
+/// Answers a failed request with an error response, and logs a failed notification in the client
+fn report_error(received_json: &Value, err: LSError) -> Result<(), LSError> {
+    let method = received_json["method"].as_str().unwrap_or_default();
+    let message = error_message(&err);
+    match NumberOrString::deserialize(&received_json["id"]) {
+        Ok(id) => {
+            tracing::error!("Request {method} failed: {message}");
+            send_error(Some(id), err.code(), message)
+        }
+        Err(_) if matches!(err, LSError::Cancelled) => Ok(()),
+        Err(_) => {
+            tracing::error!("Notification {method} failed: {message}");
+            send_stdout(&json!({
+                "jsonrpc": "2.0",
+                "method": "window/logMessage",
+                "params": LogMessageParams {
+                    typ: MessageType::ERROR,
+                    message: format!("{method} failed: {message}"),
+                },
+            }))
+        }
+    }
+}
+
+/// `err` with its cause, which the variants do not include in their own message
+fn error_message(err: &LSError) -> String {
+    match (err, std::error::Error::source(err)) {
+        (LSError::Adapter(message), _) => format!("{err}: {message}"),
+        (_, Some(source)) => format!("{err}: {source}"),
+        (_, None) => err.to_string(),
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+    /// Accepted for clients which pass it, stdio is used unless `--listen` or `--socket` is given
+    #[arg(long, hide = true)]
+    stdio: bool,
+    /// Wait for clients on a TCP port of localhost instead of using stdio, e.g. `tcp:9257`
+    #[arg(long, value_name = "tcp:PORT", value_parser = parse_listen, conflicts_with = "socket")]
+    listen: Option<u16>,
+    /// Wait for clients on a Unix domain socket instead of using stdio
+    #[arg(long, value_name = "PATH")]
+    socket: Option<PathBuf>,
+}
+
+impl Cli {
+    fn endpoint(&self) -> Endpoint {
+        match (self.listen, &self.socket) {
+            (Some(port), _) => Endpoint::Tcp(port),
+            (None, Some(path)) => Endpoint::Socket(path.clone()),
+            (None, None) => Endpoint::Stdio,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Write a .testingls.toml for the project in the current directory
+    Init(InitArgs),
+    /// Run the tests with the configured adapters and print the diagnostics.
+    /// Exits with 1 when a test fails.
+    Check(CheckArgs),
 }
 
 fn main() {
-    let mut server = TestingLS::new();
+    let cli = Cli::parse();
     let _guard = Log::init().expect("Failed to initialize logger");
-    if let Err(ls_error) = main_loop(&mut server) {
-        tracing::error!("Error: {:?}", ls_error);
+    match cli.command {
+        Some(CliCommand::Init(args)) => {
+            if let Err(err) = init::run(args) {
+                exit_with_error(err);
+            }
+            return;
+        }
+        Some(CliCommand::Check(args)) => match check::run(args) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => exit_with_error(err),
+        },
+        None => {}
+    }
+    match cli.endpoint() {
+        Endpoint::Stdio => {
+            let mut server = TestingLS::new();
+            if let Err(ls_error) = main_loop(&mut server, BufReader::new(io::stdin())) {
+                tracing::error!("Error: {:?}", ls_error);
+            }
+            // Queued runs are not waited for after `exit`
+            std::process::exit(0);
+        }
+        endpoint => {
+            if let Err(err) = serve(&endpoint) {
+                exit_with_error(err);
+            }
+        }
+    }
+}
+
+/// Serves the clients which connect to `endpoint`, one after the other.
+/// Every client gets a new server, which ends when the client sends `exit` or disconnects.
+/// The tests it still runs are stopped then.
+fn serve(endpoint: &Endpoint) -> Result<(), LSError> {
+    let listener = Listener::bind(endpoint)?;
+    eprintln!("Listening on {}", listener.address());
+    loop {
+        let connection = match listener.accept() {
+            Ok(connection) => connection,
+            Err(err) => {
+                tracing::error!("Failed to accept a client: {:?}", err);
+                continue;
+            }
+        };
+        tracing::info!("Client connected");
+        let Connection { reader, writer } = connection;
+        set_thread_sink(Some(writer.sink()));
+        let mut server = TestingLS::new();
+        if let Err(ls_error) = main_loop(&mut server, reader) {
+            tracing::error!("Error: {:?}", ls_error);
+        }
+        tracing::info!("Client disconnected");
+        // Nothing of this server reaches the next client
+        writer.close();
+        set_thread_sink(None);
+        server.disconnect();
+        // Dropping the server waits for the killed runs, which must not delay the next client
+        thread::spawn(move || drop(server));
+    }
+}
+
+fn exit_with_error(err: LSError) -> ! {
+    eprintln!("Error: {}", error_message(&err));
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use testing_language_server::util::write_message;
+
+    #[test]
+    fn serve_a_client_in_process() {
+        let dir = std::env::temp_dir().join(format!("testing-ls-main-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sent = Arc::new(Mutex::new(vec![]));
+        {
+            let sent = Arc::clone(&sent);
+            set_thread_sink(Some(Arc::new(move |message| {
+                sent.lock().unwrap().push(message)
+            })));
+        }
+        let mut input = vec![];
+        for message in [
+            json!({
+                "jsonrpc": "2.0",
+                "id": "in-process-initialize",
+                "method": "initialize",
+                "params": {
+                    "capabilities": {},
+                    "workspaceFolders": [{
+                        "uri": lsp_types::Url::from_directory_path(&dir).unwrap(),
+                        "name": "demo",
+                    }],
+                },
+            }),
+            json!([
+                { "jsonrpc": "2.0", "id": "in-process-unknown", "method": "unknown/method" },
+                // Queues the runs, and has no response of its own
+                { "jsonrpc": "2.0", "id": "in-process-run", "method": "$/runWorkspaceTest" },
+                { "jsonrpc": "2.0", "id": "in-process-shutdown", "method": "shutdown" },
+            ]),
+            json!([
+                { "jsonrpc": "2.0", "id": "in-process-discover", "method": "$/discoverWorkspaceTests" },
+                { "jsonrpc": "2.0", "method": "exit" },
+                { "jsonrpc": "2.0", "id": "in-process-skipped", "method": "shutdown" },
+            ]),
+            json!({ "jsonrpc": "2.0", "id": "in-process-after-exit", "method": "shutdown" }),
+        ] {
+            write_message(&mut input, &message.to_string()).unwrap();
+        }
+        main_loop(&mut TestingLS::new(), io::Cursor::new(input)).unwrap();
+        set_thread_sink(None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let sent = sent.lock().unwrap();
+        let initialize = sent
+            .iter()
+            .find(|message| message["id"] == "in-process-initialize")
+            .unwrap();
+        assert!(initialize["result"]["capabilities"].is_object());
+        assert!(!sent
+            .iter()
+            .any(|message| message["id"] == "in-process-after-exit"));
+        // Each batch is answered with one array, also when it stops at `exit`
+        let batches = sent.iter().filter_map(Value::as_array).collect::<Vec<_>>();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), 3);
+        assert_eq!(batches[1].len(), 2);
+        let response = |id: &str| {
+            batches
+                .iter()
+                .flat_map(|responses| responses.iter())
+                .find(|message| message["id"] == id)
+                .unwrap()
+        };
+        assert_eq!(response("in-process-unknown")["error"]["code"], -32601);
+        assert_eq!(response("in-process-run")["result"], Value::Null);
+        assert_eq!(response("in-process-shutdown")["result"], Value::Null);
+        assert!(response("in-process-discover")["result"].is_array());
+        assert_eq!(response("in-process-skipped")["error"]["code"], -32800);
     }
 }
@@ -0,0 +1,233 @@
+use crate::error::LSError;
+use crate::util::wait_with_lines;
+use crate::util::OutputStream;
+use lsp_types::NumberOrString;
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// What a running adapter process belongs to.
+/// `$/cancelRequest` refers to a request id and
+/// `window/workDoneProgress/cancel` refers to a progress token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CancelKey {
+    Request(NumberOrString),
+    Progress(NumberOrString),
+}
+
+struct RunningProcess {
+    keys: Vec<CancelKey>,
+    cancelled: bool,
+    timed_out: bool,
+}
+
+/// Keeps track of running adapter processes so that they can be killed from another thread.
+#[derive(Clone, Default)]
+pub struct ProcessRegistry {
+    running: Arc<Mutex<HashMap<u32, RunningProcess>>>,
+}
+
+impl ProcessRegistry {
+    /// Runs `command` to completion like `Command::output`.
+    /// Returns `LSError::Cancelled` if the process was killed by `cancel`.
+    pub fn output(&self, command: &mut Command, keys: Vec<CancelKey>) -> Result<Output, LSError> {
+        self.output_with_lines(command, keys, None, |_, _| {})
+    }
+
+    /// Same as `output`, but `on_line` is called with every line of the output as it is written.
+    /// The process is killed after `timeout`, and `LSError::Timeout` is returned.
+    pub fn output_with_lines(
+        &self,
+        command: &mut Command,
+        keys: Vec<CancelKey>,
+        timeout: Option<Duration>,
+        on_line: impl FnMut(OutputStream, &str),
+    ) -> Result<Output, LSError> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // The adapter leads a new process group,
+            // so the test tools it spawns are killed together with it.
+            command.process_group(0);
+        }
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| LSError::Adapter(err.to_string()))?;
+        let pid = child.id();
+        self.running.lock().unwrap().insert(
+            pid,
+            RunningProcess {
+                keys,
+                cancelled: false,
+                timed_out: false,
+            },
+        );
+        // Dropping the sender stops the timer
+        let (finished, timer) = mpsc::channel::<()>();
+        if let Some(timeout) = timeout {
+            let registry = self.clone();
+            thread::spawn(move || {
+                if timer.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+                    registry.kill_timed_out(pid);
+                }
+            });
+        }
+        let output = wait_with_lines(child, on_line);
+        drop(finished);
+        let process = self.running.lock().unwrap().remove(&pid);
+        match process {
+            Some(process) if process.cancelled => Err(LSError::Cancelled),
+            Some(process) if process.timed_out => Err(LSError::Timeout),
+            _ => output.map_err(|err| LSError::Adapter(err.to_string())),
+        }
+    }
+
+    fn kill_timed_out(&self, pid: u32) {
+        let mut running = self.running.lock().unwrap();
+        // The process may have exited while the lock was waited for
+        let Some(process) = running.get_mut(&pid) else {
+            return;
+        };
+        process.timed_out = true;
+        tracing::warn!("adapter process timed out: pid={}", pid);
+        if let Err(err) = kill_tree(pid) {
+            tracing::error!("Failed to kill adapter process {}: {:?}", pid, err);
+        }
+    }
+
+    /// Kills every process started for `key` together with its children.
+    /// Returns whether any process was found.
+    pub fn cancel(&self, key: &CancelKey) -> bool {
+        let mut running = self.running.lock().unwrap();
+        let mut found = false;
+        for (pid, process) in running
+            .iter_mut()
+            .filter(|(_, process)| process.keys.contains(key))
+        {
+            found = true;
+            process.cancelled = true;
+            tracing::info!("cancel adapter process: pid={}, key={:?}", pid, key);
+            if let Err(err) = kill_tree(*pid) {
+                tracing::error!("Failed to kill adapter process {}: {:?}", pid, err);
+            }
+        }
+        found
+    }
+
+    /// Kills every running process together with its children, e.g. when the client disconnected
+    pub fn cancel_all(&self) {
+        let mut running = self.running.lock().unwrap();
+        for (pid, process) in running.iter_mut() {
+            process.cancelled = true;
+            tracing::info!("cancel adapter process: pid={}", pid);
+            if let Err(err) = kill_tree(*pid) {
+                tracing::error!("Failed to kill adapter process {}: {:?}", pid, err);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_tree(pid: u32) -> io::Result<()> {
+    // negative pid means the process group
+    Command::new("kill")
+        .args(["-TERM", "--", &format!("-{pid}")])
+        .status()
+        .map(|_| ())
+}
+
+#[cfg(windows)]
+fn kill_tree(pid: u32) -> io::Result<()> {
+    Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status()
+        .map(|_| ())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn cancel_kills_process_tree() {
+        let registry = ProcessRegistry::default();
+        let key = CancelKey::Request(NumberOrString::Number(1));
+        let canceller = {
+            let registry = registry.clone();
+            let key = key.clone();
+            std::thread::spawn(move || {
+                let started = Instant::now();
+                while !registry.cancel(&key) {
+                    assert!(started.elapsed() < Duration::from_secs(5));
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            })
+        };
+        let started = Instant::now();
+        // the shell is the adapter, `sleep` is the test tool it spawned
+        let result = registry.output(
+            Command::new("sh").args(["-c", "sleep 30; echo done"]),
+            vec![key],
+        );
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(LSError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn unrelated_key_does_not_cancel() {
+        let registry = ProcessRegistry::default();
+        let output = registry
+            .output(
+                Command::new("sh").args(["-c", "echo done"]),
+                vec![CancelKey::Progress(NumberOrString::String(
+                    "token".to_string(),
+                ))],
+            )
+            .unwrap();
+        assert!(!registry.cancel(&CancelKey::Request(NumberOrString::Number(1))));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "done\n");
+    }
+
+    #[test]
+    fn timeout_kills_process() {
+        let registry = ProcessRegistry::default();
+        let started = Instant::now();
+        let mut lines = vec![];
+        let result = registry.output_with_lines(
+            Command::new("sh").args(["-c", "echo started; sleep 30; echo done"]),
+            vec![],
+            Some(Duration::from_millis(200)),
+            |_, line| lines.push(line.to_string()),
+        );
+        assert!(matches!(result, Err(LSError::Timeout)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(lines, vec!["started"]);
+    }
+
+    #[test]
+    fn finished_process_is_not_timed_out() {
+        let registry = ProcessRegistry::default();
+        let output = registry
+            .output_with_lines(
+                Command::new("sh").args(["-c", "echo done"]),
+                vec![],
+                Some(Duration::from_secs(5)),
+                |_, _| {},
+            )
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "done\n");
+    }
+}
@@ -0,0 +1,356 @@
+use lsp_types::Diagnostic;
+use lsp_types::DocumentDiagnosticReport;
+use lsp_types::FullDocumentDiagnosticReport;
+use lsp_types::RelatedFullDocumentDiagnosticReport;
+use lsp_types::RelatedUnchangedDocumentDiagnosticReport;
+use lsp_types::UnchangedDocumentDiagnosticReport;
+use lsp_types::Url;
+use lsp_types::WorkspaceDocumentDiagnosticReport;
+use lsp_types::WorkspaceFullDocumentDiagnosticReport;
+use lsp_types::WorkspaceUnchangedDocumentDiagnosticReport;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use testing_language_server::spec::TestResult;
+
+/// The last diagnostics of a file
+#[derive(Debug, Clone)]
+pub struct FileResult {
+    /// Changes only when the diagnostics change
+    pub result_id: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub tested_at: SystemTime,
+}
+
+/// The last result of a test, with when and how it ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestRecord {
+    pub result: TestResult,
+    pub finished_at: SystemTime,
+    /// The adapter command which ran the test
+    pub command: String,
+    /// What the adapter wrote to stderr, which is empty until the whole run has finished
+    pub stderr: String,
+}
+
+#[derive(Default)]
+struct Results {
+    files: HashMap<String, FileResult>,
+    /// The last result of every test, by path and by test id
+    tests: HashMap<String, BTreeMap<String, TestRecord>>,
+    last_result_id: u64,
+}
+
+impl Results {
+    /// Stores the diagnostics of `path` and returns its result id.
+    /// When it was tested is kept if `tested_at` is `None`, a file which was never tested is not fresh.
+    fn store(
+        &mut self,
+        path: &str,
+        diagnostics: Vec<Diagnostic>,
+        tested_at: Option<SystemTime>,
+    ) -> String {
+        let previous = self.files.get_mut(path);
+        let tested_at = tested_at
+            .or(previous.as_ref().map(|file_result| file_result.tested_at))
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if let Some(file_result) = previous {
+            file_result.tested_at = tested_at;
+            if file_result.diagnostics == diagnostics {
+                return file_result.result_id.clone();
+            }
+        }
+        self.last_result_id += 1;
+        let result_id = self.last_result_id.to_string();
+        self.files.insert(
+            path.to_string(),
+            FileResult {
+                result_id: result_id.clone(),
+                diagnostics,
+                tested_at,
+            },
+        );
+        result_id
+    }
+}
+
+/// Keeps the last diagnostics of every tested file and the last result of every test,
+/// shared with the worker threads.
+#[derive(Clone, Default)]
+pub struct ResultStore {
+    results: Arc<Mutex<Results>>,
+}
+
+impl ResultStore {
+    /// Stores the diagnostics of `path` and returns its result id.
+    pub fn update(&self, path: &str, diagnostics: Vec<Diagnostic>) -> String {
+        let mut results = self.results.lock().unwrap();
+        results.store(path, diagnostics, Some(SystemTime::now()))
+    }
+
+    /// Replaces the diagnostics of `path` which `replaced` matches with `diagnostics`,
+    /// after only some of its tests ran, and returns all the diagnostics of the file.
+    /// The file does not become fresh, since its other tests did not run.
+    pub fn replace(
+        &self,
+        path: &str,
+        replaced: impl Fn(&Diagnostic) -> bool,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Vec<Diagnostic> {
+        let mut results = self.results.lock().unwrap();
+        let mut kept = results
+            .files
+            .get(path)
+            .map(|file_result| {
+                file_result
+                    .diagnostics
+                    .iter()
+                    .filter(|diagnostic| !replaced(diagnostic))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        kept.extend(diagnostics);
+        results.store(path, kept.clone(), None);
+        kept
+    }
+
+    pub fn remove(&self, path: &str) {
+        let mut results = self.results.lock().unwrap();
+        results.files.remove(path);
+        results.tests.remove(path);
+    }
+
+    /// Stores the results of single tests run by `command`, replacing the previous result of each test.
+    pub fn update_tests(&self, tests: &[TestResult], command: &str, stderr: &str) {
+        let mut results = self.results.lock().unwrap();
+        let finished_at = SystemTime::now();
+        for test in tests {
+            results.tests.entry(test.path.clone()).or_default().insert(
+                test.id.clone(),
+                TestRecord {
+                    result: test.clone(),
+                    finished_at,
+                    command: command.to_string(),
+                    stderr: stderr.to_string(),
+                },
+            );
+        }
+    }
+
+    /// The last results of the tests in `path`, ordered by test id
+    pub fn tests(&self, path: &str) -> Vec<TestResult> {
+        let results = self.results.lock().unwrap();
+        results
+            .tests
+            .get(path)
+            .map(|tests| tests.values().map(|record| record.result.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The last result of the test `id` in `path`
+    pub fn test(&self, path: &str, id: &str) -> Option<TestRecord> {
+        let results = self.results.lock().unwrap();
+        results.tests.get(path)?.get(id).cloned()
+    }
+
+    pub fn get(&self, path: &str) -> Option<FileResult> {
+        self.results.lock().unwrap().files.get(path).cloned()
+    }
+
+    /// Whether the file has been tested since it was last modified
+    pub fn is_fresh(&self, path: &str) -> bool {
+        let Some(file_result) = self.get(path) else {
+            return false;
+        };
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified <= file_result.tested_at)
+    }
+
+    /// Report for `textDocument/diagnostic`.
+    /// It is `unchanged` when the client already has the last result.
+    pub fn document_report(
+        &self,
+        path: &str,
+        previous_result_id: Option<&str>,
+    ) -> DocumentDiagnosticReport {
+        match self.get(path) {
+            Some(FileResult { result_id, .. })
+                if previous_result_id == Some(result_id.as_str()) =>
+            {
+                DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                        result_id,
+                    },
+                })
+            }
+            file_result => DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: file_result.as_ref().map(|result| result.result_id.clone()),
+                    items: file_result
+                        .map(|result| result.diagnostics)
+                        .unwrap_or_default(),
+                },
+            }),
+        }
+    }
+
+    /// Report of a file for `workspace/diagnostic`. Files which were never tested are left out.
+    pub fn workspace_report(
+        &self,
+        path: &str,
+        previous_result_id: Option<&str>,
+    ) -> Option<WorkspaceDocumentDiagnosticReport> {
+        let uri = Url::from_file_path(path).ok()?;
+        let FileResult {
+            result_id,
+            diagnostics,
+            ..
+        } = self.get(path)?;
+        if previous_result_id == Some(result_id.as_str()) {
+            Some(WorkspaceDocumentDiagnosticReport::Unchanged(
+                WorkspaceUnchangedDocumentDiagnosticReport {
+                    uri,
+                    version: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                        result_id,
+                    },
+                },
+            ))
+        } else {
+            Some(WorkspaceDocumentDiagnosticReport::Full(
+                WorkspaceFullDocumentDiagnosticReport {
+                    uri,
+                    version: None,
+                    full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                        result_id: Some(result_id),
+                        items: diagnostics,
+                    },
+                },
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing_language_server::spec::TestStatus;
+
+    fn diagnostic(message: &str) -> Diagnostic {
+        Diagnostic {
+            message: message.to_string(),
+            ..Diagnostic::default()
+        }
+    }
+
+    #[test]
+    fn result_id_changes_only_with_diagnostics() {
+        let store = ResultStore::default();
+        let first = store.update("/demo/lib.rs", vec![diagnostic("failed")]);
+        let same = store.update("/demo/lib.rs", vec![diagnostic("failed")]);
+        assert_eq!(first, same);
+        let changed = store.update("/demo/lib.rs", vec![]);
+        assert_ne!(first, changed);
+        let other_file = store.update("/demo/main.rs", vec![]);
+        assert_ne!(changed, other_file);
+    }
+
+    #[test]
+    fn diagnostics_of_tests_which_ran_are_replaced() {
+        let store = ResultStore::default();
+        store.update(
+            "/demo/lib.rs",
+            vec![diagnostic("a failed"), diagnostic("b failed")],
+        );
+        let tested_at = store.get("/demo/lib.rs").unwrap().tested_at;
+        let diagnostics = store.replace(
+            "/demo/lib.rs",
+            |diagnostic| diagnostic.message.starts_with('b'),
+            vec![diagnostic("b failed again")],
+        );
+        assert_eq!(
+            diagnostics,
+            vec![diagnostic("a failed"), diagnostic("b failed again")]
+        );
+        let file_result = store.get("/demo/lib.rs").unwrap();
+        assert_eq!(file_result.diagnostics, diagnostics);
+        assert_eq!(file_result.tested_at, tested_at);
+        store.replace("/demo/main.rs", |_| true, vec![]);
+        assert_eq!(
+            store.get("/demo/main.rs").unwrap().tested_at,
+            SystemTime::UNIX_EPOCH
+        );
+    }
+
+    #[test]
+    fn unchanged_report_for_known_result_id() {
+        let store = ResultStore::default();
+        let result_id = store.update("/demo/lib.rs", vec![diagnostic("failed")]);
+        assert!(matches!(
+            store.document_report("/demo/lib.rs", Some(&result_id)),
+            DocumentDiagnosticReport::Unchanged(_)
+        ));
+        match store.document_report("/demo/lib.rs", Some("outdated")) {
+            DocumentDiagnosticReport::Full(report) => {
+                let report = report.full_document_diagnostic_report;
+                assert_eq!(report.result_id, Some(result_id.clone()));
+                assert_eq!(report.items, vec![diagnostic("failed")]);
+            }
+            report => panic!("unexpected report: {:?}", report),
+        }
+        assert!(matches!(
+            store.document_report("/demo/untested.rs", None),
+            DocumentDiagnosticReport::Full(_)
+        ));
+        assert!(store.workspace_report("/demo/untested.rs", None).is_none());
+        assert!(matches!(
+            store.workspace_report("/demo/lib.rs", Some(&result_id)),
+            Some(WorkspaceDocumentDiagnosticReport::Unchanged(_))
+        ));
+    }
+
+    #[test]
+    fn last_result_of_every_test() {
+        let store = ResultStore::default();
+        let result = |id: &str, status| TestResult {
+            id: id.to_string(),
+            path: "/demo/lib.rs".to_string(),
+            status,
+            duration: None,
+            output: None,
+        };
+        store.update_tests(
+            &[
+                result("tests::b", TestStatus::Failed),
+                result("tests::a", TestStatus::Passed),
+            ],
+            "adapter run-file-test",
+            "",
+        );
+        store.update_tests(
+            &[result("tests::b", TestStatus::Passed)],
+            "adapter run-test",
+            "warning",
+        );
+        assert_eq!(
+            store.tests("/demo/lib.rs"),
+            vec![
+                result("tests::a", TestStatus::Passed),
+                result("tests::b", TestStatus::Passed)
+            ]
+        );
+        let record = store.test("/demo/lib.rs", "tests::b").unwrap();
+        assert_eq!(
+            (record.command.as_str(), record.stderr.as_str()),
+            ("adapter run-test", "warning")
+        );
+        store.remove("/demo/lib.rs");
+        assert_eq!(store.tests("/demo/lib.rs"), vec![]);
+    }
+}
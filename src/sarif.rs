@@ -0,0 +1,184 @@
+use lsp_types::Diagnostic;
+use lsp_types::DiagnosticSeverity;
+use lsp_types::Url;
+use serde_json::json;
+use serde_json::Value;
+use std::path::Path;
+use testing_language_server::spec::AdapterId;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const TOOL_NAME: &str = "testing-language-server";
+/// Base of the artifact uris, which is the project directory
+const SOURCE_ROOT: &str = "%SRCROOT%";
+
+/// The last diagnostics of a tested file
+pub struct FileReport {
+    /// `None` for files which were not tested by an adapter, e.g. `.testingls.toml`
+    pub adapter: Option<AdapterId>,
+    pub path: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// SARIF 2.1.0 log with a result for every diagnostic, i.e. for every failing test.
+/// The rule of a result is the adapter which reported it.
+pub fn sarif_log(files: &[FileReport], project_dir: &Path) -> Value {
+    let base = Url::from_directory_path(project_dir).ok();
+    let mut rules: Vec<&str> = vec![];
+    let mut results = vec![];
+    for file in files {
+        let rule = file.adapter.as_deref().unwrap_or(TOOL_NAME);
+        let rule_index = rules
+            .iter()
+            .position(|known| *known == rule)
+            .unwrap_or_else(|| {
+                rules.push(rule);
+                rules.len() - 1
+            });
+        let artifact_location = artifact_location(base.as_ref(), &file.path);
+        for diagnostic in &file.diagnostics {
+            let range = diagnostic.range;
+            results.push(json!({
+                "ruleId": rule,
+                "ruleIndex": rule_index,
+                "level": level(diagnostic),
+                "message": { "text": diagnostic.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": artifact_location,
+                        // SARIF lines and columns start at 1, and the end column is exclusive like in LSP
+                        "region": {
+                            "startLine": range.start.line + 1,
+                            "startColumn": range.start.character + 1,
+                            "endLine": range.end.line + 1,
+                            "endColumn": range.end.character + 1,
+                        },
+                    },
+                }],
+            }));
+        }
+    }
+    let rules = rules
+        .iter()
+        .map(|rule| {
+            let description = if *rule == TOOL_NAME {
+                format!("Problems found by {TOOL_NAME}")
+            } else {
+                format!("Test failures reported by the {rule} adapter")
+            };
+            json!({ "id": rule, "shortDescription": { "text": description } })
+        })
+        .collect::<Vec<_>>();
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": TOOL_NAME,
+                "version": env!("CARGO_PKG_VERSION"),
+                "rules": rules,
+            },
+        },
+        "results": results,
+    });
+    if let Some(base) = base {
+        run["originalUriBaseIds"] = json!({ SOURCE_ROOT: { "uri": base } });
+    }
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [run],
+    })
+}
+
+/// Uri relative to the project directory, or an absolute one for files outside of it
+fn artifact_location(base: Option<&Url>, path: &str) -> Value {
+    let Ok(uri) = Url::from_file_path(path) else {
+        return json!({ "uri": path });
+    };
+    let relative = base
+        .and_then(|base| base.make_relative(&uri))
+        .filter(|relative| !relative.starts_with("../"));
+    match relative {
+        Some(relative) => json!({ "uri": relative, "uriBaseId": SOURCE_ROOT }),
+        None => json!({ "uri": uri }),
+    }
+}
+
+fn level(diagnostic: &Diagnostic) -> &'static str {
+    match diagnostic.severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) | Some(DiagnosticSeverity::HINT) => "note",
+        _ => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+    use lsp_types::Range;
+
+    #[test]
+    fn results_of_failing_tests() {
+        let diagnostic = |line, message: &str| Diagnostic {
+            range: Range::new(Position::new(line, 4), Position::new(line, 10)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            message: message.to_string(),
+            ..Diagnostic::default()
+        };
+        let files = [
+            FileReport {
+                adapter: Some("cargo-test".to_string()),
+                path: "/demo/src/my lib.rs".to_string(),
+                diagnostics: vec![
+                    diagnostic(11, "assertion failed"),
+                    diagnostic(20, "panicked"),
+                ],
+            },
+            FileReport {
+                adapter: Some("jest".to_string()),
+                path: "/other/index.spec.js".to_string(),
+                diagnostics: vec![diagnostic(0, "expected 1")],
+            },
+            FileReport {
+                adapter: Some("cargo-test".to_string()),
+                path: "/demo/src/main.rs".to_string(),
+                diagnostics: vec![],
+            },
+        ];
+        let log = sarif_log(&files, Path::new("/demo"));
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(
+            run["originalUriBaseIds"]["%SRCROOT%"]["uri"],
+            "file:///demo/"
+        );
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            json!([
+                { "id": "cargo-test", "shortDescription": { "text": "Test failures reported by the cargo-test adapter" } },
+                { "id": "jest", "shortDescription": { "text": "Test failures reported by the jest adapter" } },
+            ])
+        );
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[1],
+            json!({
+                "ruleId": "cargo-test",
+                "ruleIndex": 0,
+                "level": "error",
+                "message": { "text": "panicked" },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "src/my%20lib.rs", "uriBaseId": "%SRCROOT%" },
+                        "region": { "startLine": 21, "startColumn": 5, "endLine": 21, "endColumn": 11 },
+                    },
+                }],
+            })
+        );
+        assert_eq!(results[2]["ruleIndex"], 1);
+        assert_eq!(
+            results[2]["locations"][0]["physicalLocation"]["artifactLocation"],
+            json!({ "uri": "file:///other/index.spec.js" })
+        );
+    }
+}
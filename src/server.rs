@@ -1,10 +1,34 @@
+use crate::config::config_scopes;
+use crate::config::detect_options;
+use crate::config::nearest_scope;
+use crate::config::nested_config_dirs;
+use crate::config::parse_config;
+use crate::config::ConfigScope;
+use crate::config::InitializedOptions;
+use crate::config::TOML_FILE_NAME;
+use crate::details::hover_markdown;
+use crate::details::inlay_label;
 use crate::error::LSError;
+use crate::file_index::FileIndex;
+use crate::jobs::JobQueue;
+use crate::junit::junit_xml;
+use crate::junit::FileTests;
+use crate::junit::SuiteReport;
+use crate::process::CancelKey;
+use crate::process::ProcessRegistry;
+use crate::results::ResultStore;
+use crate::sarif::sarif_log;
+use crate::sarif::FileReport;
 use crate::spec::*;
+use crate::util::format_uri;
 use crate::util::resolve_path;
 use crate::util::send_stdout;
-use glob::Pattern;
+use crate::util::OutputStream;
+use crate::variables::process_env;
+use crate::variables::Variables;
 use lsp_types::*;
 use serde::Deserialize;
+use serde::Serialize;
 use serde_json::json;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -13,29 +37,143 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 use std::process::Output;
-use testing_language_server::spec::DiscoverResult;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
 
-const TOML_FILE_NAME: &str = ".testingls.toml";
+const RUN_TEST_COMMAND: &str = "testing-ls.runTest";
+const RUN_FILE_COMMAND: &str = "testing-ls.runFile";
+/// Action of the message shown when an adapter writes to stderr
+const OPEN_LOG_ACTION: &str = "Open adapter log";
 
-#[derive(Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
-pub struct InitializedOptions {
-    adapter_command: HashMap<AdapterId, AdapterConfiguration>,
-    enable_workspace_diagnostics: Option<bool>,
-}
+/// Adapter logs offered by `window/showMessageRequest`s which the client has not answered yet,
+/// by request id
+type LogRequests = Arc<Mutex<HashMap<String, Vec<PathBuf>>>>;
 
 pub struct TestingLS {
-    pub workspace_folders: Option<Vec<WorkspaceFolder>>,
+    /// The workspace folders of the client, each tested with its own configuration
+    pub folders: Vec<ProjectFolder>,
+    pub processes: ProcessRegistry,
+    pub results: ResultStore,
+    /// Options sent by the client, used when there is no `.testingls.toml`
+    initialization_options: Option<Value>,
+    client_capabilities: ClientCapabilities,
+    jobs: JobQueue,
+    /// Id of the request being handled, used to cancel adapter processes started for it
+    request_id: Option<NumberOrString>,
+    log_requests: LogRequests,
+    /// Tests which the adapters discovered in a file, kept until the file is saved or changed
+    /// on disk, or the workspaces are detected again
+    discovered: HashMap<String, Arc<DiscoveredFile>>,
+}
+
+/// Tests which the adapters discovered in a file, with the lengths of its lines at that time
+pub struct DiscoveredFile {
+    tests: DiscoverResult,
+    /// Length of each line in UTF-16 code units, the default position encoding.
+    /// Inlay hints are put at the end of a line, and the client does not send the documents.
+    line_lengths: Vec<u32>,
+}
+
+/// A workspace folder, with the configuration found in it and its detected workspaces
+pub struct ProjectFolder {
+    pub dir: PathBuf,
     pub options: InitializedOptions,
+    /// Options of the `.testingls.toml` files in subdirectories, of which only the adapters are used
+    pub nested: Vec<(PathBuf, InitializedOptions)>,
+    /// Files of the folder which are not ignored, matched against the globs of the adapters
+    pub files: FileIndex,
     pub workspaces_cache: Vec<WorkspaceAnalysis>,
 }
 
+impl ProjectFolder {
+    pub fn new(dir: PathBuf, options: InitializedOptions) -> Self {
+        Self {
+            files: FileIndex::new(&dir),
+            dir,
+            options,
+            nested: Vec::new(),
+            workspaces_cache: Vec::new(),
+        }
+    }
+
+    fn scopes(&self) -> Vec<ConfigScope> {
+        config_scopes(&self.dir, &self.options.adapter_command, &self.nested)
+    }
+
+    /// Every adapter of the folder, with the directory which its globs are relative to
+    fn adapters(&self) -> Vec<(PathBuf, AdapterConfiguration)> {
+        let mut adapters = vec![];
+        for adapter in self
+            .scopes()
+            .into_iter()
+            .flat_map(|scope| scope.adapters.into_values())
+        {
+            if !adapters.contains(&adapter) {
+                adapters.push(adapter);
+            }
+        }
+        adapters
+    }
+
+    fn workspace_diagnostics_enabled(&self) -> bool {
+        self.options.enable_workspace_diagnostics.unwrap_or(true)
+    }
+}
+
 impl Default for TestingLS {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// What a code lens runs
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum CodeLensKind {
+    Test,
+    Namespace,
+    File,
+}
+
+/// `data` of unresolved code lenses, and arguments of `testing-ls.*` commands
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RunCommandArgs {
+    pub uri: String,
+    #[serde(default)]
+    pub test_ids: Vec<String>,
+}
+
+/// Formats of `$/exportResults`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Sarif,
+    Junit,
+}
+
+/// Params of `$/exportResults`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResultsParams {
+    pub format: ExportFormat,
+    /// File to write the results to, in addition to returning them
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CodeLensData {
+    kind: CodeLensKind,
+    #[serde(flatten)]
+    args: RunCommandArgs,
+}
+
 /// The status of workspace diagnostics
 /// - Skipped: Skip workspace diagnostics (when `enable_workspace_diagnostics` is false)
 /// - Done: Finish workspace diagnostics (when `enable_workspace_diagnostics` is true)
@@ -48,34 +186,97 @@ pub enum WorkspaceDiagnosticsStatus {
 impl TestingLS {
     pub fn new() -> Self {
         Self {
-            workspace_folders: None,
-            options: Default::default(),
-            workspaces_cache: Vec::new(),
+            folders: Vec::new(),
+            processes: ProcessRegistry::default(),
+            results: ResultStore::default(),
+            initialization_options: None,
+            client_capabilities: ClientCapabilities::default(),
+            jobs: JobQueue::default(),
+            request_id: None,
+            log_requests: LogRequests::default(),
+            discovered: HashMap::new(),
         }
     }
 
+    pub fn set_request_id(&mut self, request_id: Option<NumberOrString>) {
+        self.request_id = request_id;
+    }
+
+    /// Keys to cancel an adapter process started while handling the current request
+    fn cancel_keys(&self) -> Vec<CancelKey> {
+        self.request_id
+            .iter()
+            .cloned()
+            .map(CancelKey::Request)
+            .collect()
+    }
+
+    /// The first workspace folder, which paths of exported results are relative to
     fn project_dir(&self) -> Result<PathBuf, LSError> {
-        let cwd = current_dir();
-        if let Ok(cwd) = cwd {
-            Ok(cwd)
-        } else {
-            let default_project_dir = self
-                .workspace_folders
-                .as_ref()
-                .ok_or(LSError::Any(anyhow::anyhow!("No workspace folders found")))?;
-            let default_workspace_uri = &default_project_dir[0].uri;
-            Ok(default_workspace_uri.to_file_path().unwrap())
+        match self.folders.first() {
+            Some(folder) => Ok(folder.dir.clone()),
+            None => Ok(current_dir()?),
+        }
+    }
+
+    /// Every workspace detected in the workspace folders
+    pub fn workspaces_cache(&self) -> impl Iterator<Item = &WorkspaceAnalysis> {
+        self.folders
+            .iter()
+            .flat_map(|folder| folder.workspaces_cache.iter())
+    }
+
+    /// Concurrency of the job queue, the highest one of the workspace folders
+    fn concurrency(&self) -> Option<usize> {
+        self.folders
+            .iter()
+            .filter_map(|folder| folder.options.concurrency)
+            .max()
+    }
+
+    /// Replaces the job queue when the concurrency changed from `previous`
+    fn resize_jobs(&mut self, previous: Option<usize>) {
+        let concurrency = self.concurrency();
+        if concurrency != previous {
+            let jobs = std::mem::replace(&mut self.jobs, JobQueue::new(concurrency));
+            // Dropping the queue waits for the running jobs, which must not block the main loop
+            std::thread::spawn(move || drop(jobs));
         }
     }
 
+    /// Reads the configuration of a workspace folder
+    fn open_folder(&self, dir: PathBuf) -> Result<ProjectFolder, LSError> {
+        let mut folder = ProjectFolder::new(dir, InitializedOptions::default());
+        folder.options = Self::handle_initialization_options(
+            &folder.dir,
+            &folder.files,
+            self.initialization_options.as_ref(),
+        )?;
+        folder.nested = Self::nested_options(&folder.dir, &folder.files)?;
+        Ok(folder)
+    }
+
     pub fn initialize(
         &mut self,
-        id: i64,
+        id: NumberOrString,
         initialize_params: InitializeParams,
     ) -> Result<(), LSError> {
-        self.workspace_folders = initialize_params.workspace_folders;
-        self.options = (self
-            .handle_initialization_options(initialize_params.initialization_options.as_ref()))?;
+        self.client_capabilities = initialize_params.capabilities;
+        self.initialization_options = initialize_params.initialization_options;
+        let mut dirs = initialize_params
+            .workspace_folders
+            .iter()
+            .flatten()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect::<Vec<_>>();
+        if dirs.is_empty() {
+            dirs.push(current_dir()?);
+        }
+        self.folders = dirs
+            .into_iter()
+            .map(|dir| self.open_folder(dir))
+            .collect::<Result<_, _>>()?;
+        self.jobs = JobQueue::new(self.concurrency());
         let result = InitializeResult {
             capabilities: self.build_capabilities(),
             ..InitializeResult::default()
@@ -90,36 +291,6 @@ impl TestingLS {
         Ok(())
     }
 
-    fn adapter_commands(&self) -> HashMap<AdapterId, AdapterConfiguration> {
-        self.options.adapter_command.clone()
-    }
-
-    fn project_files(base_dir: &Path, include: &[String], exclude: &[String]) -> Vec<String> {
-        let mut result: Vec<String> = vec![];
-
-        let exclude_pattern = exclude
-            .iter()
-            .filter_map(|exclude_pattern| {
-                Pattern::new(base_dir.join(exclude_pattern).to_str().unwrap()).ok()
-            })
-            .collect::<Vec<Pattern>>();
-        let base_dir = base_dir.to_str().unwrap();
-        let entries = globwalk::GlobWalkerBuilder::from_patterns(base_dir, include)
-            .follow_links(true)
-            .build()
-            .unwrap()
-            .filter_map(Result::ok);
-        for path in entries {
-            let should_exclude = exclude_pattern
-                .iter()
-                .any(|exclude_pattern| exclude_pattern.matches(path.path().to_str().unwrap()));
-            if !should_exclude {
-                result.push(path.path().to_str().unwrap().to_owned());
-            }
-        }
-        result
-    }
-
     fn build_capabilities(&self) -> ServerCapabilities {
         ServerCapabilities {
             diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
@@ -129,64 +300,469 @@ impl TestingLS {
                 work_done_progress_options: WorkDoneProgressOptions::default(),
             })),
             text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::NONE)),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(true),
+            }),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![RUN_TEST_COMMAND.to_string(), RUN_FILE_COMMAND.to_string()],
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+            }),
+            workspace: Some(WorkspaceServerCapabilities {
+                workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                    supported: Some(true),
+                    change_notifications: Some(OneOf::Left(true)),
+                }),
+                file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                    did_create: Some(file_operation_options()),
+                    did_rename: Some(file_operation_options()),
+                    did_delete: Some(file_operation_options()),
+                    ..WorkspaceFileOperationsServerCapabilities::default()
+                }),
+            }),
             ..ServerCapabilities::default()
         }
     }
 
+    /// Options of the workspace folder `project_dir`, from its `.testingls.toml`,
+    /// the options sent by the client or the adapters detected in it
     pub fn handle_initialization_options(
-        &self,
+        project_dir: &Path,
+        files: &FileIndex,
         options: Option<&Value>,
     ) -> Result<InitializedOptions, LSError> {
-        let project_dir = self.project_dir()?;
         let toml_path = project_dir.join(TOML_FILE_NAME);
-        let toml_content = std::fs::read_to_string(toml_path);
+        let toml_content = std::fs::read_to_string(&toml_path);
         match toml_content {
-            Ok(toml_content) => Ok(toml::from_str::<InitializedOptions>(&toml_content).unwrap()),
-            Err(_) => {
-                if let Some(options) = options {
+            Ok(toml_content) => {
+                let (options, diagnostics) = parse_config(&toml_content, project_dir);
+                Self::report_config_errors(&toml_path, diagnostics)?;
+                Ok(options)
+            }
+            Err(_) => match options {
+                Some(options) if options.get("adapterCommand").is_some() => {
                     Ok(serde_json::from_value(options.clone())?)
-                } else {
-                    Err(LSError::Any(anyhow::anyhow!(
-                        "Invalid initialization options"
-                    )))
                 }
+                _ => {
+                    let options = detect_options(project_dir, files);
+                    let mut adapters = options.adapter_command.keys().cloned().collect::<Vec<_>>();
+                    adapters.sort();
+                    send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "method": "window/logMessage",
+                        "params": LogMessageParams {
+                            typ: MessageType::INFO,
+                            message: format!(
+                                "No {TOML_FILE_NAME} found in {}, detected adapters: [{}]",
+                                project_dir.display(),
+                                adapters.join(", ")
+                            ),
+                        },
+                    }))?;
+                    Ok(options)
+                }
+            },
+        }
+    }
+
+    /// Options of the `.testingls.toml` files in the subdirectories of `project_dir`
+    fn nested_options(
+        project_dir: &Path,
+        files: &FileIndex,
+    ) -> Result<Vec<(PathBuf, InitializedOptions)>, LSError> {
+        let mut nested = vec![];
+        for dir in nested_config_dirs(project_dir, files) {
+            let toml_path = dir.join(TOML_FILE_NAME);
+            let Ok(toml_content) = std::fs::read_to_string(&toml_path) else {
+                continue;
+            };
+            let (options, diagnostics) = parse_config(&toml_content, &dir);
+            Self::report_config_errors(&toml_path, diagnostics)?;
+            nested.push((dir, options));
+        }
+        Ok(nested)
+    }
+
+    /// Publishes the problems found in `.testingls.toml`, which clears them once it is fixed
+    fn report_config_errors(toml_path: &Path, diagnostics: Vec<Diagnostic>) -> Result<(), LSError> {
+        if let Some(first) = diagnostics.first() {
+            let message = match diagnostics.len() {
+                1 => format!("Invalid {TOML_FILE_NAME}: {}", first.message),
+                count => format!(
+                    "Invalid {TOML_FILE_NAME}: {} (and {} more problems)",
+                    first.message,
+                    count - 1
+                ),
+            };
+            send_stdout(&json!({
+                "jsonrpc": "2.0",
+                "method": "window/showMessage",
+                "params": ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message,
+                },
+            }))?;
+        }
+        Self::send_diagnostics(Url::from_file_path(toml_path).unwrap(), diagnostics)
+    }
+
+    /// Asks the client to notify the server of changes to `.testingls.toml` and the ignore files,
+    /// and of created and deleted files
+    pub fn register_file_watchers(&self) -> Result<(), LSError> {
+        let dynamic_registration = self
+            .client_capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|capability| capability.dynamic_registration)
+            .unwrap_or(false);
+        if !dynamic_registration {
+            return Ok(());
+        }
+        let register_options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(format!("**/{TOML_FILE_NAME}")),
+                    kind: None,
+                },
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/{.gitignore,.ignore}".to_string()),
+                    kind: None,
+                },
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/*".to_string()),
+                    kind: Some(WatchKind::Create | WatchKind::Change | WatchKind::Delete),
+                },
+            ],
+        };
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "id": "testing-ls/registerConfigWatcher",
+            "method": "client/registerCapability",
+            "params": RegistrationParams {
+                registrations: vec![Registration {
+                    id: "testing-ls/configWatcher".to_string(),
+                    method: "workspace/didChangeWatchedFiles".to_string(),
+                    register_options: Some(json!(register_options)),
+                }],
+            },
+        }))
+    }
+
+    /// Handles `workspace/didChangeWatchedFiles`.
+    pub fn watched_files_changed(
+        &mut self,
+        params: DidChangeWatchedFilesParams,
+    ) -> Result<(), LSError> {
+        let config_changed = params
+            .changes
+            .iter()
+            .any(|change| change.uri.path().ends_with(TOML_FILE_NAME));
+        let mut created = vec![];
+        let mut deleted = vec![];
+        for change in params.changes {
+            let Ok(path) = change.uri.to_file_path() else {
+                continue;
+            };
+            match change.typ {
+                FileChangeType::DELETED => deleted.push(path),
+                // A changed ignore file rebuilds the index, like a created one
+                FileChangeType::CHANGED if !is_ignore_file(&path) => {
+                    if !self.is_ignored(&path) {
+                        self.forget_discovered(&path);
+                    }
+                }
+                _ => created.push(path),
+            }
+        }
+        // The configuration is read from the index, which must know created `.testingls.toml` files
+        self.files_changed(&created, &deleted)?;
+        if config_changed {
+            self.reload_configuration(None)?;
+        }
+        Ok(())
+    }
+
+    /// Handles `workspace/didCreateFiles`.
+    pub fn did_create_files(&mut self, params: CreateFilesParams) -> Result<(), LSError> {
+        let created = file_paths(params.files.iter().map(|file| file.uri.as_str()));
+        self.files_changed(&created, &[])
+    }
+
+    /// Handles `workspace/didDeleteFiles`.
+    pub fn did_delete_files(&mut self, params: DeleteFilesParams) -> Result<(), LSError> {
+        let deleted = file_paths(params.files.iter().map(|file| file.uri.as_str()));
+        self.files_changed(&[], &deleted)
+    }
+
+    /// Handles `workspace/didRenameFiles`, as the deletion of the old paths and the creation of the new ones.
+    pub fn did_rename_files(&mut self, params: RenameFilesParams) -> Result<(), LSError> {
+        let deleted = file_paths(params.files.iter().map(|file| file.old_uri.as_str()));
+        let created = file_paths(params.files.iter().map(|file| file.new_uri.as_str()));
+        self.files_changed(&created, &deleted)
+    }
+
+    /// Whether `path` is left out of the index of its folder, or is in no folder
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.folders
+            .iter()
+            .find(|folder| path.starts_with(&folder.dir))
+            .is_none_or(|folder| folder.files.is_ignored(path))
+    }
+
+    /// Updates the file index of the folders.
+    /// The workspace cache is rebuilt when a created file is tested by an adapter, or when
+    /// a cached file was deleted or is ignored now. The results of such files are cleared.
+    fn files_changed(&mut self, created: &[PathBuf], deleted: &[PathBuf]) -> Result<(), LSError> {
+        let mut refresh = false;
+        let mut stale_files = vec![];
+        let mut changed = vec![];
+        for folder in &mut self.folders {
+            // Most events are in ignored directories like build outputs, they change nothing
+            let created = created
+                .iter()
+                .filter(|path| path.starts_with(&folder.dir) && !folder.files.is_ignored(path))
+                .collect::<Vec<_>>();
+            let deleted = deleted
+                .iter()
+                .filter(|path| folder.files.contains_below(path))
+                .collect::<Vec<_>>();
+            if created.is_empty() && deleted.is_empty() {
+                continue;
+            }
+            changed.extend(
+                created
+                    .iter()
+                    .chain(&deleted)
+                    .map(|path| path.to_path_buf()),
+            );
+            if created
+                .iter()
+                .chain(&deleted)
+                .any(|path| is_ignore_file(path))
+            {
+                folder.files = FileIndex::new(&folder.dir);
+                refresh = true;
+            } else {
+                deleted.iter().for_each(|path| folder.files.remove(path));
+                created.iter().for_each(|path| folder.files.add(path));
+            }
+            let cached_files = folder
+                .workspaces_cache
+                .iter()
+                .flat_map(|analysis| analysis.workspaces.data.values().flatten());
+            for path in cached_files {
+                if !folder.files.contains(Path::new(path)) && !stale_files.contains(path) {
+                    stale_files.push(path.clone());
+                }
+            }
+            refresh |= !created.is_empty()
+                && folder.adapters().iter().any(|(base_dir, adapter)| {
+                    folder
+                        .files
+                        .files(base_dir, &adapter.include, &adapter.exclude)
+                        .iter()
+                        .any(|file| created.iter().any(|path| Path::new(file).starts_with(path)))
+                });
+        }
+        changed.iter().for_each(|path| self.forget_discovered(path));
+        if refresh || !stale_files.is_empty() {
+            self.refresh_workspaces_cache()?;
+        }
+        self.clear_results(&stale_files)
+    }
+
+    /// Adds an opened file to the index of its folder, in case the client does not send file events
+    pub fn index_file(&mut self, path: &str) {
+        let path = Path::new(path);
+        if let Some(folder) = self
+            .folders
+            .iter_mut()
+            .find(|folder| path.starts_with(&folder.dir))
+        {
+            if !folder.files.contains(path) {
+                folder.files.add(path);
+            }
+        }
+    }
+
+    /// Re-reads the configuration after `.testingls.toml` or the client settings changed.
+    /// The workspace cache is rebuilt, and the diagnostics of files tested by
+    /// removed or changed adapters are cleared.
+    /// `settings` replace the initialization options when they contain adapter commands.
+    pub fn reload_configuration(&mut self, settings: Option<&Value>) -> Result<(), LSError> {
+        if let Some(settings) = settings.filter(|settings| settings.get("adapterCommand").is_some())
+        {
+            self.initialization_options = Some(settings.clone());
+        }
+        let concurrency = self.concurrency();
+        let old_caches = self
+            .folders
+            .iter_mut()
+            .map(|folder| {
+                folder.options = Self::handle_initialization_options(
+                    &folder.dir,
+                    &folder.files,
+                    self.initialization_options.as_ref(),
+                )?;
+                folder.nested = Self::nested_options(&folder.dir, &folder.files)?;
+                Ok(std::mem::take(&mut folder.workspaces_cache))
+            })
+            .collect::<Result<Vec<_>, LSError>>()?;
+        self.resize_jobs(concurrency);
+        self.refresh_workspaces_cache()?;
+        for (folder, old_cache) in self.folders.iter().zip(old_caches) {
+            let adapters = folder
+                .adapters()
+                .into_iter()
+                .map(|(_, adapter)| adapter)
+                .collect::<Vec<_>>();
+            let stale_files = Self::stale_files(&old_cache, &adapters);
+            self.clear_results(&stale_files)?;
+        }
+        Ok(())
+    }
+
+    /// Handles `workspace/didChangeWorkspaceFolders`.
+    /// The results of removed folders are cleared, and added folders are configured and diagnosed.
+    pub fn change_workspace_folders(
+        &mut self,
+        params: DidChangeWorkspaceFoldersParams,
+    ) -> Result<(), LSError> {
+        let concurrency = self.concurrency();
+        for removed in &params.event.removed {
+            let Ok(dir) = removed.uri.to_file_path() else {
+                continue;
+            };
+            if let Some(index) = self.folders.iter().position(|folder| folder.dir == dir) {
+                let folder = self.folders.remove(index);
+                let paths = folder
+                    .workspaces_cache
+                    .iter()
+                    .flat_map(|analysis| analysis.workspaces.data.values().flatten().cloned())
+                    .collect::<Vec<_>>();
+                self.clear_results(&paths)?;
+            }
+        }
+        let mut added = vec![];
+        for folder in &params.event.added {
+            let Ok(dir) = folder.uri.to_file_path() else {
+                continue;
+            };
+            if self.folders.iter().any(|folder| folder.dir == dir) {
+                continue;
             }
+            let mut folder = self.open_folder(dir.clone())?;
+            folder.workspaces_cache = self.analyze_folder(&folder)?;
+            self.folders.push(folder);
+            added.push(dir);
         }
+        self.resize_jobs(concurrency);
+        self.send_detected_workspaces()?;
+        for folder in self
+            .folders
+            .iter()
+            .filter(|folder| added.contains(&folder.dir))
+        {
+            self.diagnose_folder(folder);
+        }
+        Ok(())
+    }
+
+    /// Forgets the results of `paths` and clears their diagnostics in the client
+    fn clear_results(&self, paths: &[String]) -> Result<(), LSError> {
+        for path in paths {
+            self.results.remove(path);
+            Self::send_diagnostics(Url::from_file_path(path).unwrap(), vec![])?;
+        }
+        Ok(())
+    }
+
+    /// Files which were tested by adapters that are not in `adapters` anymore, as they were configured
+    fn stale_files(
+        old_cache: &[WorkspaceAnalysis],
+        adapters: &[AdapterConfiguration],
+    ) -> Vec<String> {
+        let mut paths = old_cache
+            .iter()
+            .filter(|analysis| !adapters.contains(&analysis.adapter_config))
+            .flat_map(|analysis| analysis.workspaces.data.values().flatten().cloned())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
     }
 
     pub fn refresh_workspaces_cache(&mut self) -> Result<(), LSError> {
-        let adapter_commands = self.adapter_commands();
-        let project_dir = self.project_dir()?;
-        self.workspaces_cache = vec![];
+        // Files may be tested by other adapters now
+        self.discovered.clear();
+        for index in 0..self.folders.len() {
+            self.folders[index].workspaces_cache = self.analyze_folder(&self.folders[index])?;
+        }
+        self.send_detected_workspaces()
+    }
+
+    fn send_detected_workspaces(&self) -> Result<(), LSError> {
+        let workspaces_cache = self.workspaces_cache().collect::<Vec<_>>();
+        tracing::info!("workspaces_cache={:#?}", workspaces_cache);
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "method": "$/detectedWorkspace",
+            "params": workspaces_cache,
+        }))
+    }
+
+    /// Detects the workspaces of the files which the adapters of `folder` test.
+    /// Files are tested by the adapters of the nearest `.testingls.toml`.
+    fn analyze_folder(&self, folder: &ProjectFolder) -> Result<Vec<WorkspaceAnalysis>, LSError> {
+        let scopes = folder.scopes();
+        let mut workspaces_cache = vec![];
         // Nested and multiple loops, but each count is small
-        for adapter in adapter_commands.into_values() {
+        for (scope, (project_dir, adapter)) in scopes
+            .iter()
+            .flat_map(|scope| scope.adapters.values().map(move |adapter| (scope, adapter)))
+        {
+            let file_paths = folder
+                .files
+                .files(project_dir, &adapter.include, &adapter.exclude)
+                .into_iter()
+                .filter(|path| {
+                    nearest_scope(&scopes, Path::new(path))
+                        .is_some_and(|nearest| nearest.dir == scope.dir)
+                })
+                .collect::<Vec<_>>();
+            if file_paths.is_empty() {
+                continue;
+            }
             let AdapterConfiguration {
                 path,
                 extra_arg,
                 env,
-                include,
-                exclude,
                 workspace_dir,
                 ..
-            } = &adapter;
-            let file_paths = Self::project_files(&project_dir, include, exclude);
-            if file_paths.is_empty() {
-                continue;
+            } = Variables {
+                workspace_folder: &folder.dir,
+                workspace: None,
+                files: &file_paths,
+                env: process_env,
             }
+            .adapter(adapter);
             let mut adapter_command = Command::new(path);
             let mut args_file_path: Vec<&str> = vec![];
             file_paths.iter().for_each(|file_path| {
                 args_file_path.push("--file-paths");
                 args_file_path.push(file_path);
             });
-            let output = adapter_command
-                .arg("detect-workspace")
-                .args(args_file_path)
-                .arg("--")
-                .args(extra_arg)
-                .envs(env)
-                .output()
-                .map_err(|err| LSError::Adapter(err.to_string()))?;
+            let output = self.processes.output(
+                adapter_command
+                    .arg("detect-workspace")
+                    .args(args_file_path)
+                    .arg("--")
+                    .args(extra_arg)
+                    .envs(env),
+                self.cancel_keys(),
+            )?;
             let adapter_result = String::from_utf8(output.stdout)
                 .map_err(|err| LSError::Adapter(err.to_string()))?;
             let workspace: DetectWorkspaceResult = match serde_json::from_str(&adapter_result) {
@@ -199,7 +775,7 @@ impl TestingLS {
                 }
             };
             let workspace = if let Some(workspace_dir) = workspace_dir {
-                let workspace_dir = resolve_path(&project_dir, workspace_dir)
+                let workspace_dir = resolve_path(project_dir, &workspace_dir)
                     .to_str()
                     .unwrap()
                     .to_string();
@@ -212,97 +788,1065 @@ impl TestingLS {
             } else {
                 workspace.data
             };
-            self.workspaces_cache.push(WorkspaceAnalysis::new(
-                adapter,
+            workspaces_cache.push(WorkspaceAnalysis::new(
+                adapter.clone(),
                 DetectWorkspaceResult { data: workspace },
             ))
         }
-        tracing::info!("workspaces_cache={:#?}", self.workspaces_cache);
-        send_stdout(&json!({
-            "jsonrpc": "2.0",
-            "method": "$/detectedWorkspace",
-            "params": self.workspaces_cache,
-        }))?;
-        Ok(())
+        Ok(workspaces_cache)
     }
 
     /// Diagnoses the entire workspace for diagnostics.
     /// This function will refresh the workspace cache, check if workspace diagnostics are enabled,
-    /// and then iterate through all workspaces to diagnose them.
-    /// It will trigger the publication of diagnostics for all files in the workspace
-    /// through the Language Server Protocol.
+    /// and then queue a run for each workspace, so that workspaces are diagnosed in parallel.
+    /// Diagnostics for all files in the workspace are published
+    /// through the Language Server Protocol as the runs finish.
     pub fn diagnose_workspace(&mut self) -> Result<WorkspaceDiagnosticsStatus, LSError> {
         self.refresh_workspaces_cache()?;
-        if !self.options.enable_workspace_diagnostics.unwrap_or(true) {
-            return Ok(WorkspaceDiagnosticsStatus::Skipped);
+        let mut status = WorkspaceDiagnosticsStatus::Skipped;
+        for folder in &self.folders {
+            if self.diagnose_folder(folder) == WorkspaceDiagnosticsStatus::Done {
+                status = WorkspaceDiagnosticsStatus::Done;
+            }
         }
+        Ok(status)
+    }
 
-        self.workspaces_cache.iter().for_each(
-            |WorkspaceAnalysis {
-                 adapter_config: adapter,
-                 workspaces,
-             }| {
-                workspaces.data.iter().for_each(|(workspace, paths)| {
-                    let _ = self.diagnose(adapter, workspace, paths);
-                })
-            },
-        );
-        Ok(WorkspaceDiagnosticsStatus::Done)
+    /// Queues a run for each workspace of `folder`, unless workspace diagnostics are disabled in it
+    fn diagnose_folder(&self, folder: &ProjectFolder) -> WorkspaceDiagnosticsStatus {
+        if !folder.workspace_diagnostics_enabled() {
+            return WorkspaceDiagnosticsStatus::Skipped;
+        }
+        let export = folder.options.junit_report.as_ref().map(|path| {
+            Arc::new(JunitExport {
+                path: resolve_path(&folder.dir, path),
+                project_dir: folder.dir.clone(),
+                suites: Self::workspace_suites(folder),
+                results: self.results.clone(),
+            })
+        });
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in &folder.workspaces_cache
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                self.diagnose(adapter, workspace, paths, &[], None, export.clone());
+            }
+        }
+        WorkspaceDiagnosticsStatus::Done
     }
 
-    pub fn refreshing_needed(&self, path: &str) -> bool {
-        let base_dir = self.project_dir();
-        match base_dir {
-            Ok(base_dir) => self.workspaces_cache.iter().any(|cache| {
-                let include = &cache.adapter_config.include;
-                let exclude = &cache.adapter_config.exclude;
-                if cache
-                    .workspaces
-                    .data
+    /// Runs the tests of `paths`, or of every file of the configured adapters when it is empty,
+    /// and waits until they finish. Used without a client by the `check` command,
+    /// which reads the results from the published messages.
+    /// Returns the files which were tested.
+    pub fn check_headless(&mut self, paths: &[String]) -> Result<Vec<String>, LSError> {
+        self.folders = vec![self.open_folder(current_dir()?)?];
+        self.jobs = JobQueue::new(self.concurrency());
+        self.refresh_workspaces_cache()?;
+        let mut tested = vec![];
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in self.workspaces_cache()
+        {
+            for (workspace, files) in workspaces.data.iter() {
+                let files = files
                     .iter()
-                    .any(|(_, workspace)| workspace.contains(&path.to_string()))
-                {
-                    return false;
+                    .filter(|file| paths.is_empty() || paths.contains(file))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !files.is_empty() {
+                    self.diagnose(adapter, workspace, &files, &[], None, None);
+                    tested.extend(files);
                 }
-
-                Self::project_files(&base_dir, include, exclude).contains(&path.to_owned())
-            }),
-            Err(e) => {
-                tracing::error!("Error: {:?}", e);
-                false
             }
         }
+        // Dropping the queue waits for the queued runs
+        drop(std::mem::take(&mut self.jobs));
+        Ok(tested)
     }
 
-    /// Checks a specific file for diagnostics, optionally refreshing the workspace cache.
-    /// This function will trigger the publication of diagnostics for the specified file
-    /// through the Language Server Protocol.
-    pub fn check_file(&mut self, path: &str, refresh_needed: bool) -> Result<(), LSError> {
-        if refresh_needed || self.workspaces_cache.is_empty() {
+    /// Handles `textDocument/diagnostic`.
+    /// The file is tested again only when it was modified since its last run,
+    /// and the response is sent when the run finishes.
+    pub fn pull_document_diagnostics(
+        &mut self,
+        id: NumberOrString,
+        params: DocumentDiagnosticParams,
+    ) -> Result<(), LSError> {
+        if self.workspaces_cache().next().is_none() {
             self.refresh_workspaces_cache()?;
         }
-        self.workspaces_cache.iter().for_each(
-            |WorkspaceAnalysis {
-                 adapter_config: adapter,
-                 workspaces,
-             }| {
-                for (workspace, paths) in workspaces.data.iter() {
-                    if !paths.contains(&path.to_string()) {
-                        continue;
-                    }
-                    let _ = self.diagnose(adapter, workspace, &[path.to_string()]);
+        let path = format_uri(params.text_document.uri.as_str());
+        let report = Arc::new(PendingReport::new(
+            id,
+            PullRequest::Document {
+                path: path.clone(),
+                previous_result_id: params.previous_result_id,
+            },
+            self.results.clone(),
+        ));
+        if self.results.is_fresh(&path) {
+            return Ok(());
+        }
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in self.workspaces_cache()
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                if paths.contains(&path) {
+                    self.diagnose(
+                        adapter,
+                        workspace,
+                        std::slice::from_ref(&path),
+                        &[],
+                        Some(Arc::clone(&report)),
+                        None,
+                    );
                 }
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles `workspace/diagnostic`.
+    /// Only the files modified since their last run are tested,
+    /// and the response is sent when all runs finish.
+    pub fn pull_workspace_diagnostics(
+        &mut self,
+        id: NumberOrString,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<(), LSError> {
+        if self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        let enabled_folders = self
+            .folders
+            .iter()
+            .filter(|folder| folder.workspace_diagnostics_enabled())
+            .collect::<Vec<_>>();
+        let paths = enabled_folders
+            .iter()
+            .flat_map(|folder| folder.workspaces_cache.iter())
+            .flat_map(|cache| cache.workspaces.data.values().flatten().cloned())
+            .collect();
+        let previous_result_ids = params
+            .previous_result_ids
+            .into_iter()
+            .map(|PreviousResultId { uri, value }| (format_uri(uri.as_str()), value))
+            .collect();
+        let report = Arc::new(PendingReport::new(
+            id,
+            PullRequest::Workspace {
+                paths,
+                previous_result_ids,
             },
-        );
+            self.results.clone(),
+        ));
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in enabled_folders
+            .iter()
+            .flat_map(|folder| folder.workspaces_cache.iter())
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                let outdated_paths = paths
+                    .iter()
+                    .filter(|path| !self.results.is_fresh(path))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !outdated_paths.is_empty() {
+                    self.diagnose(
+                        adapter,
+                        workspace,
+                        &outdated_paths,
+                        &[],
+                        Some(Arc::clone(&report)),
+                        None,
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
-    fn get_diagnostics(
+    pub fn refreshing_needed(&self, path: &str) -> bool {
+        self.folders.iter().any(|folder| {
+            folder.adapters().iter().any(|(base_dir, adapter)| {
+                let cached = folder
+                    .workspaces_cache
+                    .iter()
+                    .filter(|cache| &cache.adapter_config == adapter)
+                    .any(|cache| {
+                        cache
+                            .workspaces
+                            .data
+                            .values()
+                            .any(|workspace| workspace.contains(&path.to_string()))
+                    });
+                if cached {
+                    return false;
+                }
+
+                folder.files.matches(
+                    Path::new(path),
+                    base_dir,
+                    &adapter.include,
+                    &adapter.exclude,
+                )
+            })
+        })
+    }
+
+    /// Checks a specific file for diagnostics, optionally refreshing the workspace cache.
+    /// This function queues the run and returns. Diagnostics for the specified file
+    /// are published through the Language Server Protocol when the run finishes.
+    pub fn check_file(&mut self, path: &str, refresh_needed: bool) -> Result<(), LSError> {
+        if refresh_needed || self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in self.workspaces_cache()
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                if !paths.contains(&path.to_string()) {
+                    continue;
+                }
+                self.diagnose(adapter, workspace, &[path.to_string()], &[], None, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs specific tests in a file, identified either by their ids
+    /// or by a position inside of a test.
+    /// Like `check_file`, diagnostics are published when the queued run finishes.
+    pub fn run_test(
+        &mut self,
+        path: &str,
+        test_ids: &[String],
+        position: Option<Position>,
+    ) -> Result<(), LSError> {
+        if self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        let target_paths = vec![path.to_string()];
+        let discovered = self.discovered_tests(path)?;
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in self.workspaces_cache()
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                if !paths.contains(&path.to_string()) {
+                    continue;
+                }
+                let test_ids = match position {
+                    Some(position) if test_ids.is_empty() => {
+                        let discovered = self.discover(adapter, workspace, &target_paths)?;
+                        Self::find_test_at(&discovered, position)
+                            .map(|test_item| vec![test_item.id.clone()])
+                            .unwrap_or_default()
+                    }
+                    _ => test_ids.to_vec(),
+                };
+                if test_ids.is_empty() {
+                    tracing::warn!("no test found to run in {}", path);
+                    continue;
+                }
+                let test_ranges = discovered
+                    .tests
+                    .data
+                    .iter()
+                    .flat_map(|found| found.tests.iter())
+                    .filter(|test_item| test_ids.contains(&test_item.id))
+                    .map(|test_item| Range {
+                        start: test_item.start_position.start,
+                        end: test_item.end_position.end,
+                    })
+                    .collect();
+                let run = TestRun {
+                    test_ranges,
+                    ..self.test_run(adapter, workspace, &target_paths, &test_ids, None, None)
+                };
+                self.jobs.push(move || run.diagnose());
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the innermost test which contains `position`.
+    fn find_test_at(discovered: &DiscoverResult, position: Position) -> Option<&TestItem> {
+        discovered
+            .data
+            .iter()
+            .flat_map(|found| found.tests.iter())
+            .filter(|test_item| {
+                test_item.start_position.start <= position && position <= test_item.end_position.end
+            })
+            .max_by_key(|test_item| test_item.start_position.start)
+    }
+
+    /// Queues a run of the tests in `paths` on the worker threads.
+    /// The diagnostics go to `report` if the run answers a pull request,
+    /// otherwise they are published.
+    fn diagnose(
         &self,
         adapter: &AdapterConfiguration,
         workspace: &str,
         paths: &[String],
+        test_ids: &[String],
+        report: Option<Arc<PendingReport>>,
+        export: Option<Arc<JunitExport>>,
+    ) {
+        let run = self.test_run(adapter, workspace, paths, test_ids, report, export);
+        self.jobs.push(move || run.diagnose());
+    }
+
+    /// A run of the tests in `paths`, which is queued by `diagnose`
+    fn test_run(
+        &self,
+        adapter: &AdapterConfiguration,
+        workspace: &str,
+        paths: &[String],
+        test_ids: &[String],
+        report: Option<Arc<PendingReport>>,
+        export: Option<Arc<JunitExport>>,
+    ) -> TestRun {
+        TestRun {
+            adapter_id: self.adapter_id(adapter),
+            adapter: adapter.clone(),
+            workspace_folder: self.folder_dir(paths),
+            workspace: workspace.to_string(),
+            paths: paths.to_vec(),
+            test_ids: test_ids.to_vec(),
+            processes: self.processes.clone(),
+            results: self.results.clone(),
+            request_id: self.request_id.clone(),
+            report,
+            junit_export: export,
+            log_requests: Arc::clone(&self.log_requests),
+            refresh_inlay_hints: self.inlay_hint_refresh_support(),
+            test_ranges: vec![],
+        }
+    }
+
+    /// Whether the client supports `workspace/inlayHint/refresh`, sent when results change
+    fn inlay_hint_refresh_support(&self) -> bool {
+        self.client_capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.inlay_hint.as_ref())
+            .and_then(|capability| capability.refresh_support)
+            .unwrap_or(false)
+    }
+
+    /// Id of `adapter` in the configuration of the folders, or its path when it is not configured anymore
+    fn adapter_id(&self, adapter: &AdapterConfiguration) -> AdapterId {
+        self.folders
+            .iter()
+            .flat_map(ProjectFolder::scopes)
+            .flat_map(|scope| scope.adapters.into_iter())
+            .find(|(_, (_, configured))| configured == adapter)
+            .map_or(adapter.path.clone(), |(id, _)| id)
+    }
+
+    /// Handles the response of the client to a request of the server.
+    /// The adapter logs are opened when the user chose the action of the `window/showMessageRequest`.
+    pub fn handle_response(&self, id: &str, result: &Value) -> Result<(), LSError> {
+        let Some(logs) = self.log_requests.lock().unwrap().remove(id) else {
+            return Ok(());
+        };
+        let chosen = MessageActionItem::deserialize(result)
+            .is_ok_and(|action| action.title == OPEN_LOG_ACTION);
+        if !chosen {
+            return Ok(());
+        }
+        let show_document = self
+            .client_capabilities
+            .window
+            .as_ref()
+            .and_then(|window| window.show_document.as_ref())
+            .is_some_and(|capability| capability.support);
+        for log in logs {
+            let Ok(uri) = Url::from_file_path(&log) else {
+                continue;
+            };
+            if show_document {
+                send_stdout(&json!({
+                    "jsonrpc": "2.0",
+                    "id": server_request_id("showAdapterLog"),
+                    "method": "window/showDocument",
+                    "params": ShowDocumentParams {
+                        uri,
+                        external: Some(false),
+                        take_focus: Some(true),
+                        selection: None,
+                    },
+                }))?;
+            } else {
+                send_stdout(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "window/showMessage",
+                    "params": ShowMessageParams {
+                        typ: MessageType::INFO,
+                        message: format!("Adapter log: {}", log.display()),
+                    },
+                }))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tests of `path` from the cache, which are discovered when they are not cached yet
+    fn discovered_tests(&mut self, path: &str) -> Result<Arc<DiscoveredFile>, LSError> {
+        if let Some(discovered) = self.discovered.get(path) {
+            return Ok(Arc::clone(discovered));
+        }
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        let discovered = Arc::new(DiscoveredFile {
+            tests: self.discover_file(path)?,
+            line_lengths: text
+                .lines()
+                .map(|line| line.encode_utf16().count() as u32)
+                .collect(),
+        });
+        self.discovered
+            .insert(path.to_string(), Arc::clone(&discovered));
+        Ok(discovered)
+    }
+
+    /// Forgets the tests discovered in `path`, or in the files below it, after they changed
+    pub fn forget_discovered(&mut self, path: &Path) {
+        self.discovered
+            .retain(|file, _| !Path::new(file).starts_with(path));
+    }
+
+    pub fn discover_file(&self, path: &str) -> Result<DiscoverResult, LSError> {
+        let target_paths = vec![path.to_string()];
+        let mut result: DiscoverResult = DiscoverResult { data: vec![] };
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in self.workspaces_cache()
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                if !paths.contains(&path.to_string()) {
+                    continue;
+                }
+                result
+                    .data
+                    .extend(self.discover(adapter, workspace, &target_paths)?.data);
+            }
+        }
+        Ok(result)
+    }
+
+    fn discover(
+        &self,
+        adapter: &AdapterConfiguration,
+        workspace: &str,
+        paths: &[String],
+    ) -> Result<DiscoverResult, LSError> {
+        let variables = Variables {
+            workspace_folder: &self.folder_dir(paths),
+            workspace: Some(workspace),
+            files: paths,
+            env: process_env,
+        };
+        discover_tests(
+            &self.processes,
+            &variables.adapter(adapter),
+            paths,
+            self.cancel_keys(),
+        )
+    }
+
+    /// The workspace folder of the first of `paths`, or the first workspace folder
+    fn folder_dir(&self, paths: &[String]) -> PathBuf {
+        let folder = paths.first().and_then(|path| {
+            self.folders
+                .iter()
+                .filter(|folder| Path::new(path).starts_with(&folder.dir))
+                .max_by_key(|folder| folder.dir.components().count())
+        });
+        match folder {
+            Some(folder) => folder.dir.clone(),
+            None => self.project_dir().unwrap_or_default(),
+        }
+    }
+
+    /// Every test of the workspace as a tree of files, namespaces and tests.
+    pub fn discover_workspace_tests(&mut self) -> Result<Vec<TestTreeItem>, LSError> {
+        if self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        let mut files = vec![];
+        for WorkspaceAnalysis {
+            adapter_config: adapter,
+            workspaces,
+        } in self.workspaces_cache()
+        {
+            for (workspace, paths) in workspaces.data.iter() {
+                let discovered = self.discover(adapter, workspace, paths)?;
+                files.extend(discovered.data.iter().map(Self::build_test_tree));
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Nests the tests of a file in their namespaces.
+    /// Namespaces which the adapter did not report are made from the `::` separated
+    /// prefixes of test ids, and cover the tests in them.
+    fn build_test_tree(found: &FoundFileTests) -> TestTreeItem {
+        let mut file = TestTreeItem {
+            id: found.path.clone(),
+            name: Path::new(&found.path)
+                .file_name()
+                .map_or(found.path.clone(), |name| {
+                    name.to_string_lossy().to_string()
+                }),
+            kind: TestItemKind::File,
+            path: found.path.clone(),
+            range: Range::default(),
+            children: vec![],
+        };
+        for test_item in &found.tests {
+            let segments = test_item.id.split("::").collect::<Vec<_>>();
+            let range = Range::new(test_item.start_position.start, test_item.end_position.end);
+            let mut parent = &mut file;
+            for depth in 1..segments.len() {
+                let id = segments[..depth].join("::");
+                let reported = found.namespaces.iter().find(|namespace| namespace.id == id);
+                let index = match parent
+                    .children
+                    .iter()
+                    .position(|child| child.kind == TestItemKind::Namespace && child.id == id)
+                {
+                    Some(index) => index,
+                    None => {
+                        parent.children.push(TestTreeItem {
+                            id,
+                            name: segments[depth - 1].to_string(),
+                            kind: TestItemKind::Namespace,
+                            path: found.path.clone(),
+                            range: reported.map_or(range, |namespace| {
+                                Range::new(
+                                    namespace.start_position.start,
+                                    namespace.end_position.end,
+                                )
+                            }),
+                            children: vec![],
+                        });
+                        parent.children.len() - 1
+                    }
+                };
+                parent = &mut parent.children[index];
+                if reported.is_none() {
+                    parent.range.start = parent.range.start.min(range.start);
+                    parent.range.end = parent.range.end.max(range.end);
+                }
+            }
+            parent.children.push(TestTreeItem {
+                id: test_item.id.clone(),
+                name: segments[segments.len() - 1].to_string(),
+                kind: TestItemKind::Test,
+                path: test_item.path.clone(),
+                range,
+                children: vec![],
+            });
+        }
+        file
+    }
+
+    /// Returns unresolved code lenses for the tests, namespaces and the file itself.
+    pub fn code_lens(&mut self, path: &str) -> Result<Vec<CodeLens>, LSError> {
+        if self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        let uri = Url::from_file_path(path).unwrap().to_string();
+        let discovered = self.discover_file(path)?;
+        let tests = discovered
+            .data
+            .iter()
+            .flat_map(|found| found.tests.iter())
+            .collect::<Vec<_>>();
+        if tests.is_empty() {
+            return Ok(vec![]);
+        }
+        let namespaces = discovered
+            .data
+            .iter()
+            .flat_map(|found| found.namespaces.iter())
+            .collect::<Vec<_>>();
+        Ok(Self::build_code_lenses(&uri, &tests, &namespaces))
+    }
+
+    /// Handles `textDocument/hover`.
+    /// Shows the last result of the test at `position`, with its output and the command which ran it.
+    pub fn hover(&mut self, path: &str, position: Position) -> Result<Option<Hover>, LSError> {
+        if self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        let discovered = self.discovered_tests(path)?;
+        let Some(test_item) = Self::find_test_at(&discovered.tests, position) else {
+            return Ok(None);
+        };
+        let record = self.results.test(&test_item.path, &test_item.id);
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: hover_markdown(&test_item.name, record.as_ref(), SystemTime::now()),
+            }),
+            range: Some(Range::new(
+                test_item.start_position.start,
+                test_item.end_position.end,
+            )),
+        }))
+    }
+
+    /// Handles `textDocument/inlayHint`.
+    /// Every test in `range` which has run gets its last status at the end of its first line.
+    pub fn inlay_hints(&mut self, path: &str, range: Range) -> Result<Vec<InlayHint>, LSError> {
+        if self.workspaces_cache().next().is_none() {
+            self.refresh_workspaces_cache()?;
+        }
+        let discovered = self.discovered_tests(path)?;
+        let now = SystemTime::now();
+        let hints = discovered
+            .tests
+            .data
+            .iter()
+            .flat_map(|found| found.tests.iter())
+            .filter(|test_item| {
+                let line = test_item.start_position.start.line;
+                range.start.line <= line && line <= range.end.line
+            })
+            .filter_map(|test_item| {
+                let record = self.results.test(&test_item.path, &test_item.id)?;
+                let line = test_item.start_position.start.line;
+                let character = discovered
+                    .line_lengths
+                    .get(line as usize)
+                    .copied()
+                    .unwrap_or(test_item.start_position.end.character);
+                Some(InlayHint {
+                    position: Position::new(line, character),
+                    label: InlayHintLabel::String(inlay_label(&record, now)),
+                    kind: None,
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: Some(true),
+                    padding_right: None,
+                    data: None,
+                })
+            })
+            .collect();
+        Ok(hints)
+    }
+
+    fn build_code_lenses(
+        uri: &str,
+        tests: &[&TestItem],
+        reported_namespaces: &[&TestItem],
+    ) -> Vec<CodeLens> {
+        let lens = |range: Range, kind: CodeLensKind, test_ids: Vec<String>| CodeLens {
+            range,
+            command: None,
+            data: Some(json!(CodeLensData {
+                kind,
+                args: RunCommandArgs {
+                    uri: uri.to_string(),
+                    test_ids,
+                },
+            })),
+        };
+        let mut lenses = vec![lens(Range::default(), CodeLensKind::File, vec![])];
+
+        // Namespaces are the `::` separated prefixes of test ids.
+        // They are put on the line of the namespace which the adapter reported,
+        // otherwise above the first test they contain.
+        let mut namespaces: Vec<(String, Range)> = vec![];
+        for test_item in tests {
+            let segments = test_item.id.split("::").collect::<Vec<_>>();
+            for depth in 1..segments.len() {
+                let namespace = segments[..depth].join("::");
+                if !namespaces.iter().any(|(name, _)| name == &namespace) {
+                    let range = reported_namespaces
+                        .iter()
+                        .find(|reported| reported.id == namespace)
+                        .map_or(test_item.start_position, |reported| reported.start_position);
+                    namespaces.push((namespace, range));
+                }
+            }
+        }
+        for (namespace, range) in namespaces {
+            let prefix = format!("{namespace}::");
+            let test_ids = tests
+                .iter()
+                .filter(|test_item| test_item.id.starts_with(&prefix))
+                .map(|test_item| test_item.id.clone())
+                .collect();
+            lenses.push(lens(range, CodeLensKind::Namespace, test_ids));
+        }
+
+        lenses.extend(tests.iter().map(|test_item| {
+            lens(
+                test_item.start_position,
+                CodeLensKind::Test,
+                vec![test_item.id.clone()],
+            )
+        }));
+        lenses
+    }
+
+    /// Fills the command of a code lens returned by `code_lens`.
+    pub fn resolve_code_lens(&self, mut code_lens: CodeLens) -> Result<CodeLens, LSError> {
+        let data = code_lens
+            .data
+            .take()
+            .ok_or(LSError::Any(anyhow::anyhow!("code lens has no data")))?;
+        let CodeLensData { kind, args } = serde_json::from_value(data)?;
+        let (title, command) = match kind {
+            CodeLensKind::Test => ("▶ Run", RUN_TEST_COMMAND),
+            CodeLensKind::Namespace => ("▶ Run namespace", RUN_TEST_COMMAND),
+            CodeLensKind::File => ("▶ Run file", RUN_FILE_COMMAND),
+        };
+        code_lens.command = Some(lsp_types::Command {
+            title: title.to_string(),
+            command: command.to_string(),
+            arguments: Some(vec![json!(args)]),
+        });
+        Ok(code_lens)
+    }
+
+    /// Handles `workspace/executeCommand` for the commands used by code lenses.
+    pub fn execute_command(&mut self, params: ExecuteCommandParams) -> Result<(), LSError> {
+        let args = params
+            .arguments
+            .first()
+            .map(RunCommandArgs::deserialize)
+            .transpose()?
+            .ok_or(LSError::Any(anyhow::anyhow!("no arguments for command")))?;
+        let path = format_uri(&args.uri);
+        match params.command.as_str() {
+            RUN_TEST_COMMAND => self.run_test(&path, &args.test_ids, None),
+            RUN_FILE_COMMAND => self.check_file(&path, false),
+            command => Err(LSError::Any(anyhow::anyhow!(
+                "unknown command: {}",
+                command
+            ))),
+        }
+    }
+
+    pub fn send_diagnostics(uri: Url, diagnostics: Vec<Diagnostic>) -> Result<(), LSError> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": params,
+        }))?;
+        Ok(())
+    }
+
+    /// Adapter id, workspace and files of every workspace detected in `folder`
+    fn workspace_suites(
+        folder: &ProjectFolder,
+    ) -> Vec<(AdapterId, WorkspaceFilePath, Vec<FilePath>)> {
+        let scopes = folder.scopes();
+        let mut suites = vec![];
+        for analysis in &folder.workspaces_cache {
+            let Some(id) = scopes
+                .iter()
+                .flat_map(|scope| scope.adapters.iter())
+                .find(|(_, (_, adapter))| *adapter == analysis.adapter_config)
+                .map(|(id, _)| id)
+            else {
+                continue;
+            };
+            for (workspace, paths) in &analysis.workspaces.data {
+                suites.push((id.clone(), workspace.clone(), paths.clone()));
+            }
+        }
+        suites.sort();
+        suites
+    }
+
+    /// Id of the adapter testing every file of the workspace folders.
+    /// A file tested by several adapters belongs to the first one found.
+    pub fn file_adapters(&self) -> HashMap<String, AdapterId> {
+        let mut file_adapters = HashMap::new();
+        for (id, _, paths) in self.folders.iter().flat_map(Self::workspace_suites) {
+            for path in paths {
+                file_adapters.entry(path).or_insert_with(|| id.clone());
+            }
+        }
+        file_adapters
+    }
+
+    /// Handles `$/exportResults`.
+    /// Returns the last results of the tested files of the workspace in the requested format,
+    /// and writes them to `path` when it is given.
+    pub fn export_results(&self, params: ExportResultsParams) -> Result<Value, LSError> {
+        let mut files = self
+            .file_adapters()
+            .into_iter()
+            .filter_map(|(path, adapter)| {
+                let result = self.results.get(&path)?;
+                Some(FileReport {
+                    adapter: Some(adapter),
+                    path,
+                    diagnostics: result.diagnostics,
+                })
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let content = match params.format {
+            ExportFormat::Junit => {
+                let suites = self
+                    .folders
+                    .iter()
+                    .flat_map(Self::workspace_suites)
+                    .collect::<Vec<_>>();
+                junit_xml(&suite_reports(&suites, &self.results), &self.project_dir()?)?
+            }
+            ExportFormat::Sarif => {
+                serde_json::to_string_pretty(&sarif_log(&files, &self.project_dir()?))?
+            }
+        };
+        if let Some(path) = &params.path {
+            std::fs::write(path, &content)?;
+        }
+        Ok(json!({
+            "format": params.format,
+            "content": content,
+            "path": params.path,
+        }))
+    }
+
+    pub fn shutdown(&self, id: NumberOrString) -> Result<(), LSError> {
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": null
+        }))?;
+        Ok(())
+    }
+
+    /// Stops the queued and running tests when the client is gone
+    pub fn disconnect(&self) {
+        self.jobs.cancel();
+        self.processes.cancel_all();
+    }
+}
+
+/// Options of the file operations which the server is notified of
+fn file_operation_options() -> FileOperationRegistrationOptions {
+    FileOperationRegistrationOptions {
+        filters: vec![FileOperationFilter {
+            scheme: Some("file".to_string()),
+            pattern: FileOperationPattern {
+                glob: "**/*".to_string(),
+                ..FileOperationPattern::default()
+            },
+        }],
+    }
+}
+
+fn file_paths<'a>(uris: impl Iterator<Item = &'a str>) -> Vec<PathBuf> {
+    uris.filter_map(|uri| Url::parse(uri).ok()?.to_file_path().ok())
+        .collect()
+}
+
+/// Whether the file index must be rebuilt when `path` changes
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == ".gitignore" || name == ".ignore")
+}
+
+/// Id of a request which the server sends to the client, different for every request
+/// so that concurrent runs do not reuse an id which is still waiting for its response.
+fn server_request_id(name: &str) -> String {
+    static REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "testing-ls/{name}/{}",
+        REQUEST_COUNT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// `command` as it can be run in a shell, with its directory and environment.
+/// `TESTING_LS_STREAM_EVENTS` is left out, since it is only needed by the server.
+fn command_line(command: &Command) -> String {
+    fn quote(word: &str) -> String {
+        let plain = !word.is_empty()
+            && word
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+        if plain {
+            word.to_string()
+        } else {
+            format!("'{}'", word.replace('\'', "'\\''"))
+        }
+    }
+    let mut words = command
+        .get_envs()
+        .filter_map(|(key, value)| {
+            Some(format!(
+                "{}={}",
+                key.to_string_lossy(),
+                quote(&value?.to_string_lossy())
+            ))
+        })
+        .collect::<Vec<_>>();
+    words.push(quote(&command.get_program().to_string_lossy()));
+    words.extend(command.get_args().map(|arg| quote(&arg.to_string_lossy())));
+    let line = words.join(" ");
+    match command.get_current_dir() {
+        Some(dir) => format!("cd {} && {line}", quote(&dir.to_string_lossy())),
+        None => line,
+    }
+}
+
+/// Runs `<adapter command> discover` for `paths`
+fn discover_tests(
+    processes: &ProcessRegistry,
+    adapter: &AdapterConfiguration,
+    paths: &[String],
+    keys: Vec<CancelKey>,
+) -> Result<DiscoverResult, LSError> {
+    let mut adapter_command = Command::new(&adapter.path);
+    let mut args: Vec<&str> = vec![];
+    paths.iter().for_each(|path| {
+        args.push("--file-paths");
+        args.push(path);
+    });
+    let output = processes.output(
+        adapter_command
+            .arg("discover")
+            .args(args)
+            .arg("--")
+            .args(&adapter.extra_arg)
+            .envs(&adapter.env),
+        keys,
+    )?;
+
+    let adapter_result =
+        String::from_utf8(output.stdout).map_err(|err| LSError::Adapter(err.to_string()))?;
+    Ok(serde_json::from_str(&adapter_result)?)
+}
+
+/// Last results of the tested files of every workspace, for the JUnit report
+fn suite_reports(
+    suites: &[(AdapterId, WorkspaceFilePath, Vec<FilePath>)],
+    results: &ResultStore,
+) -> Vec<SuiteReport> {
+    suites
+        .iter()
+        .map(|(adapter, workspace, paths)| SuiteReport {
+            adapter: adapter.clone(),
+            workspace: workspace.clone(),
+            files: paths
+                .iter()
+                .filter_map(|path| {
+                    let result = results.get(path)?;
+                    Some(FileTests {
+                        path: path.clone(),
+                        diagnostics: result.diagnostics,
+                        tests: results.tests(path),
+                    })
+                })
+                .collect(),
+        })
+        .filter(|suite| !suite.files.is_empty())
+        .collect()
+}
+
+/// The JUnit report of a workspace run, written when the last of its `TestRun`s has finished
+struct JunitExport {
+    path: PathBuf,
+    project_dir: PathBuf,
+    suites: Vec<(AdapterId, WorkspaceFilePath, Vec<FilePath>)>,
+    results: ResultStore,
+}
+
+impl JunitExport {
+    fn write(&self) {
+        let result = junit_xml(
+            &suite_reports(&self.suites, &self.results),
+            &self.project_dir,
+        )
+        .and_then(|xml| Ok(std::fs::write(&self.path, xml)?));
+        match result {
+            Ok(()) => tracing::info!("Wrote JUnit report to {:?}", self.path),
+            Err(err) => tracing::error!("Failed to write JUnit report {:?}: {:?}", self.path, err),
+        }
+    }
+}
+
+/// An adapter run queued from `TestingLS`, with everything it needs to run on a worker thread
+struct TestRun {
+    /// Prefix of the adapter stderr in the client log
+    adapter_id: AdapterId,
+    /// The adapter as configured, with its variables not expanded yet
+    adapter: AdapterConfiguration,
+    workspace_folder: PathBuf,
+    workspace: String,
+    paths: Vec<String>,
+    test_ids: Vec<String>,
+    processes: ProcessRegistry,
+    results: ResultStore,
+    /// The request which started the run. Cancelling it kills the adapter.
+    request_id: Option<NumberOrString>,
+    report: Option<Arc<PendingReport>>,
+    /// Written by the last run of the workspace to finish
+    junit_export: Option<Arc<JunitExport>>,
+    log_requests: LogRequests,
+    /// Whether the client is asked to refresh the inlay hints when the run has finished
+    refresh_inlay_hints: bool,
+    /// Where the tests of `test_ids` are, from the start of their name to their end.
+    /// Their diagnostics are replaced, the others of the file are kept.
+    test_ranges: Vec<Range>,
+}
+
+impl TestRun {
+    /// Whether `diagnostic` is in one of the tests which ran
+    fn ran(&self, diagnostic: &Diagnostic) -> bool {
+        let position = diagnostic.range.start;
+        self.test_ranges
+            .iter()
+            .any(|range| range.start <= position && position <= range.end)
+    }
+
+    fn variables(&self) -> Variables<'_> {
+        Variables {
+            workspace_folder: &self.workspace_folder,
+            workspace: Some(&self.workspace),
+            files: &self.paths,
+            env: process_env,
+        }
+    }
+
+    /// Runs the tests in `paths` through the adapter.
+    /// When `test_ids` is empty, all tests in the files are run (`run-file-test`),
+    /// otherwise only the given tests are run (`run-test`).
+    fn get_diagnostics(
+        &self,
+        progress_token: &NumberOrString,
     ) -> Result<Vec<(String, Vec<Diagnostic>)>, LSError> {
+        let Self {
+            workspace,
+            paths,
+            test_ids,
+            ..
+        } = self;
+        let adapter = &self.variables().adapter(&self.adapter);
         let mut adapter_command = Command::new(&adapter.path);
         let mut diagnostics: Vec<(String, Vec<Diagnostic>)> = vec![];
         let cwd = PathBuf::from(workspace);
@@ -312,35 +1856,81 @@ impl TestingLS {
             args.push("--file-paths");
             args.push(path);
         });
+        test_ids.iter().for_each(|test_id| {
+            args.push("--test-ids");
+            args.push(test_id);
+        });
+        let subcommand = if test_ids.is_empty() {
+            "run-file-test"
+        } else {
+            "run-test"
+        };
 
-        let output = adapter_command
-            .arg("run-file-test")
+        adapter_command
+            .arg(subcommand)
             .args(args)
             .arg("--")
             .args(&adapter.extra_arg)
-            .envs(&adapter.env)
-            .output()
-            .map_err(|err| LSError::Adapter(err.to_string()))?;
-        let Output { stdout, stderr, .. } = output;
-        if !stderr.is_empty() {
-            let message = "Error occurred when running test via adapter.\nCheck adapter log or run tests manually".to_string();
-            let message_type = MessageType::ERROR;
-            let params: ShowMessageParams = ShowMessageParams {
-                typ: message_type,
-                message,
-            };
-            send_stdout(&json!({
-                "jsonrpc": "2.0",
-                "method": "window/showMessage",
-                "params": params,
-            }))
-            .unwrap();
+            .envs(&adapter.env);
+        let mut stream = EventStream {
+            total: (!test_ids.is_empty()).then_some(test_ids.len()),
+            command: command_line(adapter_command),
+            ..EventStream::default()
+        };
+        let output = match self.processes.output_with_lines(
+            adapter_command.env(STREAM_EVENTS_ENV, "1"),
+            self.request_id
+                .iter()
+                .cloned()
+                .map(CancelKey::Request)
+                .chain([CancelKey::Progress(progress_token.clone())])
+                .collect(),
+            adapter.timeout.map(Duration::from_secs),
+            |stream_kind, line| {
+                if stream_kind == OutputStream::Stderr {
+                    if let Err(err) = self.log_stderr(line) {
+                        tracing::error!("Failed to log adapter stderr: {:?}", err);
+                    }
+                    return;
+                }
+                match serde_json::from_str::<AdapterEvent>(line) {
+                    Ok(event) => {
+                        if let Err(err) = self.handle_event(event, &mut stream, progress_token) {
+                            tracing::error!("Failed to handle adapter event: {:?}", err);
+                        }
+                    }
+                    // The final result, or the whole output of an adapter which does not stream
+                    Err(_) => {
+                        stream.result.push_str(line);
+                        stream.result.push('\n');
+                    }
+                }
+            },
+        ) {
+            Err(LSError::Timeout) => return self.timed_out_diagnostics(stream),
+            output => output?,
+        };
+        let Output { status, stderr, .. } = output;
+        let result = serde_json::from_str::<RunFileTestResult>(&stream.result);
+        // Output on stderr alone is only logged, it is often a warning
+        if !status.success() || result.is_err() {
+            self.offer_adapter_log(&stream.logs)?;
         }
 
-        let adapter_result =
-            String::from_utf8(stdout).map_err(|err| LSError::Adapter(err.to_string()))?;
-        match serde_json::from_str::<RunFileTestResult>(&adapter_result) {
+        match result {
             Ok(res) => {
+                for message in &res.messages {
+                    send_stdout(&json!({
+                        "jsonrpc": "2.0",
+                        "method": "window/showMessage",
+                        "params": message,
+                    }))?;
+                }
+                self.send_test_results(
+                    &res.tests,
+                    &stream.command,
+                    &String::from_utf8_lossy(&stderr),
+                )?;
                 for target_file in paths {
                     let diagnostics_for_file: Vec<Diagnostic> = res
                         .data
@@ -353,33 +1943,266 @@ impl TestingLS {
                     diagnostics.push((uri.to_string(), diagnostics_for_file));
                 }
             }
-            Err(err) => {
-                tracing::error!("Failed to parse adapter result: {:?}", err);
+            Err(err) => {
+                tracing::error!("Failed to parse adapter result: {:?}", err);
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// Sends a line which the adapter wrote to stderr to the client log
+    fn log_stderr(&self, line: &str) -> Result<(), LSError> {
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "method": "window/logMessage",
+            "params": LogMessageParams {
+                typ: MessageType::LOG,
+                message: format!("[{}] {line}", self.adapter_id),
+            },
+        }))
+    }
+
+    /// Tells the user that the adapter failed, with an action to open the `logs` of the run
+    fn offer_adapter_log(&self, logs: &[PathBuf]) -> Result<(), LSError> {
+        let message = format!(
+            "{} reported errors while running tests. See the output log or run the tests manually",
+            self.adapter_id
+        );
+        if logs.is_empty() {
+            return send_stdout(&json!({
+                "jsonrpc": "2.0",
+                "method": "window/showMessage",
+                "params": ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message,
+                },
+            }));
+        }
+        let id = server_request_id("openAdapterLog");
+        self.log_requests
+            .lock()
+            .unwrap()
+            .insert(id.clone(), logs.to_vec());
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "window/showMessageRequest",
+            "params": ShowMessageRequestParams {
+                typ: MessageType::ERROR,
+                message,
+                actions: Some(vec![MessageActionItem {
+                    title: OPEN_LOG_ACTION.to_string(),
+                    properties: HashMap::new(),
+                }]),
+            },
+        }))
+    }
+
+    /// Diagnostics for the tests which were still running when the adapter timed out.
+    /// When the adapter streamed no events, the requested tests are considered running,
+    /// or the whole files if all tests in them were run.
+    fn timed_out_diagnostics(
+        &self,
+        stream: EventStream,
+    ) -> Result<Vec<(String, Vec<Diagnostic>)>, LSError> {
+        let timeout = self.adapter.timeout.unwrap_or_default();
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "method": "window/showMessage",
+            "params": ShowMessageParams {
+                typ: MessageType::WARNING,
+                message: format!(
+                    "Tests did not finish within {timeout}s and were stopped ({})",
+                    self.adapter.path
+                ),
+            },
+        }))?;
+        let adapter = self.variables().adapter(&self.adapter);
+        let discovered = discover_tests(&self.processes, &adapter, &self.paths, vec![])
+            .unwrap_or_else(|err| {
+                tracing::error!("Failed to discover timed out tests: {:?}", err);
+                DiscoverResult { data: vec![] }
+            });
+        let nothing_streamed = stream.running.is_empty() && stream.finished == 0;
+        let running = discovered
+            .data
+            .iter()
+            .flat_map(|found| found.tests.iter())
+            .filter(|test_item| {
+                if nothing_streamed {
+                    self.test_ids.contains(&test_item.id)
+                } else {
+                    stream
+                        .running
+                        .iter()
+                        .any(|(id, path)| id == &test_item.id && path == &test_item.path)
+                }
+            })
+            .collect::<Vec<_>>();
+        let tests = running
+            .iter()
+            .map(|test_item| TestResult {
+                id: test_item.id.clone(),
+                path: test_item.path.clone(),
+                status: TestStatus::Errored,
+                duration: Some(timeout * 1000),
+                output: Some(format!("did not finish within {timeout}s")),
+            })
+            .collect::<Vec<_>>();
+        self.send_test_results(&tests, &stream.command, "")?;
+
+        let mut diagnostics = vec![];
+        for target_file in &self.paths {
+            let mut diagnostics_for_file = stream
+                .diagnostics
+                .get(target_file)
+                .cloned()
+                .unwrap_or_default();
+            if running.is_empty() {
+                diagnostics_for_file.push(Diagnostic {
+                    message: format!("Tests did not finish within {timeout}s"),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    ..Diagnostic::default()
+                });
+            }
+            diagnostics_for_file.extend(
+                running
+                    .iter()
+                    .filter(|test_item| &test_item.path == target_file)
+                    .map(|test_item| Diagnostic {
+                        range: test_item.start_position,
+                        message: format!("`{}` did not finish within {timeout}s", test_item.name),
+                        severity: Some(DiagnosticSeverity::ERROR),
+                        ..Diagnostic::default()
+                    }),
+            );
+            let uri = Url::from_file_path(target_file.replace("file://", "")).unwrap();
+            diagnostics.push((uri.to_string(), diagnostics_for_file));
+        }
+        Ok(diagnostics)
+    }
+
+    /// Reports an event streamed by the adapter while the tests run.
+    fn handle_event(
+        &self,
+        event: AdapterEvent,
+        stream: &mut EventStream,
+        progress_token: &NumberOrString,
+    ) -> Result<(), LSError> {
+        match event {
+            AdapterEvent::Plan { total } => {
+                stream.total = Some(total);
+            }
+            AdapterEvent::TestStarted { id, path } => {
+                stream.running.push((id.clone(), path));
+                Self::report_progress(
+                    progress_token,
+                    format!("running {id}"),
+                    stream.percentage(),
+                )?;
+            }
+            AdapterEvent::TestFinished(test) => {
+                stream.finished += 1;
+                stream
+                    .running
+                    .retain(|(id, path)| id != &test.id || path != &test.path);
+                let message = match stream.total {
+                    Some(total) => format!("{}/{} tests", stream.finished, total),
+                    None => format!("{} tests", stream.finished),
+                };
+                Self::report_progress(progress_token, message, stream.percentage())?;
+                self.send_test_results(std::slice::from_ref(&test), &stream.command, "")?;
+            }
+            AdapterEvent::FileDiagnostics(FileDiagnostics { path, diagnostics }) => {
+                let streamed = stream.diagnostics.entry(path.clone()).or_default();
+                streamed.extend(diagnostics);
+                // Pull requests are answered with the final result only
+                if self.report.is_none() && self.paths.contains(&path) {
+                    TestingLS::send_diagnostics(
+                        Url::from_file_path(&path).unwrap(),
+                        streamed.clone(),
+                    )?;
+                }
+            }
+            AdapterEvent::Log { message } => {
+                send_stdout(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "window/logMessage",
+                    "params": LogMessageParams {
+                        typ: MessageType::LOG,
+                        message,
+                    },
+                }))?;
+            }
+            AdapterEvent::LogWritten { path } => {
+                stream.logs.push(PathBuf::from(path));
             }
         }
-        Ok(diagnostics)
+        Ok(())
     }
 
-    fn diagnose(
+    /// Stores the results of single tests and sends them to the client
+    fn send_test_results(
         &self,
-        adapter: &AdapterConfiguration,
-        workspace: &str,
-        paths: &[String],
+        tests: &[TestResult],
+        command: &str,
+        stderr: &str,
+    ) -> Result<(), LSError> {
+        if tests.is_empty() {
+            return Ok(());
+        }
+        self.results.update_tests(tests, command, stderr);
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "method": "$/testResults",
+            "params": { "tests": tests },
+        }))
+    }
+
+    fn report_progress(
+        progress_token: &NumberOrString,
+        message: String,
+        percentage: Option<u32>,
     ) -> Result<(), LSError> {
-        let token = NumberOrString::String("testing-ls/start_testing".to_string());
+        let progress_report = WorkDoneProgressReport {
+            cancellable: Some(true),
+            message: Some(message),
+            percentage,
+        };
+        let params = ProgressParams {
+            token: progress_token.clone(),
+            value: ProgressParamsValue::WorkDone(WorkDoneProgress::Report(progress_report)),
+        };
+        send_stdout(&json!({
+            "jsonrpc": "2.0",
+            "method": "$/progress",
+            "params": params,
+        }))
+    }
+
+    /// Runs the tests while reporting progress, then publishes the diagnostics.
+    fn diagnose(self) {
+        let paths = &self.paths;
+        static PROGRESS_COUNT: AtomicUsize = AtomicUsize::new(0);
+        // Each run has its own token so that it can be cancelled on its own
+        let token = NumberOrString::String(format!(
+            "testing-ls/start_testing/{}",
+            PROGRESS_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
         let progress_token = WorkDoneProgressCreateParams {
             token: token.clone(),
         };
-        send_stdout(&json!({
+        if let Err(err) = send_stdout(&json!({
             "jsonrpc": "2.0",
-            "id": 1,
+            "id": server_request_id("createProgress"),
             "method": "window/workDoneProgress/create",
             "params": progress_token,
-        }))
-        .unwrap();
+        })) {
+            tracing::error!("Failed to create progress: {:?}", err);
+        }
         let progress_begin = WorkDoneProgressBegin {
             title: "Testing".to_string(),
-            cancellable: Some(false),
+            cancellable: Some(true),
             message: Some(format!("testing {} files ...", paths.len())),
             percentage: Some(0),
         };
@@ -387,98 +2210,178 @@ impl TestingLS {
             token: token.clone(),
             value: ProgressParamsValue::WorkDone(WorkDoneProgress::Begin(progress_begin)),
         };
-        send_stdout(&json!({
+        if let Err(err) = send_stdout(&json!({
             "jsonrpc": "2.0",
             "method": "$/progress",
             "params": params,
-        }))
-        .unwrap();
-        let diagnostics = self.get_diagnostics(adapter, workspace, paths)?;
-        for (path, diagnostics) in diagnostics {
-            self.send_diagnostics(
-                Url::from_file_path(path.replace("file://", "")).unwrap(),
-                diagnostics,
-            )?;
+        })) {
+            tracing::error!("Failed to begin progress: {:?}", err);
         }
+        let result = self.get_diagnostics(&token).and_then(|diagnostics| {
+            for (path, diagnostics) in diagnostics {
+                let path = path.replace("file://", "");
+                let diagnostics = if self.test_ids.is_empty() {
+                    self.results.update(&path, diagnostics.clone());
+                    diagnostics
+                } else {
+                    self.results
+                        .replace(&path, |diagnostic| self.ran(diagnostic), diagnostics)
+                };
+                if self.report.is_none() {
+                    TestingLS::send_diagnostics(Url::from_file_path(path).unwrap(), diagnostics)?;
+                }
+            }
+            Ok(())
+        });
+        let message = match result {
+            Ok(()) => format!("tested {} files", paths.len()),
+            Err(LSError::Cancelled) => {
+                if let Some(report) = &self.report {
+                    report.cancelled.store(true, Ordering::Relaxed);
+                }
+                "cancelled".to_string()
+            }
+            Err(err) => {
+                tracing::error!("Failed to run tests: {:?}", err);
+                "failed".to_string()
+            }
+        };
         let progress_end = WorkDoneProgressEnd {
-            message: Some(format!("tested {} files", paths.len())),
+            message: Some(message),
         };
         let params = ProgressParams {
             token: token.clone(),
             value: ProgressParamsValue::WorkDone(WorkDoneProgress::End(progress_end)),
         };
-        send_stdout(&json!({
+        if let Err(err) = send_stdout(&json!({
             "jsonrpc": "2.0",
             "method": "$/progress",
             "params": params,
-        }))
-        .unwrap();
-        Ok(())
-    }
-
-    #[allow(clippy::for_kv_map)]
-    pub fn discover_file(&self, path: &str) -> Result<DiscoverResult, LSError> {
-        let target_paths = vec![path.to_string()];
-        let mut result: DiscoverResult = DiscoverResult { data: vec![] };
-        for WorkspaceAnalysis {
-            adapter_config: adapter,
-            workspaces,
-        } in &self.workspaces_cache
-        {
-            for (_, paths) in workspaces.data.iter() {
-                if !paths.contains(&path.to_string()) {
-                    continue;
-                }
-                result
-                    .data
-                    .extend(self.discover(adapter, &target_paths)?.data);
+        })) {
+            tracing::error!("Failed to end progress: {:?}", err);
+        }
+        // The status of the tests is shown as inlay hints
+        if self.refresh_inlay_hints {
+            if let Err(err) = send_stdout(&json!({
+                "jsonrpc": "2.0",
+                "id": server_request_id("refreshInlayHints"),
+                "method": "workspace/inlayHint/refresh",
+            })) {
+                tracing::error!("Failed to refresh inlay hints: {:?}", err);
             }
         }
-        Ok(result)
+        if let Some(export) = self.junit_export.and_then(Arc::into_inner) {
+            export.write();
+        }
     }
+}
 
-    fn discover(
-        &self,
-        adapter: &AdapterConfiguration,
-        paths: &[String],
-    ) -> Result<DiscoverResult, LSError> {
-        let mut adapter_command = Command::new(&adapter.path);
-        let mut args: Vec<&str> = vec![];
-        paths.iter().for_each(|path| {
-            args.push("--file-paths");
-            args.push(path);
-        });
-        let output = adapter_command
-            .arg("discover")
-            .args(args)
-            .arg("--")
-            .args(&adapter.extra_arg)
-            .envs(&adapter.env)
-            .output()
-            .map_err(|err| LSError::Adapter(err.to_string()))?;
+/// What the adapter has streamed so far during a `TestRun`
+#[derive(Default)]
+struct EventStream {
+    /// Number of tests which are going to run, when known
+    total: Option<usize>,
+    finished: usize,
+    /// Tests which started and did not finish yet, as id and path
+    running: Vec<(String, String)>,
+    /// Diagnostics streamed by path
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Lines which are not events, parsed as `RunFileTestResult` after the adapter exits
+    result: String,
+    /// The adapter command, shown with the results of the tests
+    command: String,
+    /// Logs which the adapter wrote during the run
+    logs: Vec<PathBuf>,
+}
 
-        let adapter_result =
-            String::from_utf8(output.stdout).map_err(|err| LSError::Adapter(err.to_string()))?;
-        Ok(serde_json::from_str(&adapter_result)?)
+impl EventStream {
+    fn percentage(&self) -> Option<u32> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.finished.min(total) * 100 / total) as u32)
     }
+}
 
-    pub fn send_diagnostics(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> Result<(), LSError> {
-        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
-        send_stdout(&json!({
-            "jsonrpc": "2.0",
-            "method": "textDocument/publishDiagnostics",
-            "params": params,
-        }))?;
-        Ok(())
+/// A pull diagnostics request
+enum PullRequest {
+    Document {
+        path: String,
+        previous_result_id: Option<String>,
+    },
+    Workspace {
+        paths: Vec<String>,
+        /// Result ids known by the client, by path
+        previous_result_ids: HashMap<String, String>,
+    },
+}
+
+/// Response to a pull diagnostics request.
+/// The runs started for the request share it, and the response is sent
+/// from the stored results when the last of them drops it.
+struct PendingReport {
+    id: NumberOrString,
+    request: PullRequest,
+    results: ResultStore,
+    cancelled: AtomicBool,
+}
+
+impl PendingReport {
+    fn new(id: NumberOrString, request: PullRequest, results: ResultStore) -> Self {
+        Self {
+            id,
+            request,
+            results,
+            cancelled: AtomicBool::new(false),
+        }
     }
 
-    pub fn shutdown(&self, id: i64) -> Result<(), LSError> {
-        send_stdout(&json!({
-            "jsonrpc": "2.0",
-            "id": id,
-            "result": null
-        }))?;
-        Ok(())
+    fn result(&self) -> Value {
+        match &self.request {
+            PullRequest::Document {
+                path,
+                previous_result_id,
+            } => json!(self
+                .results
+                .document_report(path, previous_result_id.as_deref())),
+            PullRequest::Workspace {
+                paths,
+                previous_result_ids,
+            } => json!(WorkspaceDiagnosticReport {
+                items: paths
+                    .iter()
+                    .filter_map(|path| {
+                        self.results.workspace_report(
+                            path,
+                            previous_result_ids.get(path).map(String::as_str),
+                        )
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl Drop for PendingReport {
+    fn drop(&mut self) {
+        let response = if self.cancelled.load(Ordering::Relaxed) {
+            json!({
+                "jsonrpc": "2.0",
+                "id": self.id,
+                "error": {
+                    "code": -32800, // Request cancelled
+                    "message": "request cancelled",
+                },
+            })
+        } else {
+            json!({
+                "jsonrpc": "2.0",
+                "id": self.id,
+                "result": self.result(),
+            })
+        };
+        if let Err(err) = send_stdout(&response) {
+            tracing::error!("Failed to send diagnostic report: {:?}", err);
+        }
     }
 }
 
@@ -486,22 +2389,24 @@ impl TestingLS {
 mod tests {
     use lsp_types::{Url, WorkspaceFolder};
     use std::collections::HashMap;
+    use testing_language_server::util::set_thread_sink;
 
     use super::*;
 
     #[test]
     fn test_check_file() {
+        capture_messages();
         let abs_path_of_demo = std::env::current_dir().unwrap().join("demo/rust");
         let mut server = TestingLS {
-            workspace_folders: Some(vec![WorkspaceFolder {
-                uri: Url::from_file_path(&abs_path_of_demo).unwrap(),
-                name: "demo".to_string(),
-            }]),
-            options: InitializedOptions {
-                adapter_command: HashMap::new(),
-                enable_workspace_diagnostics: Some(true),
-            },
-            workspaces_cache: Vec::new(),
+            folders: vec![ProjectFolder::new(
+                abs_path_of_demo.clone(),
+                InitializedOptions {
+                    adapter_command: HashMap::new(),
+                    enable_workspace_diagnostics: Some(true),
+                    ..Default::default()
+                },
+            )],
+            ..TestingLS::new()
         };
         let librs = abs_path_of_demo.join("lib.rs");
         server.check_file(librs.to_str().unwrap(), true).unwrap();
@@ -509,6 +2414,7 @@ mod tests {
 
     #[test]
     fn test_check_workspace() {
+        capture_messages();
         let abs_path_of_demo = std::env::current_dir().unwrap().join("demo/rust");
         let abs_path_of_rust_adapter = std::env::current_dir()
             .unwrap()
@@ -523,48 +2429,46 @@ mod tests {
             ..Default::default()
         };
         let mut server = TestingLS {
-            workspace_folders: Some(vec![WorkspaceFolder {
-                uri: Url::from_file_path(&abs_path_of_demo).unwrap(),
-                name: "demo".to_string(),
-            }]),
-            options: InitializedOptions {
-                adapter_command: HashMap::from([(String::from(".rs"), adapter_conf)]),
-                enable_workspace_diagnostics: Some(true),
-            },
-            workspaces_cache: Vec::new(),
+            folders: vec![ProjectFolder::new(
+                abs_path_of_demo.clone(),
+                InitializedOptions {
+                    adapter_command: HashMap::from([(String::from(".rs"), adapter_conf)]),
+                    enable_workspace_diagnostics: Some(true),
+                    ..Default::default()
+                },
+            )],
+            ..TestingLS::new()
         };
         server.diagnose_workspace().unwrap();
-        server
-            .workspaces_cache
-            .iter()
-            .for_each(|workspace_analysis| {
-                let adapter_command_path = workspace_analysis.adapter_config.path.clone();
-                assert!(adapter_command_path.contains("target/debug/testing-ls-adapter"));
-                workspace_analysis
-                    .workspaces
-                    .data
-                    .iter()
-                    .for_each(|(workspace, paths)| {
-                        assert_eq!(workspace, abs_path_of_demo.to_str().unwrap());
-                        paths.iter().for_each(|path| {
-                            assert!(path.contains("rust/src"));
-                        });
+        server.workspaces_cache().for_each(|workspace_analysis| {
+            let adapter_command_path = workspace_analysis.adapter_config.path.clone();
+            assert!(adapter_command_path.contains("target/debug/testing-ls-adapter"));
+            workspace_analysis
+                .workspaces
+                .data
+                .iter()
+                .for_each(|(workspace, paths)| {
+                    assert_eq!(workspace, abs_path_of_demo.to_str().unwrap());
+                    paths.iter().for_each(|path| {
+                        assert!(path.contains("rust/src"));
                     });
-            });
+                });
+        });
     }
 
     #[test]
     fn project_files_are_filtered_by_extension() {
         let absolute_path_of_demo = std::env::current_dir().unwrap().join("demo");
-        let files = TestingLS::project_files(
-            &absolute_path_of_demo.clone(),
+        let index = FileIndex::new(&absolute_path_of_demo);
+        let files = index.files(
+            &absolute_path_of_demo,
             &["/rust/src/lib.rs".to_string()],
             &["/rust/target/**/*".to_string()],
         );
         let librs = absolute_path_of_demo.join("rust/src/lib.rs");
         assert_eq!(files, vec![librs.to_str().unwrap()]);
-        let files = TestingLS::project_files(
-            &absolute_path_of_demo.clone(),
+        let files = index.files(
+            &absolute_path_of_demo,
             &["jest/*.spec.js".to_string()],
             &["jest/another.spec.js".to_string()],
         );
@@ -572,20 +2476,622 @@ mod tests {
         assert_eq!(files, vec![test_file.to_str().unwrap()]);
     }
 
+    #[test]
+    fn test_at_a_position_is_the_innermost_containing_it() {
+        let test_item = |id: &str, start: Position, end: Position| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: "/demo/cases_test.go".to_string(),
+            start_position: Range::new(start, start),
+            end_position: Range::new(end, end),
+        };
+        let discovered = DiscoverResult {
+            data: vec![FoundFileTests {
+                path: "/demo/cases_test.go".to_string(),
+                tests: vec![
+                    test_item("TestA", Position::new(0, 0), Position::new(2, 1)),
+                    test_item("TestB", Position::new(2, 3), Position::new(8, 1)),
+                    test_item("\"sub\"", Position::new(4, 2), Position::new(6, 4)),
+                ],
+                namespaces: vec![],
+            }],
+        };
+        let id_at = |line: u32, character: u32| {
+            TestingLS::find_test_at(&discovered, Position::new(line, character))
+                .map(|test_item| test_item.id.as_str())
+        };
+        assert_eq!(id_at(2, 0), Some("TestA"));
+        assert_eq!(id_at(2, 5), Some("TestB"));
+        assert_eq!(id_at(5, 0), Some("\"sub\""));
+        assert_eq!(id_at(6, 8), Some("TestB"));
+        assert_eq!(id_at(9, 0), None);
+    }
+
+    #[test]
+    fn discovered_tests_are_forgotten_when_files_change() {
+        capture_messages();
+        let mut server = TestingLS::new();
+        for path in ["/demo/src/lib.rs", "/demo/src/main.rs", "/demo/tests/it.rs"] {
+            let discovered = DiscoveredFile {
+                tests: DiscoverResult { data: vec![] },
+                line_lengths: vec![],
+            };
+            server
+                .discovered
+                .insert(path.to_string(), Arc::new(discovered));
+        }
+        server.forget_discovered(Path::new("/demo/src"));
+        assert_eq!(
+            server.discovered.keys().collect::<Vec<_>>(),
+            vec!["/demo/tests/it.rs"]
+        );
+        // Cached tests are returned without running the adapters
+        assert!(server.discovered_tests("/demo/tests/it.rs").is_ok());
+        server.refresh_workspaces_cache().unwrap();
+        assert!(server.discovered.is_empty());
+    }
+
+    #[test]
+    fn inlay_hints_are_refreshed_when_the_client_supports_it() {
+        let mut server = TestingLS::new();
+        assert!(!server.inlay_hint_refresh_support());
+        server.client_capabilities = serde_json::from_value(json!({
+            "workspace": { "inlayHint": { "refreshSupport": true } }
+        }))
+        .unwrap();
+        assert!(server.inlay_hint_refresh_support());
+    }
+
+    #[test]
+    fn code_lenses_for_tests_namespaces_and_file() {
+        let test_item = |id: &str, line: u32| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: "/demo/src/lib.rs".to_string(),
+            start_position: Range::new(Position::new(line, 4), Position::new(line, 10000)),
+            end_position: Range::new(Position::new(line + 2, 0), Position::new(line + 2, 5)),
+        };
+        let tests = [
+            test_item("tests::success", 8),
+            test_item("tests::nested::fail", 20),
+        ];
+        let tests = tests.iter().collect::<Vec<_>>();
+        let namespaces = [test_item("tests", 5)];
+        let namespaces = namespaces.iter().collect::<Vec<_>>();
+        let uri = "file:///demo/src/lib.rs";
+        let server = TestingLS::new();
+        let lenses = TestingLS::build_code_lenses(uri, &tests, &namespaces)
+            .into_iter()
+            .map(|lens| server.resolve_code_lens(lens).unwrap())
+            .collect::<Vec<_>>();
+        let summary = lenses
+            .iter()
+            .map(|lens| {
+                let command = lens.command.as_ref().unwrap();
+                let args: RunCommandArgs =
+                    serde_json::from_value(command.arguments.as_ref().unwrap()[0].clone()).unwrap();
+                assert_eq!(args.uri, uri);
+                (
+                    lens.range.start.line,
+                    command.title.as_str(),
+                    command.command.as_str(),
+                    args.test_ids,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (0, "▶ Run file", RUN_FILE_COMMAND, vec![]),
+                (
+                    5,
+                    "▶ Run namespace",
+                    RUN_TEST_COMMAND,
+                    vec![
+                        "tests::success".to_string(),
+                        "tests::nested::fail".to_string()
+                    ]
+                ),
+                (
+                    20,
+                    "▶ Run namespace",
+                    RUN_TEST_COMMAND,
+                    vec!["tests::nested::fail".to_string()]
+                ),
+                (
+                    8,
+                    "▶ Run",
+                    RUN_TEST_COMMAND,
+                    vec!["tests::success".to_string()]
+                ),
+                (
+                    20,
+                    "▶ Run",
+                    RUN_TEST_COMMAND,
+                    vec!["tests::nested::fail".to_string()]
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn skip_workspace_diagnostics() {
+        capture_messages();
         let mut server = TestingLS {
-            workspace_folders: Some(vec![WorkspaceFolder {
-                uri: Url::from_file_path(current_dir().unwrap()).unwrap(),
-                name: "demo".to_string(),
-            }]),
-            options: InitializedOptions {
-                adapter_command: HashMap::new(),
-                enable_workspace_diagnostics: Some(false),
-            },
-            workspaces_cache: Vec::new(),
+            folders: vec![ProjectFolder::new(
+                current_dir().unwrap(),
+                InitializedOptions {
+                    adapter_command: HashMap::new(),
+                    enable_workspace_diagnostics: Some(false),
+                    ..Default::default()
+                },
+            )],
+            ..TestingLS::new()
         };
         let status = server.diagnose_workspace().unwrap();
         assert_eq!(status, WorkspaceDiagnosticsStatus::Skipped);
     }
+
+    /// Writes a shell script to be used as an adapter
+    #[cfg(unix)]
+    fn write_adapter(name: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("testing-ls-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let adapter_path = dir.join("adapter.sh");
+        std::fs::write(&adapter_path, script).unwrap();
+        std::fs::set_permissions(&adapter_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    impl TestRun {
+        /// A run of `/demo/lib.rs` by the shell script `adapter`, in the directory it is written to.
+        /// The test removes the directory.
+        fn for_test(name: &str, adapter: &str) -> Self {
+            let dir = write_adapter(name, adapter);
+            let adapter_path = dir.join("adapter.sh");
+            TestRun {
+                adapter_id: "fake".to_string(),
+                adapter: AdapterConfiguration {
+                    path: adapter_path.to_str().unwrap().to_string(),
+                    ..Default::default()
+                },
+                workspace_folder: dir.clone(),
+                workspace: dir.to_str().unwrap().to_string(),
+                paths: vec!["/demo/lib.rs".to_string()],
+                test_ids: vec![],
+                processes: ProcessRegistry::default(),
+                results: ResultStore::default(),
+                request_id: None,
+                report: None,
+                junit_export: None,
+                log_requests: LogRequests::default(),
+                refresh_inlay_hints: false,
+                test_ranges: vec![],
+            }
+        }
+    }
+
+    /// Collects the messages sent by the current thread instead of writing them to stdout
+    fn capture_messages() -> Arc<Mutex<Vec<Value>>> {
+        let sent = Arc::new(Mutex::new(vec![]));
+        {
+            let sent = Arc::clone(&sent);
+            set_thread_sink(Some(Arc::new(move |message| {
+                sent.lock().unwrap().push(message)
+            })));
+        }
+        sent
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streamed_events_are_followed_by_final_result() {
+        capture_messages();
+        // The final result is pretty printed, like adapters which do not stream write it
+        let run = TestRun::for_test(
+            "stream",
+            r#"#!/bin/sh
+echo '{"event":"plan","total":2}'
+echo '{"event":"testStarted","id":"first","path":"/demo/lib.rs"}'
+echo '{"event":"testFinished","id":"first","path":"/demo/lib.rs","status":"passed"}'
+echo '{"event":"log","message":"running second"}'
+echo '{"event":"testFinished","id":"second","path":"/demo/lib.rs","status":"failed"}'
+echo '{"event":"fileDiagnostics","path":"/demo/lib.rs","diagnostics":[{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":1}},"message":"streamed"}]}'
+echo '{'
+echo '  "data": [{"path":"/demo/lib.rs","diagnostics":[{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":1}},"message":"final"}]}]'
+echo '}'
+"#,
+        );
+        let diagnostics = run
+            .get_diagnostics(&NumberOrString::String("token".to_string()))
+            .unwrap();
+        std::fs::remove_dir_all(&run.workspace_folder).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, "file:///demo/lib.rs");
+        let messages = diagnostics[0]
+            .1
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["final"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rerun_tests_keep_the_failures_of_the_others() {
+        let sent = capture_messages();
+        let adapter = r#"#!/bin/sh
+failure() {
+  echo "{\"range\":{\"start\":{\"line\":$1,\"character\":4},\"end\":{\"line\":$1,\"character\":9}},\"message\":\"$2\"}"
+}
+case "$*" in
+  *--test-ids*) echo "{\"data\":[{\"path\":\"/demo/lib.rs\",\"diagnostics\":[$(failure 6 'b failed again')]}]}" ;;
+  *) echo "{\"data\":[{\"path\":\"/demo/lib.rs\",\"diagnostics\":[$(failure 2 'a failed'),$(failure 6 'b failed')]}]}" ;;
+esac
+"#;
+        let run = TestRun::for_test("rerun", adapter);
+        let results = run.results.clone();
+        run.diagnose();
+        let tested_at = results.get("/demo/lib.rs").unwrap().tested_at;
+
+        let run = TestRun {
+            results: results.clone(),
+            test_ids: vec!["b".to_string()],
+            test_ranges: vec![Range::new(Position::new(5, 0), Position::new(8, 1))],
+            ..TestRun::for_test("rerun", adapter)
+        };
+        let dir = run.workspace_folder.clone();
+        run.diagnose();
+        std::fs::remove_dir_all(dir).unwrap();
+        let file_result = results.get("/demo/lib.rs").unwrap();
+        let messages = |diagnostics: &[Diagnostic]| {
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages(&file_result.diagnostics),
+            vec!["a failed", "b failed again"]
+        );
+        assert_eq!(file_result.tested_at, tested_at);
+        let sent = sent.lock().unwrap();
+        let published = sent
+            .iter()
+            .rfind(|message| message["method"] == "textDocument/publishDiagnostics")
+            .unwrap();
+        let diagnostics: Vec<Diagnostic> =
+            serde_json::from_value(published["params"]["diagnostics"].clone()).unwrap();
+        assert_eq!(messages(&diagnostics), vec!["a failed", "b failed again"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timed_out_tests_get_diagnostics() {
+        capture_messages();
+        let mut run = TestRun::for_test(
+            "timeout",
+            r#"#!/bin/sh
+if [ "$1" = discover ]; then
+  echo '{"data":[{"path":"/demo/lib.rs","tests":[
+    {"id":"fast","name":"fast","path":"/demo/lib.rs","start_position":{"start":{"line":1,"character":0},"end":{"line":1,"character":1}},"end_position":{"start":{"line":2,"character":0},"end":{"line":2,"character":1}}},
+    {"id":"hang","name":"hang","path":"/demo/lib.rs","start_position":{"start":{"line":5,"character":0},"end":{"line":5,"character":1}},"end_position":{"start":{"line":8,"character":0},"end":{"line":8,"character":1}}}
+  ]}]}'
+  exit
+fi
+echo '{"event":"testStarted","id":"fast","path":"/demo/lib.rs"}'
+echo '{"event":"testFinished","id":"fast","path":"/demo/lib.rs","status":"passed"}'
+echo '{"event":"testStarted","id":"hang","path":"/demo/lib.rs"}'
+sleep 30
+"#,
+        );
+        run.adapter.timeout = Some(1);
+        let diagnostics = run
+            .get_diagnostics(&NumberOrString::String("token".to_string()))
+            .unwrap();
+        std::fs::remove_dir_all(&run.workspace_folder).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        let summary = diagnostics[0]
+            .1
+            .iter()
+            .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![(5, "`hang` did not finish within 1s")]);
+    }
+
+    #[test]
+    fn progress_percentage_of_finished_tests() {
+        let mut stream = EventStream::default();
+        assert_eq!(stream.percentage(), None);
+        stream.total = Some(4);
+        stream.finished = 1;
+        assert_eq!(stream.percentage(), Some(25));
+        // Adapters may report more tests than planned, e.g. subtests
+        stream.finished = 5;
+        assert_eq!(stream.percentage(), Some(100));
+    }
+
+    #[test]
+    fn test_tree_nests_tests_in_namespaces() {
+        let item = |id: &str, start: u32, end: u32| TestItem {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: "/demo/src/lib.rs".to_string(),
+            start_position: Range::new(Position::new(start, 4), Position::new(start, 10000)),
+            end_position: Range::new(Position::new(end, 0), Position::new(end, 5)),
+        };
+        let found = FoundFileTests {
+            path: "/demo/src/lib.rs".to_string(),
+            tests: vec![
+                item("tests::success", 8, 10),
+                item("tests::nested::fail", 20, 22),
+                item("top_level", 30, 32),
+            ],
+            // `tests::nested` is not reported
+            namespaces: vec![item("tests", 5, 25)],
+        };
+        fn summary(item: &TestTreeItem) -> (String, TestItemKind, u32, u32, Vec<String>) {
+            (
+                item.name.clone(),
+                item.kind,
+                item.range.start.line,
+                item.range.end.line,
+                item.children.iter().map(|child| child.id.clone()).collect(),
+            )
+        }
+        let file = TestingLS::build_test_tree(&found);
+        assert_eq!(
+            summary(&file),
+            (
+                "lib.rs".to_string(),
+                TestItemKind::File,
+                0,
+                0,
+                vec!["tests".to_string(), "top_level".to_string()]
+            )
+        );
+        let tests = &file.children[0];
+        assert_eq!(
+            summary(tests),
+            (
+                "tests".to_string(),
+                TestItemKind::Namespace,
+                5,
+                25,
+                vec!["tests::success".to_string(), "tests::nested".to_string()]
+            )
+        );
+        assert_eq!(
+            summary(&tests.children[1]),
+            (
+                "nested".to_string(),
+                TestItemKind::Namespace,
+                20,
+                22,
+                vec!["tests::nested::fail".to_string()]
+            )
+        );
+        assert_eq!(
+            summary(&tests.children[1].children[0]),
+            ("fail".to_string(), TestItemKind::Test, 20, 22, vec![])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_events_in_ignored_directories_are_dropped() {
+        let sent = capture_messages();
+        let dir = write_adapter(
+            "watch",
+            r#"#!/bin/sh
+echo "{\"data\":{\"$(dirname "$3")\":[\"$3\"]}}"
+"#,
+        );
+        std::fs::write(dir.join(".gitignore"), "/target\n").unwrap();
+        std::fs::write(
+            dir.join(TOML_FILE_NAME),
+            format!(
+                r#"[adapterCommand.a]
+path = "{}"
+include = ["/**/*.a"]
+exclude = []
+"#,
+                dir.join("adapter.sh").display()
+            ),
+        )
+        .unwrap();
+        let mut server = TestingLS::new();
+        server
+            .change_workspace_folders(DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![WorkspaceFolder {
+                        uri: Url::from_file_path(&dir).unwrap(),
+                        name: "watch".to_string(),
+                    }],
+                    removed: vec![],
+                },
+            })
+            .unwrap();
+        let detected = || {
+            sent.lock()
+                .unwrap()
+                .iter()
+                .filter(|message| message["method"] == "$/detectedWorkspace")
+                .count()
+        };
+        let before = detected();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("target/built.a"), "").unwrap();
+        server
+            .files_changed(&[dir.join("target/built.a")], &[dir.join("target/old.a")])
+            .unwrap();
+        assert_eq!(detected(), before);
+        std::fs::write(dir.join("test.a"), "").unwrap();
+        server.files_changed(&[dir.join("test.a")], &[]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(detected(), before + 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_folders_are_configured_independently() {
+        capture_messages();
+        // `detect-workspace --file-paths <path> -- ...` puts the file in its directory
+        let adapter_dir = write_adapter(
+            "folders",
+            r#"#!/bin/sh
+echo "{\"data\":{\"$(dirname "$3")\":[\"$3\"]}}"
+"#,
+        );
+        let adapter_path = adapter_dir.join("adapter.sh");
+        let folder = |name: &str, extension: &str| {
+            let dir = adapter_dir.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("test.a"), "").unwrap();
+            std::fs::write(dir.join("test.b"), "").unwrap();
+            std::fs::write(
+                dir.join(TOML_FILE_NAME),
+                format!(
+                    r#"enableWorkspaceDiagnostics = false
+
+[adapterCommand.{extension}]
+path = "{}"
+include = ["/*.{extension}"]
+exclude = []
+"#,
+                    adapter_path.display()
+                ),
+            )
+            .unwrap();
+            WorkspaceFolder {
+                uri: Url::from_file_path(&dir).unwrap(),
+                name: name.to_string(),
+            }
+        };
+        let (first, second) = (folder("first", "a"), folder("second", "b"));
+        let path = |folder: &WorkspaceFolder, file: &str| {
+            folder
+                .uri
+                .to_file_path()
+                .unwrap()
+                .join(file)
+                .display()
+                .to_string()
+        };
+
+        let mut server = TestingLS::new();
+        server
+            .change_workspace_folders(DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![first.clone(), second.clone()],
+                    removed: vec![],
+                },
+            })
+            .unwrap();
+        assert_eq!(
+            server.file_adapters(),
+            HashMap::from([
+                (path(&first, "test.a"), "a".to_string()),
+                (path(&second, "test.b"), "b".to_string()),
+            ])
+        );
+
+        server
+            .change_workspace_folders(DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![],
+                    removed: vec![first],
+                },
+            })
+            .unwrap();
+        std::fs::remove_dir_all(&adapter_dir).unwrap();
+        assert_eq!(
+            server.file_adapters(),
+            HashMap::from([(path(&second, "test.b"), "b".to_string())])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn logs_of_a_failed_run_are_offered() {
+        let mut run = TestRun::for_test(
+            "failed",
+            r#"#!/bin/sh
+echo "warning: deprecated" >&2
+echo '{"event":"logWritten","path":"/demo/adapter.log"}'
+[ "$FAIL" = 1 ] && exit 1
+echo '{"data":[]}'
+"#,
+        );
+        let sent = capture_messages();
+        let mut log_requests_of = |fail: &str| {
+            run.adapter.env = HashMap::from([("FAIL".to_string(), fail.to_string())]);
+            run.get_diagnostics(&NumberOrString::String("token".to_string()))
+                .unwrap();
+            let log_requests = run.log_requests.lock().unwrap().clone();
+            log_requests
+        };
+        // Only stderr is not a failure
+        assert!(log_requests_of("0").is_empty());
+        let log_requests = log_requests_of("1");
+        set_thread_sink(None);
+        std::fs::remove_dir_all(&run.workspace_folder).unwrap();
+        assert_eq!(
+            log_requests.into_values().collect::<Vec<_>>(),
+            vec![vec![PathBuf::from("/demo/adapter.log")]]
+        );
+        let sent = sent.lock().unwrap();
+        let popups = sent
+            .iter()
+            .filter(|message| message["method"] == "window/showMessageRequest")
+            .count();
+        assert_eq!(popups, 1);
+
+        // Dismissing the message forgets the logs
+        let server = TestingLS::new();
+        server.log_requests.lock().unwrap().insert(
+            "testing-ls/openAdapterLog/0".to_string(),
+            vec![PathBuf::from("/demo/adapter.log")],
+        );
+        server
+            .handle_response("testing-ls/openAdapterLog/0", &Value::Null)
+            .unwrap();
+        assert!(server.log_requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn files_of_removed_and_changed_adapters_are_stale() {
+        let adapter = |kind: &str| AdapterConfiguration {
+            path: "testing-ls-adapter".to_string(),
+            extra_arg: vec![format!("--test-kind={kind}")],
+            include: vec!["/**/*".to_string()],
+            ..Default::default()
+        };
+        let analysis = |adapter: AdapterConfiguration, paths: &[&str]| {
+            WorkspaceAnalysis::new(
+                adapter,
+                DetectWorkspaceResult {
+                    data: HashMap::from([(
+                        "/demo".to_string(),
+                        paths.iter().map(|path| path.to_string()).collect(),
+                    )]),
+                },
+            )
+        };
+        let old_cache = [
+            analysis(adapter("cargo-test"), &["/demo/lib.rs"]),
+            analysis(adapter("jest"), &["/demo/index.spec.js"]),
+            analysis(adapter("go-test"), &["/demo/main_test.go"]),
+        ];
+        let mut changed = adapter("go-test");
+        changed.exclude = vec!["/**/*_test.go".to_string()];
+        let adapters = [adapter("cargo-test"), changed];
+        assert_eq!(
+            TestingLS::stale_files(&old_cache, &adapters),
+            vec!["/demo/index.spec.js", "/demo/main_test.go"]
+        );
+    }
 }
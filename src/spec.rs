@@ -10,6 +10,7 @@ use std::collections::HashMap;
 pub enum AdapterCommands {
     Discover(DiscoverArgs),
     RunFileTest(RunFileTestArgs),
+    RunTest(RunTestArgs),
     DetectWorkspace(DetectWorkspaceArgs),
 }
 
@@ -37,6 +38,24 @@ pub struct RunFileTestArgs {
     pub extra: Vec<String>,
 }
 
+/// Arguments for `<adapter command> run-test` command
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+pub struct RunTestArgs {
+    #[arg(short, long)]
+    pub file_paths: Vec<String>,
+
+    #[arg(short, long)]
+    pub workspace: String,
+
+    /// Ids of the tests to run, as returned by `discover`
+    #[arg(short, long)]
+    pub test_ids: Vec<String>,
+
+    #[arg(last = true)]
+    pub extra: Vec<String>,
+}
+
 /// Arguments for `<adapter command> detect-workspace` command
 #[derive(clap::Args, Debug)]
 #[command(version, about, long_about = None)]
@@ -66,7 +85,7 @@ impl WorkspaceAnalysis {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+#[derive(Debug, Deserialize, Clone, Serialize, Default, PartialEq)]
 pub struct AdapterConfiguration {
     pub path: String,
     #[serde(default)]
@@ -76,6 +95,9 @@ pub struct AdapterConfiguration {
     pub include: Vec<String>,
     pub exclude: Vec<String>,
     pub workspace_dir: Option<String>,
+    /// Seconds after which a test run is stopped
+    #[serde(default)]
+    pub timeout: Option<u64>,
 }
 
 /// Result of `<adapter command> detect-workspace`
@@ -90,12 +112,74 @@ pub struct FileDiagnostics {
     pub diagnostics: Vec<Diagnostic>,
 }
 
-/// Result of `<adapter command> run-file-test`
+/// Outcome of a single test
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+    /// The test could not finish, e.g. it timed out or crashed
+    Errored,
+}
+
+/// Result of a single test which ran
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct TestResult {
+    /// Same as `TestItem.id`
+    pub id: String,
+    pub path: String,
+    pub status: TestStatus,
+    /// Milliseconds, when the test tool reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    /// Failure message or captured output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// Result of `<adapter command> run-file-test` and `<adapter command> run-test`
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub struct RunFileTestResult {
     pub data: Vec<FileDiagnostics>,
     #[serde(default)]
     pub messages: Vec<ShowMessageParams>,
+    /// Every test which ran, including passed and skipped ones
+    #[serde(default)]
+    pub tests: Vec<TestResult>,
+}
+
+/// Directory below the home directory, where adapters write the output of their last run
+pub const ADAPTER_LOG_DIR: &str = ".config/testing_language_server/adapter/";
+
+/// Set to `1` in the environment of `run-file-test` and `run-test`
+/// when the server reads the adapter stdout line by line as a stream of `AdapterEvent`s.
+pub const STREAM_EVENTS_ENV: &str = "TESTING_LS_STREAM_EVENTS";
+
+/// A line written to stdout by an adapter while the tests run.
+/// The `RunFileTestResult` written after the events is still the final result.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum AdapterEvent {
+    /// Number of tests which are going to run
+    Plan {
+        total: usize,
+    },
+    TestStarted {
+        id: String,
+        path: String,
+    },
+    TestFinished(TestResult),
+    /// Diagnostics to add to the ones already streamed for the file
+    FileDiagnostics(FileDiagnostics),
+    Log {
+        message: String,
+    },
+    /// The adapter wrote the output of the test tool to the file at `path`,
+    /// which the user is offered to open when the run fails
+    LogWritten {
+        path: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -115,6 +199,32 @@ pub struct TestItem {
 pub struct FoundFileTests {
     pub path: String,
     pub tests: Vec<TestItem>,
+    /// `mod`s, `describe`s and classes which contain the tests.
+    /// The id of a namespace is the `::` separated prefix of the ids of the tests in it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub namespaces: Vec<TestItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum TestItemKind {
+    File,
+    Namespace,
+    Test,
+}
+
+/// A node of the test tree returned by `$/discoverWorkspaceTests`
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct TestTreeItem {
+    /// The path for files, otherwise same as `TestItem.id`
+    pub id: String,
+    pub name: String,
+    pub kind: TestItemKind,
+    pub path: String,
+    /// Whole range of a test, or of the enclosing `mod`, `describe` or class of a namespace
+    pub range: Range,
+    #[serde(default)]
+    pub children: Vec<TestTreeItem>,
 }
 
 /// Result of `<adapter command> discover`
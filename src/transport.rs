@@ -0,0 +1,353 @@
+use serde_json::Value;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use testing_language_server::error::LSError;
+use testing_language_server::util::send_unscoped;
+use testing_language_server::util::write_message;
+use testing_language_server::util::MessageSink;
+
+/// Where the server is reached by the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Stdio,
+    /// `--listen tcp:PORT`, on the loopback interface
+    Tcp(u16),
+    /// `--socket PATH`, a Unix domain socket
+    Socket(PathBuf),
+}
+
+/// Parses the value of `--listen`
+pub fn parse_listen(value: &str) -> Result<u16, String> {
+    let port = value
+        .strip_prefix("tcp:")
+        .ok_or(format!("expected tcp:PORT, got {value:?}"))?;
+    port.parse()
+        .map_err(|err| format!("invalid port {port:?}: {err}"))
+}
+
+/// Reads the next message, which is an array for a batch.
+/// Returns `None` when the input is closed.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>, LSError> {
+    let mut size = None;
+    loop {
+        let mut buffer = String::new();
+        if reader.read_line(&mut buffer)? == 0 {
+            return Ok(None);
+        }
+        let line = buffer.trim_end_matches(['\r', '\n']);
+        // The end of header section
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            tracing::warn!("Invalid header: {:?}", line);
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_lowercase().as_str() {
+            "content-length" => {
+                let length = value.parse::<usize>().map_err(|err| {
+                    LSError::Any(anyhow::anyhow!("Invalid Content-Length {:?}: {err}", value))
+                })?;
+                size = Some(length);
+            }
+            "content-type" => {
+                // `utf8` is accepted for backwards compatibility, like the LSP specification says
+                let charset = value
+                    .split(';')
+                    .filter_map(|parameter| parameter.trim().strip_prefix("charset="))
+                    .next();
+                if charset.is_some_and(|charset| {
+                    !charset.eq_ignore_ascii_case("utf-8") && !charset.eq_ignore_ascii_case("utf8")
+                }) {
+                    tracing::warn!("Unsupported Content-Type {:?}, reading utf-8", value);
+                }
+            }
+            _ => tracing::warn!("Unknown header: {:?}", line),
+        }
+    }
+    let size = size.ok_or(LSError::Any(anyhow::anyhow!(
+        "The Content-Length header is missing"
+    )))?;
+    let mut buf = vec![0u8; size];
+    reader.read_exact(&mut buf)?;
+    Ok(Some(serde_json::from_slice(&buf)?))
+}
+
+/// Sends `message` to `sink`, or where `send_stdout` writes when there is none
+fn forward(sink: Option<&MessageSink>, message: Value) {
+    match sink {
+        Some(sink) => sink(message),
+        None => {
+            if let Err(err) = send_unscoped(&message) {
+                tracing::error!("Failed to send message: {:?}", err);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    /// Ids of the requests which are not answered yet
+    pending: Vec<Value>,
+    responses: Vec<Value>,
+}
+
+/// Holds back the responses to the requests of a batch and sends them as one array
+/// to `outer` when the last one is answered. Other messages go to `outer` as they are sent.
+#[derive(Clone)]
+pub struct BatchSink {
+    batch: Arc<Mutex<Batch>>,
+    outer: Option<MessageSink>,
+}
+
+impl BatchSink {
+    /// `ids` are the ids of the requests of the batch
+    pub fn new(ids: Vec<Value>, outer: Option<MessageSink>) -> Self {
+        Self {
+            batch: Arc::new(Mutex::new(Batch {
+                pending: ids,
+                responses: vec![],
+            })),
+            outer,
+        }
+    }
+
+    /// Sink which collects the responses, for `set_thread_sink`
+    pub fn sink(&self) -> MessageSink {
+        let this = self.clone();
+        Arc::new(move |message: Value| {
+            let mut batch = this.batch.lock().unwrap();
+            let index = batch
+                .pending
+                .iter()
+                .position(|id| message.get("method").is_none() && message["id"] == *id);
+            let Some(index) = index else {
+                drop(batch);
+                return forward(this.outer.as_ref(), message);
+            };
+            batch.pending.swap_remove(index);
+            batch.responses.push(message);
+            if batch.pending.is_empty() {
+                drop(batch);
+                this.flush();
+            }
+        })
+    }
+
+    /// Sends the responses collected so far, e.g. when the rest of the batch is not handled.
+    /// The requests which are answered later get a response of their own.
+    pub fn flush(&self) {
+        let mut batch = self.batch.lock().unwrap();
+        batch.pending.clear();
+        let responses = std::mem::take(&mut batch.responses);
+        drop(batch);
+        if !responses.is_empty() {
+            forward(self.outer.as_ref(), Value::Array(responses));
+        }
+    }
+}
+
+/// Writes the messages of the server to a client connected over a socket
+#[derive(Clone)]
+pub struct ClientWriter(Arc<Mutex<Option<Box<dyn Write + Send>>>>);
+
+impl ClientWriter {
+    fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Some(Box::new(writer)))))
+    }
+
+    /// Sink which writes to the client, for `set_thread_sink`
+    pub fn sink(&self) -> MessageSink {
+        let writer = Arc::clone(&self.0);
+        Arc::new(move |message: Value| {
+            let mut writer = writer.lock().unwrap();
+            let Some(stream) = writer.as_mut() else {
+                tracing::warn!("The client is disconnected, dropped {:?}", message);
+                return;
+            };
+            if let Err(err) = write_message(stream, &message.to_string()) {
+                tracing::error!("Failed to send message: {:?}", err);
+            }
+        })
+    }
+
+    /// Drops the messages sent from now on, e.g. by runs which outlive the connection
+    pub fn close(&self) {
+        self.0.lock().unwrap().take();
+    }
+}
+
+/// A client connected over a socket
+pub struct Connection {
+    pub reader: Box<dyn BufRead + Send>,
+    pub writer: ClientWriter,
+}
+
+/// Accepts the clients of `--listen` and `--socket`, one after the other
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Socket(std::os::unix::net::UnixListener, PathBuf),
+}
+
+impl Listener {
+    pub fn bind(endpoint: &Endpoint) -> Result<Self, LSError> {
+        let listener = match endpoint {
+            Endpoint::Stdio => {
+                return Err(LSError::Any(anyhow::anyhow!("stdio is not a socket")));
+            }
+            Endpoint::Tcp(port) => Self::Tcp(TcpListener::bind((Ipv4Addr::LOCALHOST, *port))?),
+            #[cfg(unix)]
+            Endpoint::Socket(path) => {
+                use std::os::unix::net::UnixListener;
+                use std::os::unix::net::UnixStream;
+                // A socket file which nobody listens on is left by a server which was killed
+                if path.exists() && UnixStream::connect(path).is_err() {
+                    std::fs::remove_file(path)?;
+                }
+                Self::Socket(UnixListener::bind(path)?, path.clone())
+            }
+            #[cfg(not(unix))]
+            Endpoint::Socket(_) => {
+                return Err(LSError::Any(anyhow::anyhow!(
+                    "--socket is only supported on Unix"
+                )));
+            }
+        };
+        Ok(listener)
+    }
+
+    /// Address to print, e.g. when the port is chosen by the system
+    pub fn address(&self) -> String {
+        match self {
+            Self::Tcp(listener) => listener
+                .local_addr()
+                .map_or("tcp".to_string(), |address| format!("tcp:{address}")),
+            #[cfg(unix)]
+            Self::Socket(_, path) => path.display().to_string(),
+        }
+    }
+
+    pub fn accept(&self) -> io::Result<Connection> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                Ok(Connection {
+                    reader: Box::new(BufReader::new(stream.try_clone()?)),
+                    writer: ClientWriter::new(stream),
+                })
+            }
+            #[cfg(unix)]
+            Self::Socket(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                Ok(Connection {
+                    reader: Box::new(BufReader::new(stream.try_clone()?)),
+                    writer: ClientWriter::new(stream),
+                })
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Socket(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn read_messages_and_batches() {
+        let body = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+        let mut input = format!(
+            "content-length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf8\r\n\r\n{body}",
+            body.len()
+        )
+        .into_bytes();
+        let batch = json!([{ "id": 1, "method": "shutdown" }, { "method": "exit" }]);
+        write_message(&mut input, &batch.to_string()).unwrap();
+        let mut reader = io::Cursor::new(input);
+        let message = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message["method"], "initialized");
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), batch);
+        assert!(read_message(&mut reader).unwrap().is_none());
+
+        for input in [
+            "Content-Type: application/json\r\n\r\n{}",
+            "Content-Length: two\r\n\r\n{}",
+            "Content-Length: 10\r\n\r\n{}",
+        ] {
+            assert!(read_message(&mut io::Cursor::new(input)).is_err());
+        }
+        assert!(matches!(
+            read_message(&mut io::Cursor::new("Content-Length: 1\r\n\r\n{")),
+            Err(LSError::Serialization(_))
+        ));
+    }
+
+    #[test]
+    fn responses_of_a_batch_are_sent_together() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let outer: MessageSink = {
+            let sent = Arc::clone(&sent);
+            Arc::new(move |message| sent.lock().unwrap().push(message))
+        };
+        let sink = BatchSink::new(vec![json!(1), json!("two")], Some(outer.clone())).sink();
+        sink(json!({ "id": "two", "result": null }));
+        sink(json!({ "method": "$/progress" }));
+        // A request of the server with the same id is not a response
+        sink(json!({ "id": 1, "method": "window/workDoneProgress/create" }));
+        assert_eq!(sent.lock().unwrap().len(), 2);
+        sink(json!({ "id": 1, "error": { "code": -32601 } }));
+        sink(json!({ "method": "window/logMessage" }));
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                json!({ "method": "$/progress" }),
+                json!({ "id": 1, "method": "window/workDoneProgress/create" }),
+                json!([
+                    { "id": "two", "result": null },
+                    { "id": 1, "error": { "code": -32601 } }
+                ]),
+                json!({ "method": "window/logMessage" }),
+            ]
+        );
+
+        // The responses collected when the batch stops early are sent, later ones on their own
+        sent.lock().unwrap().clear();
+        let batch = BatchSink::new(vec![json!(1), json!(2)], Some(outer));
+        let sink = batch.sink();
+        sink(json!({ "id": 1, "result": null }));
+        batch.flush();
+        sink(json!({ "id": 2, "result": null }));
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                json!([{ "id": 1, "result": null }]),
+                json!({ "id": 2, "result": null }),
+            ]
+        );
+    }
+
+    #[test]
+    fn listen_values() {
+        assert_eq!(parse_listen("tcp:9257"), Ok(9257));
+        assert!(parse_listen("9257").is_err());
+        assert!(parse_listen("tcp:port").is_err());
+    }
+}
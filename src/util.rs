@@ -1,48 +1,121 @@
 use crate::error::LSError;
 use chrono::NaiveDate;
 use chrono::Utc;
+use lsp_types::NumberOrString;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::json;
-use serde_json::Number;
 use serde_json::Value;
+use std::cell::RefCell;
 use std::fs;
+use std::io;
 use std::io::stdout;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Child;
+use std::process::Output;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::thread;
+
+type MessageHandler = Box<dyn Fn(Value) + Send + Sync>;
+
+static MESSAGE_HANDLER: OnceLock<MessageHandler> = OnceLock::new();
+
+/// Receives the messages of `send_stdout` on the threads it is set for, see `set_thread_sink`
+pub type MessageSink = Arc<dyn Fn(Value) + Send + Sync>;
+
+thread_local! {
+    static THREAD_SINK: RefCell<Option<MessageSink>> = const { RefCell::new(None) };
+}
+
+/// Passes the messages of `send_stdout` on the current thread to `sink`,
+/// e.g. the client connected over a socket. Returns the sink which was set before.
+pub fn set_thread_sink(sink: Option<MessageSink>) -> Option<MessageSink> {
+    THREAD_SINK.with(|current| current.replace(sink))
+}
+
+/// The sink of the current thread, which work handed over to other threads is meant to keep
+pub fn thread_sink() -> Option<MessageSink> {
+    THREAD_SINK.with(|current| current.borrow().clone())
+}
+
+/// Passes every message of `send_stdout` to `handler` instead of writing it, for the rest of the process.
+/// Used when the server runs without a client, e.g. to print the results in a terminal.
+pub fn set_message_handler(handler: impl Fn(Value) + Send + Sync + 'static) {
+    if MESSAGE_HANDLER.set(Box::new(handler)).is_err() {
+        tracing::error!("The message handler is already set");
+    }
+}
 
 pub fn send_stdout<T>(message: &T) -> Result<(), LSError>
 where
     T: ?Sized + Serialize + std::fmt::Debug,
 {
     tracing::info!("send stdout: {:#?}", message);
+    match thread_sink() {
+        Some(sink) => {
+            sink(serde_json::to_value(message)?);
+            Ok(())
+        }
+        None => send_unscoped(message),
+    }
+}
+
+/// Sends `message` to the message handler or stdout, ignoring the sink of the thread
+pub fn send_unscoped<T>(message: &T) -> Result<(), LSError>
+where
+    T: ?Sized + Serialize,
+{
+    if let Some(handler) = MESSAGE_HANDLER.get() {
+        handler(serde_json::to_value(message)?);
+        return Ok(());
+    }
     let msg = serde_json::to_string(message)?;
-    let mut stdout = stdout().lock();
-    write!(stdout, "Content-Length: {}\r\n\r\n{}", msg.len(), msg)?;
-    stdout.flush()?;
+    write_message(&mut stdout().lock(), &msg)?;
     Ok(())
 }
 
+/// Writes `message` with its `Content-Length` header
+pub fn write_message(writer: &mut impl Write, message: &str) -> io::Result<()> {
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        message.len(),
+        message
+    )?;
+    writer.flush()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorMessage {
     jsonrpc: String,
-    id: Option<Number>,
+    id: Option<NumberOrString>,
     pub error: Value,
 }
 
 impl ErrorMessage {
-    #[allow(dead_code)]
-    pub fn new<N: Into<Number>>(id: Option<N>, error: Value) -> Self {
+    pub fn new(id: Option<NumberOrString>, error: Value) -> Self {
         Self {
             jsonrpc: "2.0".into(),
-            id: id.map(|i| i.into()),
+            id,
             error,
         }
     }
 }
 
-pub fn send_error<S: Into<String>>(id: Option<i64>, code: i64, msg: S) -> Result<(), LSError> {
+/// Sends an error response. `id` is `None` when the id of the request could not be read.
+pub fn send_error<S: Into<String>>(
+    id: Option<NumberOrString>,
+    code: i64,
+    msg: S,
+) -> Result<(), LSError> {
     send_stdout(&ErrorMessage::new(
         id,
         json!({ "code": code, "message": msg.into() }),
@@ -76,6 +149,72 @@ pub fn resolve_path(base_dir: &Path, relative_path: &str) -> PathBuf {
     PathBuf::from_iter(components)
 }
 
+/// Which pipe of a child process a line was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Waits for `child` like `Child::wait_with_output`, but calls `on_line` with every line
+/// of its stdout and stderr as soon as it is written, without the line ending.
+/// The returned output still contains everything the child wrote.
+pub fn wait_with_lines(
+    mut child: Child,
+    mut on_line: impl FnMut(OutputStream, &str),
+) -> io::Result<Output> {
+    let (sender, receiver) = mpsc::channel();
+    if let Some(pipe) = child.stdout.take() {
+        spawn_line_reader(pipe, OutputStream::Stdout, sender.clone());
+    }
+    if let Some(pipe) = child.stderr.take() {
+        spawn_line_reader(pipe, OutputStream::Stderr, sender.clone());
+    }
+    drop(sender);
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    // Ends when both pipes are closed
+    for (stream, line) in receiver {
+        let text = String::from_utf8_lossy(&line);
+        on_line(stream, text.trim_end_matches(['\r', '\n']));
+        match stream {
+            OutputStream::Stdout => stdout.extend(line),
+            OutputStream::Stderr => stderr.extend(line),
+        }
+    }
+    let status = child.wait()?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+fn spawn_line_reader(
+    pipe: impl Read + Send + 'static,
+    stream: OutputStream,
+    sender: Sender<(OutputStream, Vec<u8>)>,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut line = vec![];
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    if sender.send((stream, line)).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    tracing::error!("Failed to read {:?}: {:?}", stream, err);
+                    break;
+                }
+            }
+        }
+    });
+}
+
 pub fn clean_old_logs(
     log_dir: &str,
     retention_days: i64,
@@ -163,6 +302,40 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn lines_are_passed_before_exit() {
+        use std::process::Command;
+        use std::process::Stdio;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        let child = Command::new("sh")
+            .args(["-c", "echo first; echo error >&2; sleep 1; printf last"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        let mut lines = vec![];
+        let output = wait_with_lines(child, |stream, line| {
+            lines.push((stream, line.to_string(), started.elapsed()));
+        })
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"first\nlast");
+        assert_eq!(output.stderr, b"error\n");
+        let first = lines
+            .iter()
+            .find(|(stream, line, _)| *stream == OutputStream::Stdout && line == "first")
+            .unwrap();
+        assert!(first.2 < Duration::from_millis(900));
+        assert!(lines
+            .iter()
+            .any(|(stream, line, _)| *stream == OutputStream::Stderr && line == "error"));
+        assert_eq!(lines.last().unwrap().1, "last");
+    }
+
     #[test]
     fn test_clean_old_logs() {
         let home_dir = dirs::home_dir().unwrap();
@@ -0,0 +1,145 @@
+use regex::Captures;
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+use testing_language_server::spec::AdapterConfiguration;
+
+/// Values of the variables which can be used in `path`, `extra_arg`, `env` and `workspace_dir`
+/// of an adapter.
+pub struct Variables<'a> {
+    /// `${workspaceFolder}`, the workspace folder of the client
+    pub workspace_folder: &'a Path,
+    /// `${workspace}`, the workspace detected by the adapter.
+    /// It is the workspace folder until the workspaces are detected.
+    pub workspace: Option<&'a str>,
+    /// `${file}`, the tested files
+    pub files: &'a [String],
+    /// `${env:VAR}`, looks up `VAR`. It is `process_env` except in tests.
+    pub env: fn(&str) -> Option<String>,
+}
+
+/// The environment variables of the server
+pub fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// The variable which is an argument of its own, and expands to one argument per tested file
+const FILE_ARG: &str = "${file}";
+
+fn variable_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\$\{([^}]*)\}").unwrap())
+}
+
+impl Variables<'_> {
+    /// Expands the variables and a leading `~` in `value`.
+    /// `${env:VAR}` is empty when `VAR` is not set, and unknown variables are kept as they are.
+    pub fn expand(&self, value: &str) -> String {
+        let value = match value.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+                match dirs::home_dir() {
+                    Some(home_dir) => format!("{}{rest}", home_dir.display()),
+                    None => value.to_string(),
+                }
+            }
+            _ => value.to_string(),
+        };
+        variable_pattern()
+            .replace_all(&value, |captures: &Captures| {
+                let name = &captures[1];
+                match name {
+                    "workspaceFolder" => self.workspace_folder.display().to_string(),
+                    "workspace" => self
+                        .workspace
+                        .map_or(self.workspace_folder.display().to_string(), str::to_string),
+                    "file" => self.files.join(" "),
+                    _ => match name.strip_prefix("env:") {
+                        Some(name) => (self.env)(name).unwrap_or_default(),
+                        None => captures[0].to_string(),
+                    },
+                }
+            })
+            .into_owned()
+    }
+
+    /// Copy of `adapter` with the variables expanded.
+    /// An argument which is only `${file}` is repeated for every tested file.
+    pub fn adapter(&self, adapter: &AdapterConfiguration) -> AdapterConfiguration {
+        let mut extra_arg = vec![];
+        for arg in &adapter.extra_arg {
+            if arg == FILE_ARG {
+                extra_arg.extend(self.files.iter().cloned());
+            } else {
+                extra_arg.push(self.expand(arg));
+            }
+        }
+        AdapterConfiguration {
+            path: self.expand(&adapter.path),
+            extra_arg,
+            env: adapter
+                .env
+                .iter()
+                .map(|(key, value)| (key.clone(), self.expand(value)))
+                .collect(),
+            workspace_dir: adapter
+                .workspace_dir
+                .as_ref()
+                .map(|workspace_dir| self.expand(workspace_dir)),
+            ..adapter.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn expand_variables_of_adapter() {
+        let files = ["/demo/a.rs".to_string(), "/demo/b.rs".to_string()];
+        let variables = Variables {
+            workspace_folder: Path::new("/demo"),
+            workspace: Some("/demo/crates/core"),
+            files: &files,
+            env: |name| (name == "PROFILE").then(|| "ci".to_string()),
+        };
+        let adapter = variables.adapter(&AdapterConfiguration {
+            path: "${workspaceFolder}/node_modules/.bin/adapter".to_string(),
+            extra_arg: vec![
+                "--profile=${env:PROFILE}".to_string(),
+                "--root=${workspace}".to_string(),
+                "${file}".to_string(),
+                "--unset=${env:UNSET}".to_string(),
+                "${unknown}".to_string(),
+            ],
+            env: HashMap::from([("CACHE".to_string(), "~/.cache".to_string())]),
+            ..Default::default()
+        });
+        assert_eq!(adapter.path, "/demo/node_modules/.bin/adapter");
+        assert_eq!(
+            adapter.extra_arg,
+            vec![
+                "--profile=ci",
+                "--root=/demo/crates/core",
+                "/demo/a.rs",
+                "/demo/b.rs",
+                "--unset=",
+                "${unknown}",
+            ]
+        );
+        assert_eq!(
+            adapter.env["CACHE"],
+            dirs::home_dir()
+                .unwrap()
+                .join(".cache")
+                .display()
+                .to_string()
+        );
+        assert_eq!(
+            variables.expand("files: ${file}"),
+            "files: /demo/a.rs /demo/b.rs"
+        );
+        assert_eq!(variables.expand("~user/bin"), "~user/bin");
+    }
+}